@@ -1,8 +1,11 @@
 #![no_main]
 use libfuzzer_sys::fuzz_target;
 
-use flavors::parser::complete_tag;
+use flavors::parser::{complete_tag, Parser, ParserOptions};
 
 fuzz_target!(|data: &[u8]| {
     let _tag_result = complete_tag(data);
+    let mut options = ParserOptions::default();
+    options.check_data_size = true;
+    let _checked_result = Parser::with_options(options).complete_tag(data);
 });
@@ -0,0 +1,39 @@
+#![no_std]
+
+use flavors::parser::{
+  audio_data, complete_tag, header, tag_header, video_data, FrameType, TagData, TagType,
+};
+
+/// Returns the timestamp of the first video keyframe of the tags following
+/// the file header.
+pub fn first_keyframe(input: &[u8]) -> Option<u32> {
+  let (_, header) = header(input).ok()?;
+  let mut i = input.get(header.offset as usize + 4..)?;
+
+  loop {
+    let (_, header) = tag_header(i).ok()?;
+    if header.tag_type == TagType::Video {
+      let (_, tag) = complete_tag(i).ok()?;
+      if let TagData::Video(video) = tag.data {
+        if video.frame_type == FrameType::Key {
+          return Some(header.timestamp);
+        }
+      }
+    }
+    i = i.get(11 + header.data_size as usize + 4..)?;
+  }
+}
+
+/// Whether a tag body parses as the audio or video data of its type.
+pub fn is_valid_body(tag_type: TagType, body: &[u8]) -> bool {
+  match tag_type {
+    TagType::Audio => audio_data(body, body.len()).is_ok(),
+    TagType::Video => video_data(body, body.len()).is_ok(),
+    TagType::Script => true,
+  }
+}
+
+#[cfg(feature = "alloc")]
+pub fn script_name(body: &[u8]) -> Option<&str> {
+  flavors::parser::script_data(body).ok().map(|(_, script)| script.name)
+}
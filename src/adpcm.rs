@@ -0,0 +1,188 @@
+use alloc::vec::Vec;
+
+use crate::parser::{AudioData, SoundFormat, SoundType};
+
+const STEP_TABLE: [i32; 89] = [
+  7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66, 73,
+  80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449, 494,
+  544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272, 2499,
+  2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493, 10442, 11487,
+  12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794, 32767,
+];
+
+const INDEX_TABLES: [&[i32]; 4] = [
+  &[-1, 2],
+  &[-1, -1, 2, 4],
+  &[-1, -1, -1, -1, 2, 4, 6, 8],
+  &[-1, -1, -1, -1, -1, -1, -1, -1, 1, 2, 4, 6, 8, 10, 13, 16],
+];
+
+/// Samples per channel following the initial value in a block.
+const BLOCK_SAMPLES: usize = 4095;
+
+struct BitReader<'a> {
+  data: &'a [u8],
+  position: usize,
+}
+
+impl<'a> BitReader<'a> {
+  fn remaining(&self) -> usize {
+    self.data.len() * 8 - self.position
+  }
+
+  fn read(&mut self, count: usize) -> u32 {
+    let mut value = 0;
+    for _ in 0..count {
+      let bit = (self.data[self.position / 8] >> (7 - self.position % 8)) & 1;
+      value = (value << 1) | u32::from(bit);
+      self.position += 1;
+    }
+    value
+  }
+}
+
+/// Decodes the Flash ADPCM `sound_data` of an audio tag to interleaved
+/// 16-bit linear PCM.
+///
+/// The payload starts with a 2-bit field giving the code size (2 to 5 bits),
+/// followed by blocks of up to 4096 samples per channel, each beginning with
+/// a 16-bit initial sample and a 6-bit step index per channel.
+///
+/// Returns an empty `Vec` if the tag is not ADPCM.
+pub fn decode_adpcm(audio: &AudioData<'_>) -> Vec<i16> {
+  if audio.sound_format != SoundFormat::ADPCM || audio.sound_data.is_empty() {
+    return Vec::new();
+  }
+
+  let channels = match audio.sound_type {
+    SoundType::SndMono => 1,
+    SoundType::SndStereo => 2,
+  };
+
+  let mut reader = BitReader {
+    data: audio.sound_data,
+    position: 0,
+  };
+  let code_size = reader.read(2) as usize + 2;
+  let index_table = INDEX_TABLES[code_size - 2];
+  let sign_mask = 1 << (code_size - 1);
+  let first_bit = 1 << (code_size - 2);
+
+  let mut predictors = [0i32; 2];
+  let mut step_indexes = [0i32; 2];
+  let mut samples = Vec::new();
+
+  while reader.remaining() >= 22 * channels {
+    for channel in 0..channels {
+      predictors[channel] = i32::from(reader.read(16) as u16 as i16);
+      step_indexes[channel] = reader.read(6) as i32;
+      samples.push(predictors[channel] as i16);
+    }
+
+    let mut count = 0;
+    while reader.remaining() >= code_size * channels && count < BLOCK_SAMPLES {
+      for channel in 0..channels {
+        let code = reader.read(code_size);
+        let mut step = STEP_TABLE[step_indexes[channel] as usize];
+
+        // difference = (code + 0.5) * step / 2^(code_size - 2)
+        let mut difference = 0;
+        let mut bit = first_bit;
+        while bit != 0 {
+          if code & bit != 0 {
+            difference += step;
+          }
+          step >>= 1;
+          bit >>= 1;
+        }
+        difference += step;
+
+        if code & sign_mask != 0 {
+          predictors[channel] -= difference;
+        } else {
+          predictors[channel] += difference;
+        }
+        predictors[channel] = predictors[channel].clamp(i16::MIN.into(), i16::MAX.into());
+
+        step_indexes[channel] += index_table[(code & !sign_mask) as usize];
+        step_indexes[channel] = step_indexes[channel].clamp(0, 88);
+
+        samples.push(predictors[channel] as i16);
+      }
+      count += 1;
+    }
+  }
+
+  samples
+}
+
+#[allow(non_upper_case_globals)]
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::{audio_data, raw_tags, SoundRate, SoundSize, TagType};
+  use alloc::vec;
+
+  const zelda: &[u8] = include_bytes!("../assets/zelda.flv");
+
+  #[test]
+  fn zelda_first_audio_tag() {
+    let body = raw_tags(zelda)
+      .find(|tag| tag.header.tag_type == TagType::Audio)
+      .and_then(|tag| tag.body())
+      .unwrap();
+    let (_, audio) = audio_data(body, body.len()).unwrap();
+    let samples = decode_adpcm(&audio);
+
+    // 5-bit codes, one mono block: the initial sample then (5128 - 24) / 5
+    assert_eq!(samples.len(), 1021);
+    assert_eq!(samples[0], 0);
+  }
+
+  #[test]
+  fn stereo_two_bit_block() {
+    // 2-bit codes, left starts at 256 / step index 0, right at -256 / step
+    // index 63, followed by one sample pair: left 0b01, right 0b11, then six
+    // bits of zero padding which still decode as one more pair
+    let bits = [
+      "00",
+      "0000000100000000",
+      "000000",
+      "1111111100000000",
+      "111111",
+      "01",
+      "11",
+      "000000",
+    ]
+    .concat();
+    let sound_data: Vec<u8> = (0..bits.len() / 8)
+      .map(|i| u8::from_str_radix(&bits[i * 8..i * 8 + 8], 2).unwrap())
+      .collect();
+
+    let audio = AudioData {
+      sound_format: SoundFormat::ADPCM,
+      sound_rate: SoundRate::_22KHZ,
+      sound_size: SoundSize::Snd16bit,
+      sound_type: SoundType::SndStereo,
+      sound_data: &sound_data,
+    };
+    // left: step 7, adds 7 + 3, then step 9 adds 4
+    // right: step 3024, removes 3024 + 1512, then step 3660 adds 1830
+    assert_eq!(
+      decode_adpcm(&audio),
+      vec![256, -256, 266, -4792, 270, -2962]
+    );
+  }
+
+  #[test]
+  fn not_adpcm() {
+    let audio = AudioData {
+      sound_format: SoundFormat::MP3,
+      sound_rate: SoundRate::_44KHZ,
+      sound_size: SoundSize::Snd16bit,
+      sound_type: SoundType::SndStereo,
+      sound_data: &[0xff, 0xfb],
+    };
+    assert!(decode_adpcm(&audio).is_empty());
+  }
+}
@@ -0,0 +1,420 @@
+use alloc::vec::Vec;
+use core::str::from_utf8;
+
+use nom::bytes::streaming::take;
+use nom::error::ErrorKind;
+use nom::number::streaming::{be_f64, be_u8};
+use nom::{Err, IResult};
+
+use crate::error::FlvError;
+use crate::parser::{check_array_len, ParserOptions};
+
+/// An AMF3 value, found in script data after the AMF0 `avmplus-object-marker`
+/// (17).
+///
+/// Object and array references are kept as `Reference` with their index in
+/// the object table, like AMF0 references. String and traits references are
+/// resolved.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AMF3Value<'a> {
+  Undefined,
+  Null,
+  Boolean(bool),
+  /// A 29-bit signed integer.
+  Integer(i32),
+  Double(f64),
+  String(&'a str),
+  /// A string value that is not UTF-8, kept with
+  /// `ParserOptions::raw_invalid_strings`.
+  RawString(&'a [u8]),
+  Array {
+    associative: Vec<AMF3Property<'a>>,
+    dense: Vec<AMF3Value<'a>>,
+  },
+  Object {
+    /// Empty for anonymous objects.
+    class_name: &'a str,
+    sealed: Vec<AMF3Property<'a>>,
+    /// `None` if the object traits are not dynamic.
+    dynamic: Option<Vec<AMF3Property<'a>>>,
+  },
+  Reference(u32),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AMF3Property<'a> {
+  pub name: &'a str,
+  pub value: AMF3Value<'a>,
+}
+
+struct Traits<'a> {
+  class_name: &'a str,
+  dynamic: bool,
+  sealed: Vec<&'a str>,
+}
+
+/// The string and traits reference tables, which start empty for each AMF3
+/// value switched to from AMF0, and the options of the AMF0 parser.
+struct Context<'a> {
+  /// Not checked for UTF-8, since raw string values are referenced too.
+  strings: Vec<&'a [u8]>,
+  traits: Vec<Traits<'a>>,
+  options: ParserOptions,
+}
+
+fn error<T>() -> Result<T, Err<FlvError>> {
+  Err(Err::Error(FlvError::Nom(ErrorKind::Alt)))
+}
+
+/// Parses a variable length unsigned 29-bit integer: up to 3 bytes with 7
+/// bits and a continuation flag, then a last byte with 8 bits.
+pub fn u29(input: &[u8]) -> IResult<&[u8], u32, FlvError> {
+  let mut value = 0;
+  let mut i = input;
+  for _ in 0..3 {
+    let (rest, byte) = be_u8(i)?;
+    i = rest;
+    value = (value << 7) | u32::from(byte & 0x7f);
+    if byte & 0x80 == 0 {
+      return Ok((i, value));
+    }
+  }
+  let (i, byte) = be_u8(i)?;
+  Ok((i, (value << 8) | u32::from(byte)))
+}
+
+/// Parses the bytes of a string, or of the string it references.
+fn bytes<'a>(input: &'a [u8], context: &mut Context<'a>) -> IResult<&'a [u8], &'a [u8], FlvError> {
+  let (i, header) = u29(input)?;
+  if header & 1 == 0 {
+    return match context.strings.get((header >> 1) as usize) {
+      Some(bytes) => Ok((i, bytes)),
+      None => error(),
+    };
+  }
+  let declared = (header >> 1) as usize;
+  if context.options.strict_lengths && declared > i.len() {
+    return Err(Err::Error(FlvError::BadLength {
+      field: "string",
+      declared,
+      available: i.len(),
+    }));
+  }
+  let (i, bytes) = take(declared)(i)?;
+  // the empty string is never sent by reference
+  if !bytes.is_empty() {
+    context.strings.push(bytes);
+  }
+  Ok((i, bytes))
+}
+
+fn string<'a>(input: &'a [u8], context: &mut Context<'a>) -> IResult<&'a [u8], &'a str, FlvError> {
+  let (i, bytes) = bytes(input, context)?;
+  let s = from_utf8(bytes).map_err(|_| Err::Error(FlvError::Nom(ErrorKind::MapRes)))?;
+  Ok((i, s))
+}
+
+/// Parses name and value pairs up to the empty name.
+fn properties<'a>(
+  input: &'a [u8],
+  context: &mut Context<'a>,
+) -> IResult<&'a [u8], Vec<AMF3Property<'a>>, FlvError> {
+  let mut properties = Vec::new();
+  let mut input = input;
+  loop {
+    let (i, name) = string(input, context)?;
+    if name.is_empty() {
+      return Ok((i, properties));
+    }
+    let (i, value) = value(i, context)?;
+    properties.push(AMF3Property { name, value });
+    input = i;
+  }
+}
+
+fn array<'a>(
+  input: &'a [u8],
+  context: &mut Context<'a>,
+) -> IResult<&'a [u8], AMF3Value<'a>, FlvError> {
+  let (i, header) = u29(input)?;
+  if header & 1 == 0 {
+    return Ok((i, AMF3Value::Reference(header >> 1)));
+  }
+  check_array_len(header >> 1, &context.options)?;
+  let (mut i, associative) = properties(i, context)?;
+  let mut dense = Vec::new();
+  for _ in 0..header >> 1 {
+    let (rest, value) = value(i, context)?;
+    dense.push(value);
+    i = rest;
+  }
+  Ok((i, AMF3Value::Array { associative, dense }))
+}
+
+fn object<'a>(
+  input: &'a [u8],
+  context: &mut Context<'a>,
+) -> IResult<&'a [u8], AMF3Value<'a>, FlvError> {
+  let (mut i, header) = u29(input)?;
+  if header & 1 == 0 {
+    return Ok((i, AMF3Value::Reference(header >> 1)));
+  }
+  let index = if header & 2 == 0 {
+    let index = (header >> 2) as usize;
+    if index >= context.traits.len() {
+      return error();
+    }
+    index
+  } else {
+    // externalizable objects need the class to be read
+    if header & 4 != 0 {
+      return error();
+    }
+    let (rest, class_name) = string(i, context)?;
+    i = rest;
+    let mut sealed = Vec::new();
+    for _ in 0..header >> 4 {
+      let (rest, name) = string(i, context)?;
+      sealed.push(name);
+      i = rest;
+    }
+    context.traits.push(Traits {
+      class_name,
+      dynamic: header & 8 != 0,
+      sealed,
+    });
+    context.traits.len() - 1
+  };
+
+  let class_name = context.traits[index].class_name;
+  let dynamic = context.traits[index].dynamic;
+  let names = context.traits[index].sealed.clone();
+  let mut sealed = Vec::new();
+  for name in names {
+    let (rest, value) = value(i, context)?;
+    sealed.push(AMF3Property { name, value });
+    i = rest;
+  }
+  let (i, dynamic) = if dynamic {
+    let (i, properties) = properties(i, context)?;
+    (i, Some(properties))
+  } else {
+    (i, None)
+  };
+
+  Ok((
+    i,
+    AMF3Value::Object {
+      class_name,
+      sealed,
+      dynamic,
+    },
+  ))
+}
+
+fn value<'a>(
+  input: &'a [u8],
+  context: &mut Context<'a>,
+) -> IResult<&'a [u8], AMF3Value<'a>, FlvError> {
+  let (i, marker) = be_u8(input)?;
+  match marker {
+    0 => Ok((i, AMF3Value::Undefined)),
+    1 => Ok((i, AMF3Value::Null)),
+    2 => Ok((i, AMF3Value::Boolean(false))),
+    3 => Ok((i, AMF3Value::Boolean(true))),
+    4 => {
+      let (i, n) = u29(i)?;
+      // sign extend from 29 bits
+      Ok((i, AMF3Value::Integer(((n << 3) as i32) >> 3)))
+    }
+    5 => {
+      let (i, n) = be_f64(i)?;
+      Ok((i, AMF3Value::Double(n)))
+    }
+    6 if context.options.raw_invalid_strings => {
+      let (i, bytes) = bytes(i, context)?;
+      match from_utf8(bytes) {
+        Ok(s) => Ok((i, AMF3Value::String(s))),
+        Err(_) => Ok((i, AMF3Value::RawString(bytes))),
+      }
+    }
+    6 => {
+      let (i, s) = string(i, context)?;
+      Ok((i, AMF3Value::String(s)))
+    }
+    9 => array(i, context),
+    10 => object(i, context),
+    _ => error(),
+  }
+}
+
+/// Parses an AMF3 value, type marker included, with empty reference tables.
+pub fn script_data_amf3_value(input: &[u8]) -> IResult<&[u8], AMF3Value<'_>, FlvError> {
+  amf3_value(input, &ParserOptions::default())
+}
+
+/// `script_data_amf3_value` using the options of the enclosing AMF0 parser:
+/// `max_array_len` bounds the dense part of arrays, `strict_lengths` string
+/// lengths and `raw_invalid_strings` applies to string values.
+pub(crate) fn amf3_value<'a>(
+  input: &'a [u8],
+  options: &ParserOptions,
+) -> IResult<&'a [u8], AMF3Value<'a>, FlvError> {
+  let mut context = Context {
+    strings: Vec::new(),
+    traits: Vec::new(),
+    options: *options,
+  };
+  value(input, &mut context)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use alloc::vec;
+  use nom::Needed;
+
+  #[test]
+  fn integers() {
+    assert_eq!(u29(&[0x7f]), Ok((&b""[..], 0x7f)));
+    assert_eq!(u29(&[0x81, 0x00]), Ok((&b""[..], 0x80)));
+    assert_eq!(u29(&[0xff, 0xff, 0x7f]), Ok((&b""[..], 0x1f_ffff)));
+    assert_eq!(u29(&[0xff, 0xff, 0xff, 0xff]), Ok((&b""[..], 0x1fff_ffff)));
+    assert_eq!(u29(&[0x81]), Err(Err::Incomplete(Needed::new(1))));
+
+    assert_eq!(
+      script_data_amf3_value(&[4, 0xff, 0xff, 0xff, 0xff]),
+      Ok((&b""[..], AMF3Value::Integer(-1)))
+    );
+    assert_eq!(
+      script_data_amf3_value(&[4, 0xbf, 0xff, 0xff, 0xff]),
+      Ok((&b""[..], AMF3Value::Integer(0x0fff_ffff)))
+    );
+  }
+
+  #[test]
+  fn references() {
+    // a dynamic anonymous object, then the same traits and a string reference
+    let input = [
+      9, 0x05, 0x01, // dense array of 2
+      10, 0x0b, 0x01, 0x03, b'a', 4, 1, 0x01, // {a: 1}
+      10, 0x01, 0x00, 6, 0x00, 0x01, // {a: "a"}
+      9, 0x02, // reference to the array
+    ];
+    let (rest, value) = script_data_amf3_value(&input[..input.len() - 2]).unwrap();
+    assert!(rest.is_empty());
+    let object = |value| AMF3Value::Object {
+      class_name: "",
+      sealed: vec![],
+      dynamic: Some(vec![AMF3Property { name: "a", value }]),
+    };
+    assert_eq!(
+      value,
+      AMF3Value::Array {
+        associative: vec![],
+        dense: vec![
+          object(AMF3Value::Integer(1)),
+          object(AMF3Value::String("a"))
+        ],
+      }
+    );
+    assert_eq!(
+      script_data_amf3_value(&input[input.len() - 2..]),
+      Ok((&b""[..], AMF3Value::Reference(1)))
+    );
+
+    // unknown string and traits references
+    assert!(script_data_amf3_value(&[6, 0x00]).is_err());
+    assert!(script_data_amf3_value(&[10, 0x01]).is_err());
+  }
+
+  #[test]
+  fn typed_object() {
+    // sealed members x and y, not dynamic
+    let input = [
+      10, 0x23, 0x0b, b'P', b'o', b'i', b'n', b't', 0x03, b'x', 0x03, b'y', 4, 1, 4, 2,
+    ];
+    assert_eq!(
+      script_data_amf3_value(&input),
+      Ok((
+        &b""[..],
+        AMF3Value::Object {
+          class_name: "Point",
+          sealed: vec![
+            AMF3Property {
+              name: "x",
+              value: AMF3Value::Integer(1),
+            },
+            AMF3Property {
+              name: "y",
+              value: AMF3Value::Integer(2),
+            },
+          ],
+          dynamic: None,
+        }
+      ))
+    );
+    // externalizable
+    assert!(script_data_amf3_value(&[10, 0x07, 0x01]).is_err());
+  }
+
+  #[test]
+  fn options() {
+    // a dense array of 2 strings, the second one not UTF-8
+    let input = [9, 0x05, 0x01, 6, 0x03, b'a', 6, 0x03, 0xe9];
+    assert!(script_data_amf3_value(&input).is_err());
+
+    let options = ParserOptions {
+      raw_invalid_strings: true,
+      ..ParserOptions::default()
+    };
+    assert_eq!(
+      amf3_value(&input, &options),
+      Ok((
+        &b""[..],
+        AMF3Value::Array {
+          associative: vec![],
+          dense: vec![AMF3Value::String("a"), AMF3Value::RawString(b"\xe9")],
+        }
+      ))
+    );
+    // a raw string is referenced like any other, but is no property name
+    assert_eq!(
+      amf3_value(&[9, 0x05, 0x01, 6, 0x03, 0xe9, 6, 0x00], &options),
+      Ok((
+        &b""[..],
+        AMF3Value::Array {
+          associative: vec![],
+          dense: vec![AMF3Value::RawString(b"\xe9"), AMF3Value::RawString(b"\xe9")],
+        }
+      ))
+    );
+    assert!(amf3_value(&[10, 0x0b, 0x01, 0x03, 0xe9, 1, 0x01], &options).is_err());
+
+    let options = ParserOptions {
+      max_array_len: 1,
+      ..ParserOptions::default()
+    };
+    assert_eq!(
+      amf3_value(&input, &options),
+      Err(Err::Error(FlvError::ArrayTooLarge { len: 2, max: 1 }))
+    );
+
+    let options = ParserOptions {
+      strict_lengths: true,
+      ..ParserOptions::default()
+    };
+    assert_eq!(
+      amf3_value(&[6, 0x07, b'a'], &options),
+      Err(Err::Error(FlvError::BadLength {
+        field: "string",
+        declared: 3,
+        available: 1,
+      }))
+    );
+    assert_eq!(
+      script_data_amf3_value(&[6, 0x07, b'a']),
+      Err(Err::Incomplete(Needed::new(2)))
+    );
+  }
+}
@@ -0,0 +1,377 @@
+#[cfg(feature = "debug")]
+use alloc::boxed::Box;
+#[cfg(feature = "debug")]
+use alloc::vec::Vec;
+use core::fmt;
+
+use nom::error::{ErrorKind, FromExternalError, ParseError};
+use nom::{ErrorConvert, Needed};
+
+use crate::parser::{CodecId, SoundFormat, TagType};
+
+/// Error type used by every parser in this crate, in place of nom's
+/// `nom::error::Error<&[u8]>`.
+///
+/// It does not borrow the input, so it can be stored and passed around
+/// freely by callers that don't depend on nom themselves.
+///
+/// Some variants only exist with the `std` or `debug` features, and new ones
+/// may be added, so matches need a wildcard arm.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FlvError {
+  /// A nom combinator failed.
+  Nom(ErrorKind),
+  /// A length prefix, or a field of fixed size, needs more bytes than the
+  /// input holds.
+  BadLength {
+    field: &'static str,
+    declared: usize,
+    available: usize,
+  },
+  /// The input ended before the parser could complete, when converting a
+  /// `nom::Err::Incomplete`.
+  Incomplete(Needed),
+  /// The tag type byte is not 8, 9 or 18.
+  UnknownTagType(u8),
+  /// The sound format nibble is one of the reserved 12 or 13. 9 only gets
+  /// here through `SoundFormat::try_from`: the audio parsers reject it first,
+  /// as an enhanced RTMP header, see `is_ex_audio_header`.
+  UnknownSoundFormat(u8),
+  /// The video codec id does not fit in 4 bits, or is reserved where a
+  /// specific codec is expected.
+  UnknownCodecId(u8),
+  /// The codec is only signaled by a FourCC in enhanced RTMP, so it has no
+  /// codec id to write in a legacy video tag.
+  NoCodecId(CodecId),
+  /// The video frame type is not between 1 and 5.
+  UnknownFrameType(u8),
+  /// The packet type of an AAC, AVC or HEVC packet, or the packet type
+  /// nibble of an enhanced RTMP tag, is not one the specification defines.
+  UnknownPacketType(u8),
+  /// The packet type is a known one that the parser does not accept, like
+  /// a multitrack packet given to `ex_video_tag_header` or a
+  /// `MultichannelConfig` packet given to `opus_audio_packet`.
+  UnexpectedPacketType(u8),
+  /// The multitrack type nibble of an enhanced RTMP multitrack tag is not
+  /// between 0 and 2.
+  UnknownMultitrackType(u8),
+  /// An enhanced RTMP tag carries a FourCC other than the one of the codec
+  /// it is parsed as.
+  FourCcMismatch { expected: [u8; 4], found: [u8; 4] },
+  /// An audio tag is not of the sound format it is parsed as.
+  UnexpectedSoundFormat(SoundFormat),
+  /// A video tag is not of the codec it is parsed as.
+  UnexpectedCodecId(CodecId),
+  /// The rate, size or type flags of an audio tag are not the ones its
+  /// sound format requires, like 5.5 kHz, 16-bit mono for Speex.
+  UnexpectedSoundFlags(SoundFormat),
+  /// A payload made of fixed size blocks, like the 64-byte Nellymoser ones,
+  /// is empty or not a whole number of them.
+  PartialBlock { len: usize, block_size: usize },
+  /// The encryption header of a filtered tag does not hold the single
+  /// filter the specification defines.
+  UnsupportedFilterCount(u8),
+  /// A tag header declares a `data_size` larger than the rest of the input,
+  /// see `ParserOptions::check_data_size`.
+  SizeMismatch { declared: usize, available: usize },
+  /// A field the specification reserves, named after it, is not zero, with
+  /// `ParserOptions::strict_reserved`.
+  ReservedBitsSet(&'static str),
+  /// An AVC sequence header or end of sequence packet has a non zero
+  /// composition time, with `ParserOptions::strict_composition_time`.
+  NonZeroCompositionTime(i32),
+  /// An AMF0 strict array, or the dense part of an AMF3 array, declares more
+  /// elements than `ParserOptions::max_array_len`.
+  ArrayTooLarge { len: u32, max: u32 },
+  /// A script data string in a tag body declares more bytes than are left
+  /// in the body, so it would read into the next tag.
+  StringOverrun { declared: usize, available: usize },
+  /// The file header version is not 1, with `ParserOptions::strict_header`.
+  UnsupportedVersion(u8),
+  /// The file header data offset is smaller than the header, with
+  /// `ParserOptions::strict_header`.
+  BadDataOffset(u32),
+  /// The PreviousTagSize after the file header is not 0, with
+  /// `ParserOptions::strict_initial_tag_size`.
+  NonZeroInitialTagSize(u32),
+  /// An audio or video tag in a file whose header does not announce that
+  /// kind of tag, with `ParserOptions::strict_tag_types`.
+  UnexpectedTagType(TagType),
+  /// Reading the input failed, in `stream::FlvStream`.
+  #[cfg(feature = "std")]
+  Io(std::io::ErrorKind),
+  /// An error along with where it happened, see `FlvError::with_context`.
+  /// The tag iterators only return it with `ParserOptions::error_context`,
+  /// match on `FlvError::root` then.
+  #[cfg(feature = "debug")]
+  Context {
+    offset: usize,
+    /// Up to `CONTEXT_BEFORE` bytes before `offset`, then up to
+    /// `CONTEXT_AFTER` bytes from it.
+    snippet: Vec<u8>,
+    error: Box<FlvError>,
+  },
+}
+
+impl FlvError {
+  /// How many bytes before the failure offset `with_context` keeps.
+  #[cfg(feature = "debug")]
+  pub const CONTEXT_BEFORE: usize = 8;
+  /// How many bytes from the failure offset `with_context` keeps.
+  #[cfg(feature = "debug")]
+  pub const CONTEXT_AFTER: usize = 16;
+
+  /// Wraps this error with `offset` in `input` and a copy of the bytes
+  /// around it. An error that already has a context is left as is.
+  #[cfg(feature = "debug")]
+  pub fn with_context(self, input: &[u8], offset: usize) -> FlvError {
+    if let FlvError::Context { .. } = self {
+      return self;
+    }
+    let offset = offset.min(input.len());
+    let start = offset.saturating_sub(Self::CONTEXT_BEFORE);
+    let end = input.len().min(offset + Self::CONTEXT_AFTER);
+    FlvError::Context {
+      offset,
+      snippet: input[start..end].to_vec(),
+      error: Box::new(self),
+    }
+  }
+
+  /// The error itself, without the context added by `with_context`.
+  pub fn root(&self) -> &FlvError {
+    match self {
+      #[cfg(feature = "debug")]
+      FlvError::Context { error, .. } => error,
+      _ => self,
+    }
+  }
+}
+
+impl<I> ParseError<I> for FlvError {
+  fn from_error_kind(_input: I, kind: ErrorKind) -> Self {
+    FlvError::Nom(kind)
+  }
+
+  fn append(_input: I, _kind: ErrorKind, other: Self) -> Self {
+    other
+  }
+}
+
+impl<I> FromExternalError<I, FlvError> for FlvError {
+  fn from_external_error(_input: I, _kind: ErrorKind, e: FlvError) -> Self {
+    e
+  }
+}
+
+impl<I> FromExternalError<I, core::str::Utf8Error> for FlvError {
+  fn from_external_error(_input: I, kind: ErrorKind, _e: core::str::Utf8Error) -> Self {
+    FlvError::Nom(kind)
+  }
+}
+
+impl ErrorConvert<FlvError> for FlvError {
+  fn convert(self) -> FlvError {
+    self
+  }
+}
+
+impl From<nom::Err<FlvError>> for FlvError {
+  fn from(e: nom::Err<FlvError>) -> Self {
+    match e {
+      nom::Err::Incomplete(needed) => FlvError::Incomplete(needed),
+      nom::Err::Error(e) | nom::Err::Failure(e) => e,
+    }
+  }
+}
+
+/// Converts errors from nom's own error type, borrowing the input or owning
+/// it after `nom::Err::to_owned`.
+impl<I> From<nom::Err<nom::error::Error<I>>> for FlvError {
+  fn from(e: nom::Err<nom::error::Error<I>>) -> Self {
+    match e {
+      nom::Err::Incomplete(needed) => FlvError::Incomplete(needed),
+      nom::Err::Error(e) | nom::Err::Failure(e) => FlvError::Nom(e.code),
+    }
+  }
+}
+
+impl fmt::Display for FlvError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      FlvError::Nom(kind) => write!(f, "parser error: {}", kind.description()),
+      FlvError::BadLength {
+        field,
+        declared,
+        available,
+      } => write!(
+        f,
+        "{} declares {} bytes but only {} are available",
+        field, declared, available
+      ),
+      FlvError::Incomplete(Needed::Size(size)) => {
+        write!(f, "incomplete input: {} more bytes needed", size)
+      }
+      FlvError::Incomplete(Needed::Unknown) => write!(f, "incomplete input"),
+      FlvError::UnknownTagType(tag_type) => write!(f, "unknown tag type {}", tag_type),
+      FlvError::UnknownSoundFormat(sound_format) => {
+        write!(f, "unknown sound format {}", sound_format)
+      }
+      FlvError::UnknownCodecId(codec_id) => write!(f, "unknown codec id {}", codec_id),
+      FlvError::NoCodecId(codec_id) => write!(f, "{:?} has no codec id", codec_id),
+      FlvError::UnknownFrameType(frame_type) => write!(f, "unknown frame type {}", frame_type),
+      FlvError::UnknownPacketType(packet_type) => write!(f, "unknown packet type {}", packet_type),
+      FlvError::UnexpectedPacketType(packet_type) => {
+        write!(f, "packet type {} is not accepted here", packet_type)
+      }
+      FlvError::UnknownMultitrackType(multitrack_type) => {
+        write!(f, "unknown multitrack type {}", multitrack_type)
+      }
+      FlvError::FourCcMismatch { expected, found } => {
+        write!(f, "expected FourCC ")?;
+        write_fourcc(f, expected)?;
+        write!(f, ", found ")?;
+        write_fourcc(f, found)
+      }
+      FlvError::UnexpectedSoundFormat(sound_format) => {
+        write!(f, "unexpected sound format {:?}", sound_format)
+      }
+      FlvError::UnexpectedCodecId(codec_id) => write!(f, "unexpected codec {:?}", codec_id),
+      FlvError::UnexpectedSoundFlags(sound_format) => {
+        write!(f, "unexpected sound flags for {:?}", sound_format)
+      }
+      FlvError::PartialBlock { len, block_size } => write!(
+        f,
+        "payload of {} bytes is not a whole number of {} byte blocks",
+        len, block_size
+      ),
+      FlvError::UnsupportedFilterCount(count) => {
+        write!(f, "{} filters instead of 1 in the encryption header", count)
+      }
+      FlvError::SizeMismatch {
+        declared,
+        available,
+      } => write!(
+        f,
+        "tag declares {} data bytes but only {} are available",
+        declared, available
+      ),
+      FlvError::ReservedBitsSet(field) => write!(f, "reserved field {} is not zero", field),
+      FlvError::NonZeroCompositionTime(time) => {
+        write!(f, "composition time {} of a non NALU AVC packet", time)
+      }
+      FlvError::ArrayTooLarge { len, max } => {
+        write!(f, "array of {} elements is over the limit of {}", len, max)
+      }
+      FlvError::StringOverrun {
+        declared,
+        available,
+      } => write!(
+        f,
+        "string declares {} bytes but only {} are left in the tag",
+        declared, available
+      ),
+      FlvError::UnsupportedVersion(version) => write!(f, "unsupported FLV version {}", version),
+      FlvError::BadDataOffset(offset) => write!(f, "data offset {} is inside the header", offset),
+      FlvError::NonZeroInitialTagSize(size) => {
+        write!(f, "first PreviousTagSize is {} instead of 0", size)
+      }
+      FlvError::UnexpectedTagType(tag_type) => {
+        write!(f, "{:?} tag not announced by the file header", tag_type)
+      }
+      #[cfg(feature = "std")]
+      FlvError::Io(kind) => write!(f, "read error: {}", kind),
+      #[cfg(feature = "debug")]
+      FlvError::Context {
+        offset,
+        snippet,
+        error,
+      } => {
+        write!(f, "{} at offset {}:", error, offset)?;
+        for byte in snippet {
+          write!(f, " {:02x}", byte)?;
+        }
+        Ok(())
+      }
+    }
+  }
+}
+
+/// Writes a FourCC as text, escaping the bytes that are not printable ASCII.
+fn write_fourcc(f: &mut fmt::Formatter<'_>, fourcc: &[u8; 4]) -> fmt::Result {
+  for byte in fourcc {
+    write!(f, "{}", core::ascii::escape_default(*byte))?;
+  }
+  Ok(())
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FlvError {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::header;
+
+  fn parse_header(input: &[u8]) -> Result<u8, FlvError> {
+    let (_, header) = header(input)?;
+    Ok(header.version)
+  }
+
+  #[test]
+  fn from_nom_err() {
+    assert_eq!(parse_header(b"FLV\x01\x05\0\0\0\x09"), Ok(1));
+    assert_eq!(
+      parse_header(b"FLV\x01"),
+      Err(FlvError::Incomplete(Needed::new(1)))
+    );
+    assert_eq!(
+      parse_header(b"RIFF\x01\x05\0\0\0\x09"),
+      Err(FlvError::Nom(ErrorKind::Tag))
+    );
+
+    let error = || nom::Err::Error(nom::error::Error::new(&b"abc"[..], ErrorKind::Tag));
+    assert_eq!(FlvError::from(error()), FlvError::Nom(ErrorKind::Tag));
+    #[cfg(feature = "alloc")]
+    assert_eq!(
+      FlvError::from(error().to_owned()),
+      FlvError::Nom(ErrorKind::Tag)
+    );
+  }
+
+  #[cfg(feature = "debug")]
+  #[test]
+  fn with_context() {
+    let input: Vec<u8> = (0..32).collect();
+    let error = FlvError::UnknownTagType(3).with_context(&input, 10);
+    assert_eq!(
+      error,
+      FlvError::Context {
+        offset: 10,
+        snippet: (2..26).collect(),
+        error: Box::new(FlvError::UnknownTagType(3)),
+      }
+    );
+    assert_eq!(error.root(), &FlvError::UnknownTagType(3));
+    assert_eq!(error.clone().with_context(&input, 0), error);
+
+    let error = FlvError::Nom(ErrorKind::Tag).with_context(&input[..3], 1);
+    assert_eq!(error.to_string(), "parser error: Tag at offset 1: 00 01 02");
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn fourcc_display() {
+    use alloc::string::ToString;
+
+    let error = FlvError::FourCcMismatch {
+      expected: *b"Opus",
+      found: *b"\0\x01ab",
+    };
+    assert_eq!(
+      error.to_string(),
+      "expected FourCC Opus, found \\x00\\x01ab"
+    );
+  }
+}
@@ -0,0 +1,236 @@
+use alloc::vec::Vec;
+
+/// Reads the bits of a NAL unit payload, with the emulation prevention bytes
+/// already removed. Every read returns `None` past the end of the data.
+struct RbspReader {
+  data: Vec<u8>,
+  position: usize,
+}
+
+impl RbspReader {
+  fn new(nal: &[u8]) -> RbspReader {
+    let mut data = Vec::with_capacity(nal.len());
+    let mut zeros = 0;
+    for &byte in nal {
+      if zeros >= 2 && byte == 3 {
+        zeros = 0;
+        continue;
+      }
+      zeros = if byte == 0 { zeros + 1 } else { 0 };
+      data.push(byte);
+    }
+    RbspReader { data, position: 0 }
+  }
+
+  fn read(&mut self, count: usize) -> Option<u32> {
+    let mut value = 0;
+    for _ in 0..count {
+      let byte = self.data.get(self.position / 8)?;
+      let bit = (byte >> (7 - self.position % 8)) & 1;
+      value = (value << 1) | u32::from(bit);
+      self.position += 1;
+    }
+    Some(value)
+  }
+
+  fn flag(&mut self) -> Option<bool> {
+    self.read(1).map(|bit| bit == 1)
+  }
+
+  /// Unsigned Exp-Golomb code.
+  fn ue(&mut self) -> Option<u32> {
+    let mut leading_zeros = 0;
+    while !self.flag()? {
+      leading_zeros += 1;
+      if leading_zeros > 31 {
+        return None;
+      }
+    }
+    Some((1 << leading_zeros) - 1 + self.read(leading_zeros)?)
+  }
+
+  /// Signed Exp-Golomb code.
+  fn se(&mut self) -> Option<i32> {
+    let code = self.ue()?;
+    let value = code.div_ceil(2) as i32;
+    Some(if code % 2 == 1 { value } else { -value })
+  }
+}
+
+/// VUI timing information of a sequence parameter set.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpsTiming {
+  pub num_units_in_tick: u32,
+  pub time_scale: u32,
+  pub fixed_frame_rate: bool,
+}
+
+impl SpsTiming {
+  /// Frames per second: a frame lasts two ticks, one per field.
+  pub fn frame_rate(&self) -> Option<f64> {
+    if self.num_units_in_tick == 0 || self.time_scale == 0 {
+      return None;
+    }
+    Some(f64::from(self.time_scale) / (2.0 * f64::from(self.num_units_in_tick)))
+  }
+}
+
+fn skip_scaling_list(reader: &mut RbspReader, size: usize) -> Option<()> {
+  let mut last_scale = 8i64;
+  let mut next_scale = 8i64;
+  for _ in 0..size {
+    if next_scale != 0 {
+      // a corrupt delta can be far outside of the -128..127 range
+      next_scale = (last_scale + i64::from(reader.se()?)).rem_euclid(256);
+    }
+    if next_scale != 0 {
+      last_scale = next_scale;
+    }
+  }
+  Some(())
+}
+
+/// Reads the VUI timing information of a sequence parameter set NAL unit,
+/// NAL header byte included, as stored in the AVC decoder configuration
+/// record. Returns `None` if the SPS has no timing information or is cut
+/// short.
+pub fn sps_timing(sps: &[u8]) -> Option<SpsTiming> {
+  let mut reader = RbspReader::new(sps);
+  // NAL header, then constraint flags and level around the profile
+  let _nal_header = reader.read(8)?;
+  let profile_idc = reader.read(8)?;
+  reader.read(16)?;
+  let _seq_parameter_set_id = reader.ue()?;
+
+  if matches!(
+    profile_idc,
+    100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 | 138 | 139 | 134 | 135
+  ) {
+    let chroma_format_idc = reader.ue()?;
+    if chroma_format_idc == 3 {
+      let _separate_colour_plane = reader.flag()?;
+    }
+    let _bit_depth_luma_minus8 = reader.ue()?;
+    let _bit_depth_chroma_minus8 = reader.ue()?;
+    let _qpprime_y_zero_transform_bypass = reader.flag()?;
+    if reader.flag()? {
+      let lists = if chroma_format_idc == 3 { 12 } else { 8 };
+      for i in 0..lists {
+        if reader.flag()? {
+          skip_scaling_list(&mut reader, if i < 6 { 16 } else { 64 })?;
+        }
+      }
+    }
+  }
+
+  let _log2_max_frame_num_minus4 = reader.ue()?;
+  match reader.ue()? {
+    0 => {
+      let _log2_max_pic_order_cnt_lsb_minus4 = reader.ue()?;
+    }
+    1 => {
+      let _delta_pic_order_always_zero = reader.flag()?;
+      let _offset_for_non_ref_pic = reader.se()?;
+      let _offset_for_top_to_bottom_field = reader.se()?;
+      for _ in 0..reader.ue()? {
+        let _offset_for_ref_frame = reader.se()?;
+      }
+    }
+    _ => {}
+  }
+  let _max_num_ref_frames = reader.ue()?;
+  let _gaps_in_frame_num_value_allowed = reader.flag()?;
+  let _pic_width_in_mbs_minus1 = reader.ue()?;
+  let _pic_height_in_map_units_minus1 = reader.ue()?;
+  if !reader.flag()? {
+    let _mb_adaptive_frame_field = reader.flag()?;
+  }
+  let _direct_8x8_inference = reader.flag()?;
+  if reader.flag()? {
+    for _ in 0..4 {
+      let _frame_crop_offset = reader.ue()?;
+    }
+  }
+
+  if !reader.flag()? {
+    return None;
+  }
+  // VUI parameters, up to the timing information
+  if reader.flag()? && reader.read(8)? == 255 {
+    let _sar = reader.read(32)?;
+  }
+  if reader.flag()? {
+    let _overscan_appropriate = reader.flag()?;
+  }
+  if reader.flag()? {
+    let _video_format_and_full_range = reader.read(4)?;
+    if reader.flag()? {
+      let _colour_description = reader.read(24)?;
+    }
+  }
+  if reader.flag()? {
+    let _chroma_sample_loc_type_top_field = reader.ue()?;
+    let _chroma_sample_loc_type_bottom_field = reader.ue()?;
+  }
+  if !reader.flag()? {
+    return None;
+  }
+
+  Some(SpsTiming {
+    num_units_in_tick: reader.read(32)?,
+    time_scale: reader.read(32)?,
+    fixed_frame_rate: reader.flag()?,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // baseline profile, 320x240, 25 fps, with an emulation prevention byte in
+  // num_units_in_tick
+  const BASELINE_SPS: &[u8] = &[
+    0x67, 0x42, 0x00, 0x1e, 0xda, 0x05, 0x07, 0xe8, 0x40, 0x00, 0x00, 0x03, 0x00, 0x40, 0x00, 0x00,
+    0x0c, 0xa8,
+  ];
+
+  // high profile with a scaling matrix, 320x240, 30000/1001 fps
+  const HIGH_SPS: &[u8] = &[
+    0x67, 0x64, 0x00, 0x1e, 0xad, 0xaf, 0xff, 0xe0, 0x2d, 0x02, 0x83, 0xf4, 0x20, 0x00, 0x00, 0x7d,
+    0x20, 0x00, 0x1d, 0x4c, 0x14,
+  ];
+
+  #[test]
+  fn timing() {
+    let timing = sps_timing(BASELINE_SPS).unwrap();
+    assert_eq!(
+      timing,
+      SpsTiming {
+        num_units_in_tick: 1,
+        time_scale: 50,
+        fixed_frame_rate: true,
+      }
+    );
+    assert_eq!(timing.frame_rate(), Some(25.0));
+
+    let timing = sps_timing(HIGH_SPS).unwrap();
+    assert_eq!(timing.num_units_in_tick, 1001);
+    assert_eq!(timing.time_scale, 60000);
+    assert!((timing.frame_rate().unwrap() - 29.97).abs() < 0.001);
+  }
+
+  #[test]
+  fn truncated() {
+    assert_eq!(sps_timing(&BASELINE_SPS[..10]), None);
+    assert_eq!(sps_timing(&[]), None);
+  }
+
+  #[test]
+  fn huge_scaling_delta() {
+    // high profile with a scaling list whose first delta is 2^31 - 1
+    let sps = [
+      0x67, 0x64, 0x00, 0x1e, 0xad, 0x80, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xfe,
+    ];
+    assert_eq!(sps_timing(&sps), None);
+  }
+}
@@ -1 +1,37 @@
+//! Without the default `std` feature, the crate is `no_std`: the tag and
+//! header parsers only need slices. The `alloc` feature brings back script
+//! data and the other parsers that collect into a `Vec`.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+/// The nom version the parsers are built on, re-exported so downstream crates
+/// can name `nom::Err` or `nom::Needed` without depending on the same release.
+pub use nom;
+
+#[cfg(feature = "alloc")]
+pub mod adpcm;
+#[cfg(feature = "alloc")]
+pub mod amf3;
+pub mod error;
+#[cfg(feature = "alloc")]
+pub mod h264;
+#[cfg(feature = "std")]
+pub mod metadata;
+pub mod mp3;
+#[cfg(feature = "alloc")]
+pub mod owned;
 pub mod parser;
+#[cfg(feature = "alloc")]
+pub mod repair;
+pub mod screen;
+pub mod seek;
+#[cfg(feature = "alloc")]
+pub mod segment;
+#[cfg(feature = "std")]
+pub mod serialize;
+#[cfg(feature = "std")]
+pub mod stream;
+pub mod visit;
+pub mod vp6;
@@ -0,0 +1,896 @@
+use cookie_factory::bytes::be_u32;
+use cookie_factory::combinator::slice;
+use cookie_factory::sequence::tuple;
+use cookie_factory::{gen_simple, GenError};
+
+use crate::parser::{
+  audio_data, audio_specific_config, raw_tags, Parser, ParserOptions, ScriptData, ScriptDataObject,
+  ScriptDataValue, SoundFormat, TagHeader, TagType,
+};
+use crate::serialize::{write_script_data, write_tag_header};
+
+/// Returns the properties of an AMF0 object or ECMA array.
+fn properties<'a, 'b>(value: &'b ScriptDataValue<'a>) -> Option<&'b [ScriptDataObject<'a>]> {
+  match value {
+    ScriptDataValue::Object(objects)
+    | ScriptDataValue::ECMAArray {
+      entries: objects, ..
+    } => Some(objects),
+    _ => None,
+  }
+}
+
+fn property<'a, 'b>(
+  objects: &'b [ScriptDataObject<'a>],
+  name: &str,
+) -> Option<&'b ScriptDataValue<'a>> {
+  objects.iter().find(|o| o.name == name).map(|o| &o.data)
+}
+
+fn number(objects: &[ScriptDataObject<'_>], name: &str) -> Option<f64> {
+  match property(objects, name)? {
+    ScriptDataValue::Number(n) => Some(*n),
+    _ => None,
+  }
+}
+
+fn boolean(objects: &[ScriptDataObject<'_>], name: &str) -> Option<bool> {
+  match property(objects, name)? {
+    ScriptDataValue::Boolean(b) => Some(*b),
+    _ => None,
+  }
+}
+
+fn string(objects: &[ScriptDataObject<'_>], name: &str) -> Option<String> {
+  match property(objects, name)? {
+    ScriptDataValue::String(s) | ScriptDataValue::LongString(s) => Some(s.to_string()),
+    ScriptDataValue::RawString(b) => Some(String::from_utf8_lossy(b).into_owned()),
+    _ => None,
+  }
+}
+
+/// The `onMetaData` properties with a field in `Metadata`.
+const METADATA_KEYS: &[&str] = &[
+  "duration",
+  "width",
+  "height",
+  "framerate",
+  "videodatarate",
+  "videocodecid",
+  "audiodatarate",
+  "audiosamplerate",
+  "audiosamplesize",
+  "audiocodecid",
+  "filesize",
+  "metadatacreator",
+  "encoder",
+  "major_brand",
+  "audiochannels",
+  "stereo",
+];
+
+/// Typed view of the `onMetaData` script tag.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metadata<'a> {
+  /// In seconds.
+  pub duration: Option<f64>,
+  pub width: Option<f64>,
+  pub height: Option<f64>,
+  /// In frames per second.
+  pub framerate: Option<f64>,
+  /// In kilobits per second.
+  pub video_data_rate: Option<f64>,
+  /// The `CodecId` number, or a FourCC as a number with enhanced RTMP.
+  pub video_codec_id: Option<f64>,
+  /// In kilobits per second.
+  pub audio_data_rate: Option<f64>,
+  /// In Hz.
+  pub audio_sample_rate: Option<f64>,
+  /// In bits.
+  pub audio_sample_size: Option<f64>,
+  /// The `SoundFormat` number, or a FourCC as a number with enhanced RTMP.
+  pub audio_codec_id: Option<f64>,
+  /// In bytes.
+  pub file_size: Option<f64>,
+  /// The tool that wrote the metadata, from `metadatacreator`.
+  pub metadata_creator: Option<String>,
+  /// The muxer or encoder that produced the file, from `encoder`.
+  pub encoder: Option<String>,
+  /// Brand of the MP4 file the stream was remuxed from, from `major_brand`.
+  pub major_brand: Option<String>,
+  /// Number of audio channels, from `audiochannels`.
+  pub audio_channels: Option<u8>,
+  /// From `stereo`.
+  pub stereo: Option<bool>,
+  /// Number of channels of the AAC AudioSpecificConfig. This is not part of
+  /// `onMetaData`: `from_flv` reads it from the first AAC sequence header.
+  pub aac_channels: Option<u8>,
+  /// The properties without a field, in the order of the tag.
+  pub extra: Vec<ScriptDataObject<'a>>,
+}
+
+impl<'a> Metadata<'a> {
+  /// Extracts the known fields of an `onMetaData` script tag. Fields that are
+  /// missing or of an unexpected type are left to `None`, and the other
+  /// properties are kept in `extra`.
+  pub fn from_script_data(script_data: &ScriptData<'a>) -> Metadata<'a> {
+    let objects = properties(&script_data.arguments).unwrap_or(&[]);
+
+    Metadata {
+      duration: number(objects, "duration"),
+      width: number(objects, "width"),
+      height: number(objects, "height"),
+      framerate: number(objects, "framerate"),
+      video_data_rate: number(objects, "videodatarate"),
+      video_codec_id: number(objects, "videocodecid"),
+      audio_data_rate: number(objects, "audiodatarate"),
+      audio_sample_rate: number(objects, "audiosamplerate"),
+      audio_sample_size: number(objects, "audiosamplesize"),
+      audio_codec_id: number(objects, "audiocodecid"),
+      file_size: number(objects, "filesize"),
+      metadata_creator: string(objects, "metadatacreator"),
+      encoder: string(objects, "encoder"),
+      major_brand: string(objects, "major_brand"),
+      audio_channels: number(objects, "audiochannels").map(|n| n as u8),
+      stereo: boolean(objects, "stereo"),
+      aac_channels: None,
+      extra: objects
+        .iter()
+        .filter(|o| !METADATA_KEYS.contains(&o.name))
+        .cloned()
+        .collect(),
+    }
+  }
+
+  /// Width and height, if both are set.
+  pub fn dimensions(&self) -> Option<(f64, f64)> {
+    Some((self.width?, self.height?))
+  }
+
+  /// Number of audio channels, from `audiochannels` if set, then `stereo`,
+  /// then the AAC configuration, since encoders don't agree on how to report
+  /// it.
+  pub fn channels(&self) -> Option<u8> {
+    self
+      .audio_channels
+      .or_else(|| self.stereo.map(|stereo| if stereo { 2 } else { 1 }))
+      .or(self.aac_channels)
+  }
+
+  /// Overrides the fields of `self` with the ones set in `other`, as when a
+  /// later `onMetaData` tag updates an earlier one.
+  pub fn merge(&mut self, other: &Metadata<'a>) {
+    fn merge_field<T: Clone>(field: &mut Option<T>, other: &Option<T>) {
+      if other.is_some() {
+        field.clone_from(other);
+      }
+    }
+
+    merge_field(&mut self.duration, &other.duration);
+    merge_field(&mut self.width, &other.width);
+    merge_field(&mut self.height, &other.height);
+    merge_field(&mut self.framerate, &other.framerate);
+    merge_field(&mut self.video_data_rate, &other.video_data_rate);
+    merge_field(&mut self.video_codec_id, &other.video_codec_id);
+    merge_field(&mut self.audio_data_rate, &other.audio_data_rate);
+    merge_field(&mut self.audio_sample_rate, &other.audio_sample_rate);
+    merge_field(&mut self.audio_sample_size, &other.audio_sample_size);
+    merge_field(&mut self.audio_codec_id, &other.audio_codec_id);
+    merge_field(&mut self.file_size, &other.file_size);
+    merge_field(&mut self.metadata_creator, &other.metadata_creator);
+    merge_field(&mut self.encoder, &other.encoder);
+    merge_field(&mut self.major_brand, &other.major_brand);
+    merge_field(&mut self.audio_channels, &other.audio_channels);
+    merge_field(&mut self.stereo, &other.stereo);
+    merge_field(&mut self.aac_channels, &other.aac_channels);
+    for object in &other.extra {
+      match self.extra.iter_mut().find(|o| o.name == object.name) {
+        Some(existing) => existing.data = object.data.clone(),
+        None => self.extra.push(object.clone()),
+      }
+    }
+  }
+
+  /// Parses every `onMetaData` script tag of a complete FLV file and merges
+  /// them in file order, so later tags win. Returns `None` if the file has
+  /// no `onMetaData` tag.
+  pub fn from_flv(input: &'a [u8]) -> Option<Metadata<'a>> {
+    let mut metadata: Option<Metadata> = None;
+    let mut aac_channels = None;
+    // one Latin-1 encoder name should not lose the whole tag
+    let parser = Parser::with_options(ParserOptions {
+      raw_invalid_strings: true,
+      ..ParserOptions::default()
+    });
+
+    for tag in raw_tags(input) {
+      let body = match tag.body() {
+        Some(body) => body,
+        None => continue,
+      };
+      if tag.header.tag_type == TagType::Script {
+        if let Ok((_, script)) = parser.script_data(body) {
+          if script.name == "onMetaData" {
+            let parsed = Metadata::from_script_data(&script);
+            match metadata.as_mut() {
+              Some(metadata) => metadata.merge(&parsed),
+              None => metadata = Some(parsed),
+            }
+          }
+        }
+      }
+      if tag.header.tag_type == TagType::Audio && aac_channels.is_none() {
+        if let Ok((_, audio)) = audio_data(body, body.len()) {
+          if audio.sound_format == SoundFormat::AAC {
+            aac_channels = aac_sequence_header_channels(audio.sound_data);
+          }
+        }
+      }
+    }
+
+    if let Some(metadata) = metadata.as_mut() {
+      metadata.aac_channels = aac_channels;
+    }
+    metadata
+  }
+}
+
+/// Reads the channel configuration of an AAC sequence header. A layout in a
+/// program config element is not handled.
+fn aac_sequence_header_channels(aac: &[u8]) -> Option<u8> {
+  match aac {
+    [0, config @ ..] => audio_specific_config(config).ok()?.1.channels(),
+    _ => None,
+  }
+}
+
+/// The seek table that tools like yamdi and FLVTool2 store in the
+/// `keyframes` object of `onMetaData`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KeyframeIndex {
+  /// In seconds.
+  pub times: Vec<f64>,
+  /// Byte offsets of the keyframe tags from the start of the file.
+  pub file_positions: Vec<f64>,
+  /// Set when the two arrays of the tag don't have the same length, or hold
+  /// something else than numbers, and were truncated to the shorter one.
+  pub truncated: bool,
+}
+
+impl KeyframeIndex {
+  /// Extracts the keyframe index of an `onMetaData` script tag. Returns
+  /// `None` if it has no `keyframes` object with `times` and
+  /// `filepositions` arrays.
+  pub fn from_script_data(script_data: &ScriptData<'_>) -> Option<KeyframeIndex> {
+    let keyframes =
+      property(properties(&script_data.arguments)?, "keyframes").and_then(properties)?;
+    let array = |name| match property(keyframes, name)? {
+      ScriptDataValue::StrictArray(values) => Some(values),
+      _ => None,
+    };
+    let number = |value: &ScriptDataValue<'_>| match value {
+      ScriptDataValue::Number(n) => Some(*n),
+      _ => None,
+    };
+    let times = array("times")?;
+    let file_positions = array("filepositions")?;
+
+    let entries: Vec<_> = times
+      .iter()
+      .zip(file_positions)
+      .map_while(|(time, position)| Some((number(time)?, number(position)?)))
+      .collect();
+    let truncated = entries.len() != times.len() || entries.len() != file_positions.len();
+    let (times, file_positions) = entries.into_iter().unzip();
+
+    Some(KeyframeIndex {
+      times,
+      file_positions,
+      truncated,
+    })
+  }
+}
+
+/// Property names, compared case-insensitively, under which live platforms
+/// store DRM init data in `onMetaData`.
+const DRM_KEYS: &[&str] = &["widevine", "playready", "fairplay", "pssh"];
+
+/// Encryption information stashed in `onMetaData` under vendor keys.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EncryptionInfo {
+  /// DRM init data, as the property name and its opaque bytes, in the order
+  /// of the tag.
+  pub drm_init_data: Vec<(String, Vec<u8>)>,
+}
+
+impl EncryptionInfo {
+  /// Collects the DRM init data of an `onMetaData` script tag. The blobs are
+  /// carried as strings, usually long strings, and returned byte for byte.
+  /// Returns `None` if the tag has no such property.
+  ///
+  /// A blob that is not valid UTF-8 only gets here if the tag was parsed
+  /// with `ParserOptions::raw_invalid_strings`, which keeps it as a
+  /// `ScriptDataValue::RawString`.
+  pub fn from_script_data(script_data: &ScriptData<'_>) -> Option<EncryptionInfo> {
+    let objects = properties(&script_data.arguments)?;
+
+    let drm_init_data: Vec<_> = objects
+      .iter()
+      .filter(|o| DRM_KEYS.iter().any(|key| o.name.eq_ignore_ascii_case(key)))
+      .filter_map(|o| match o.data {
+        ScriptDataValue::String(s) | ScriptDataValue::LongString(s) => {
+          Some((o.name.to_string(), s.as_bytes().to_vec()))
+        }
+        ScriptDataValue::RawString(b) => Some((o.name.to_string(), b.to_vec())),
+        _ => None,
+      })
+      .collect();
+
+    if drm_init_data.is_empty() {
+      return None;
+    }
+    Some(EncryptionInfo { drm_init_data })
+  }
+}
+
+/// SMPTE ST 2086 mastering display color volume, from the `hdrMdcv` object.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MasteringDisplay {
+  pub red_x: Option<f64>,
+  pub red_y: Option<f64>,
+  pub green_x: Option<f64>,
+  pub green_y: Option<f64>,
+  pub blue_x: Option<f64>,
+  pub blue_y: Option<f64>,
+  pub white_point_x: Option<f64>,
+  pub white_point_y: Option<f64>,
+  pub max_luminance: Option<f64>,
+  pub min_luminance: Option<f64>,
+}
+
+/// Content light level, from the `hdrCll` object.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContentLightLevel {
+  pub max_fall: Option<u16>,
+  pub max_cll: Option<u16>,
+}
+
+/// The `colorInfo` object sent in an enhanced RTMP video metadata packet.
+///
+/// The color fields come from the nested `colorConfig` object and use the
+/// ITU-T H.273 code points.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ColorInfo {
+  pub bit_depth: Option<u8>,
+  pub color_primaries: Option<u8>,
+  pub transfer_characteristics: Option<u8>,
+  pub matrix_coefficients: Option<u8>,
+  pub mastering_display: Option<MasteringDisplay>,
+  pub content_light_level: Option<ContentLightLevel>,
+}
+
+impl ColorInfo {
+  /// Extracts the color information from a parsed video metadata packet
+  /// body, which is a script data record named `colorInfo`.
+  pub fn from_script_data(script_data: &ScriptData<'_>) -> Option<ColorInfo> {
+    if script_data.name != "colorInfo" {
+      return None;
+    }
+    ColorInfo::from_value(&script_data.arguments)
+  }
+
+  /// Extracts the color information from the `colorInfo` object itself.
+  pub fn from_value(value: &ScriptDataValue<'_>) -> Option<ColorInfo> {
+    let objects = properties(value)?;
+    let mut info = ColorInfo::default();
+
+    if let Some(config) = property(objects, "colorConfig").and_then(properties) {
+      info.bit_depth = number(config, "bitDepth").map(|n| n as u8);
+      info.color_primaries = number(config, "colorPrimaries").map(|n| n as u8);
+      info.transfer_characteristics = number(config, "transferCharacteristics").map(|n| n as u8);
+      info.matrix_coefficients = number(config, "matrixCoefficients").map(|n| n as u8);
+    }
+
+    if let Some(cll) = property(objects, "hdrCll").and_then(properties) {
+      info.content_light_level = Some(ContentLightLevel {
+        max_fall: number(cll, "maxFall").map(|n| n as u16),
+        max_cll: number(cll, "maxCLL").map(|n| n as u16),
+      });
+    }
+
+    if let Some(mdcv) = property(objects, "hdrMdcv").and_then(properties) {
+      info.mastering_display = Some(MasteringDisplay {
+        red_x: number(mdcv, "redX"),
+        red_y: number(mdcv, "redY"),
+        green_x: number(mdcv, "greenX"),
+        green_y: number(mdcv, "greenY"),
+        blue_x: number(mdcv, "blueX"),
+        blue_y: number(mdcv, "blueY"),
+        white_point_x: number(mdcv, "whitePointX"),
+        white_point_y: number(mdcv, "whitePointY"),
+        max_luminance: number(mdcv, "maxLuminance"),
+        min_luminance: number(mdcv, "minLuminance"),
+      });
+    }
+
+    Some(info)
+  }
+}
+
+/// A cue point, as carried by an `onCuePoint` script tag.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CuePoint {
+  pub name: String,
+  /// Position of the cue point in seconds.
+  pub time: f64,
+  /// Usually `"event"` or `"navigation"`.
+  pub kind: String,
+  pub parameters: Vec<(String, String)>,
+}
+
+impl CuePoint {
+  /// Extracts a cue point from an `onCuePoint` script tag.
+  pub fn from_script_data(script_data: &ScriptData<'_>) -> Option<CuePoint> {
+    if script_data.name != "onCuePoint" {
+      return None;
+    }
+    let objects = properties(&script_data.arguments)?;
+
+    let parameters = property(objects, "parameters")
+      .and_then(properties)
+      .unwrap_or(&[])
+      .iter()
+      .filter_map(|o| match o.data {
+        ScriptDataValue::String(s) => Some((o.name.to_string(), s.to_string())),
+        _ => None,
+      })
+      .collect();
+
+    Some(CuePoint {
+      name: string(objects, "name")?,
+      time: number(objects, "time")?,
+      kind: string(objects, "type").unwrap_or_default(),
+      parameters,
+    })
+  }
+
+  /// Serializes the cue point as a complete `onCuePoint` script tag at
+  /// `timestamp` milliseconds, followed by its PreviousTagSize.
+  ///
+  /// Since the result ends with its own PreviousTagSize, it can be spliced
+  /// into an existing stream right after any PreviousTagSize field. To keep
+  /// the stream ordered, insert it before the first tag with a timestamp
+  /// greater than or equal to `timestamp`.
+  pub fn to_script_tag(&self, timestamp: u32) -> Result<Vec<u8>, GenError> {
+    let parameters = self
+      .parameters
+      .iter()
+      .map(|(name, value)| ScriptDataObject {
+        name,
+        data: ScriptDataValue::String(value),
+      })
+      .collect();
+    let script_data = ScriptData {
+      name: "onCuePoint",
+      arguments: ScriptDataValue::Object(vec![
+        ScriptDataObject {
+          name: "name",
+          data: ScriptDataValue::String(&self.name),
+        },
+        ScriptDataObject {
+          name: "time",
+          data: ScriptDataValue::Number(self.time),
+        },
+        ScriptDataObject {
+          name: "type",
+          data: ScriptDataValue::String(&self.kind),
+        },
+        ScriptDataObject {
+          name: "parameters",
+          data: ScriptDataValue::Object(parameters),
+        },
+      ]),
+    };
+
+    let body = gen_simple(write_script_data(&script_data), Vec::new())?;
+    let header = TagHeader {
+      tag_type: TagType::Script,
+      data_size: body.len() as u32,
+      timestamp,
+      stream_id: 0,
+      filtered: false,
+    };
+    gen_simple(
+      tuple((
+        write_tag_header(&header),
+        slice(&body),
+        be_u32(11 + header.data_size),
+      )),
+      Vec::new(),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::{script_data, tag_header};
+
+  fn num(name: &str, n: f64) -> ScriptDataObject<'_> {
+    ScriptDataObject {
+      name,
+      data: ScriptDataValue::Number(n),
+    }
+  }
+
+  #[test]
+  fn hdr_color_info() {
+    let script_data = ScriptData {
+      name: "colorInfo",
+      arguments: ScriptDataValue::Object(vec![
+        ScriptDataObject {
+          name: "colorConfig",
+          data: ScriptDataValue::Object(vec![
+            num("bitDepth", 10.0),
+            num("colorPrimaries", 9.0),
+            num("transferCharacteristics", 16.0),
+            num("matrixCoefficients", 9.0),
+          ]),
+        },
+        ScriptDataObject {
+          name: "hdrCll",
+          data: ScriptDataValue::Object(vec![num("maxFall", 400.0), num("maxCLL", 1000.0)]),
+        },
+        ScriptDataObject {
+          name: "hdrMdcv",
+          data: ScriptDataValue::Object(vec![
+            num("redX", 0.708),
+            num("redY", 0.292),
+            num("maxLuminance", 1000.0),
+            num("minLuminance", 0.0001),
+          ]),
+        },
+      ]),
+    };
+
+    assert_eq!(
+      ColorInfo::from_script_data(&script_data),
+      Some(ColorInfo {
+        bit_depth: Some(10),
+        color_primaries: Some(9),
+        transfer_characteristics: Some(16),
+        matrix_coefficients: Some(9),
+        mastering_display: Some(MasteringDisplay {
+          red_x: Some(0.708),
+          red_y: Some(0.292),
+          max_luminance: Some(1000.0),
+          min_luminance: Some(0.0001),
+          ..MasteringDisplay::default()
+        }),
+        content_light_level: Some(ContentLightLevel {
+          max_fall: Some(400),
+          max_cll: Some(1000),
+        }),
+      })
+    );
+  }
+
+  #[test]
+  fn provenance() {
+    let script_data = ScriptData {
+      name: "onMetaData",
+      arguments: ScriptDataValue::ecma_array(vec![
+        ScriptDataObject {
+          name: "metadatacreator",
+          data: ScriptDataValue::String("inlet media FLVTool2 v1.0.6"),
+        },
+        ScriptDataObject {
+          name: "encoder",
+          data: ScriptDataValue::String("Lavf58.29.100"),
+        },
+        num("major_brand", 0.0),
+      ]),
+    };
+
+    assert_eq!(
+      Metadata::from_script_data(&script_data),
+      Metadata {
+        metadata_creator: Some("inlet media FLVTool2 v1.0.6".to_string()),
+        encoder: Some("Lavf58.29.100".to_string()),
+        ..Metadata::default()
+      }
+    );
+  }
+
+  #[test]
+  fn merge_later_wins() {
+    let mut early = Metadata {
+      metadata_creator: Some("live encoder".to_string()),
+      encoder: Some("placeholder".to_string()),
+      ..Metadata::default()
+    };
+    early.merge(&Metadata {
+      metadata_creator: None,
+      encoder: Some("Lavf58.29.100".to_string()),
+      major_brand: Some("isom".to_string()),
+      ..Metadata::default()
+    });
+
+    assert_eq!(
+      early,
+      Metadata {
+        metadata_creator: Some("live encoder".to_string()),
+        encoder: Some("Lavf58.29.100".to_string()),
+        major_brand: Some("isom".to_string()),
+        ..Metadata::default()
+      }
+    );
+  }
+
+  #[test]
+  fn from_flv() {
+    let commercials = include_bytes!("../assets/asian-commercials-are-weird.flv");
+    let metadata = Metadata::from_flv(commercials).unwrap();
+    assert_eq!(metadata.dimensions(), Some((464.0, 348.0)));
+    assert_eq!(metadata.framerate, Some(30.0));
+    assert_eq!(metadata.video_data_rate, Some(368.0));
+    assert_eq!(metadata.video_codec_id, Some(4.0));
+    assert_eq!(metadata.audio_data_rate, Some(56.0));
+    assert_eq!(metadata.audio_codec_id, Some(2.0));
+    assert_eq!(metadata.file_size, None);
+    assert!((metadata.duration.unwrap() - 28.13).abs() < 0.01);
+    let extra: Vec<_> = metadata.extra.iter().map(|o| o.name).collect();
+    assert_eq!(extra, ["audiodelay", "canSeekToEnd", "creationdate"]);
+
+    // zelda has no script tag at all
+    let zelda = include_bytes!("../assets/zelda.flv");
+    assert_eq!(Metadata::from_flv(zelda), None);
+  }
+
+  #[test]
+  fn cue_point_tag() {
+    let cue_point = CuePoint {
+      name: "ad-break".to_string(),
+      time: 12.5,
+      kind: "event".to_string(),
+      parameters: vec![("duration".to_string(), "30".to_string())],
+    };
+    let tag = cue_point.to_script_tag(0x0102_0304).unwrap();
+
+    let (rest, header) = tag_header(&tag).unwrap();
+    assert_eq!(header.tag_type, TagType::Script);
+    assert_eq!(header.timestamp, 0x0102_0304);
+    assert_eq!(rest.len(), header.data_size as usize + 4);
+
+    let (trailer, script) = script_data(&rest[..header.data_size as usize + 4]).unwrap();
+    assert_eq!(trailer, (11 + header.data_size).to_be_bytes());
+    assert_eq!(CuePoint::from_script_data(&script), Some(cue_point));
+  }
+
+  #[test]
+  fn not_color_info() {
+    let script_data = ScriptData {
+      name: "onMetaData",
+      arguments: ScriptDataValue::ecma_array(vec![]),
+    };
+    assert_eq!(ColorInfo::from_script_data(&script_data), None);
+  }
+
+  #[test]
+  fn drm_init_data() {
+    let script_data = ScriptData {
+      name: "onMetaData",
+      arguments: ScriptDataValue::ecma_array(vec![
+        num("duration", 10.0),
+        ScriptDataObject {
+          name: "Widevine",
+          data: ScriptDataValue::LongString("\u{0}\u{0}\u{0}\u{20}pssh"),
+        },
+        ScriptDataObject {
+          name: "playready",
+          data: ScriptDataValue::String("<WRMHEADER/>"),
+        },
+        num("pssh", 1.0),
+        ScriptDataObject {
+          name: "FairPlay",
+          data: ScriptDataValue::RawString(b"\xff\xfe"),
+        },
+      ]),
+    };
+
+    assert_eq!(
+      EncryptionInfo::from_script_data(&script_data),
+      Some(EncryptionInfo {
+        drm_init_data: vec![
+          ("Widevine".to_string(), b"\0\0\0\x20pssh".to_vec()),
+          ("playready".to_string(), b"<WRMHEADER/>".to_vec()),
+          ("FairPlay".to_string(), b"\xff\xfe".to_vec()),
+        ],
+      })
+    );
+
+    let script_data = ScriptData {
+      name: "onMetaData",
+      arguments: ScriptDataValue::ecma_array(vec![num("duration", 10.0)]),
+    };
+    assert_eq!(EncryptionInfo::from_script_data(&script_data), None);
+  }
+
+  #[test]
+  fn channels_precedence() {
+    let metadata = Metadata {
+      audio_channels: Some(6),
+      stereo: Some(true),
+      aac_channels: Some(1),
+      ..Metadata::default()
+    };
+    assert_eq!(metadata.channels(), Some(6));
+
+    let metadata = Metadata {
+      audio_channels: None,
+      ..metadata
+    };
+    assert_eq!(metadata.channels(), Some(2));
+
+    let metadata = Metadata {
+      stereo: None,
+      ..metadata
+    };
+    assert_eq!(metadata.channels(), Some(1));
+
+    assert_eq!(Metadata::default().channels(), None);
+  }
+
+  #[test]
+  fn aac_channels_from_flv() {
+    fn tag(tag_type: u8, body: &[u8]) -> Vec<u8> {
+      let size = body.len() as u32;
+      let mut tag = vec![tag_type];
+      tag.extend_from_slice(&size.to_be_bytes()[1..]);
+      tag.extend_from_slice(&[0; 7]);
+      tag.extend_from_slice(body);
+      tag.extend_from_slice(&(11 + size).to_be_bytes());
+      tag
+    }
+
+    let script_data = ScriptData {
+      name: "onMetaData",
+      arguments: ScriptDataValue::ecma_array(vec![ScriptDataObject {
+        name: "stereo",
+        data: ScriptDataValue::Boolean(false),
+      }]),
+    };
+    let mut flv = b"FLV\x01\x05\x00\x00\x00\x09\x00\x00\x00\x00".to_vec();
+    flv.extend(tag(
+      18,
+      &gen_simple(write_script_data(&script_data), Vec::new()).unwrap(),
+    ));
+    // AAC LC, 44.1 kHz, 2 channels
+    flv.extend(tag(8, &[0xaf, 0, 0x12, 0x10]));
+
+    let metadata = Metadata::from_flv(&flv).unwrap();
+    assert_eq!(metadata.stereo, Some(false));
+    assert_eq!(metadata.aac_channels, Some(2));
+    assert_eq!(metadata.channels(), Some(1));
+
+    // a Latin-1 string does not hide the rest of the tag
+    let script_data = ScriptData {
+      name: "onMetaData",
+      arguments: ScriptDataValue::ecma_array(vec![
+        ScriptDataObject {
+          name: "encoder",
+          data: ScriptDataValue::RawString(b"caf\xe9"),
+        },
+        num("duration", 10.0),
+      ]),
+    };
+    let mut flv = b"FLV\x01\x05\x00\x00\x00\x09\x00\x00\x00\x00".to_vec();
+    flv.extend(tag(
+      18,
+      &gen_simple(write_script_data(&script_data), Vec::new()).unwrap(),
+    ));
+
+    let metadata = Metadata::from_flv(&flv).unwrap();
+    assert_eq!(metadata.encoder.as_deref(), Some("caf\u{fffd}"));
+    assert_eq!(metadata.duration, Some(10.0));
+  }
+
+  #[test]
+  fn merge_extra() {
+    let script_data = |arguments| ScriptData {
+      name: "onMetaData",
+      arguments,
+    };
+    let early = script_data(ScriptDataValue::ecma_array(vec![
+      num("width", 640.0),
+      num("audiodelay", 0.0),
+      num("hasVideo", 1.0),
+    ]));
+    let late = script_data(ScriptDataValue::ecma_array(vec![
+      num("height", 360.0),
+      num("audiodelay", 0.5),
+      num("lasttimestamp", 10.0),
+    ]));
+
+    let mut metadata = Metadata::from_script_data(&early);
+    metadata.merge(&Metadata::from_script_data(&late));
+    assert_eq!(metadata.dimensions(), Some((640.0, 360.0)));
+    assert_eq!(
+      metadata.extra,
+      vec![
+        num("audiodelay", 0.5),
+        num("hasVideo", 1.0),
+        num("lasttimestamp", 10.0)
+      ]
+    );
+  }
+
+  #[test]
+  fn keyframe_index() {
+    let numbers = |numbers: &[f64]| {
+      ScriptDataValue::StrictArray(
+        numbers
+          .iter()
+          .map(|n| ScriptDataValue::Number(*n))
+          .collect(),
+      )
+    };
+    let script_data = |times, file_positions| ScriptData {
+      name: "onMetaData",
+      arguments: ScriptDataValue::ecma_array(vec![
+        num("duration", 4.0),
+        ScriptDataObject {
+          name: "keyframes",
+          data: ScriptDataValue::Object(vec![
+            ScriptDataObject {
+              name: "filepositions",
+              data: file_positions,
+            },
+            ScriptDataObject {
+              name: "times",
+              data: times,
+            },
+          ]),
+        },
+      ]),
+    };
+
+    assert_eq!(
+      KeyframeIndex::from_script_data(&script_data(
+        numbers(&[0.0, 2.0, 4.0]),
+        numbers(&[13.0, 47201.0, 92680.0])
+      )),
+      Some(KeyframeIndex {
+        times: vec![0.0, 2.0, 4.0],
+        file_positions: vec![13.0, 47201.0, 92680.0],
+        truncated: false,
+      })
+    );
+    assert_eq!(
+      KeyframeIndex::from_script_data(&script_data(
+        numbers(&[0.0, 2.0, 4.0]),
+        numbers(&[13.0, 47201.0])
+      )),
+      Some(KeyframeIndex {
+        times: vec![0.0, 2.0],
+        file_positions: vec![13.0, 47201.0],
+        truncated: true,
+      })
+    );
+    assert_eq!(
+      KeyframeIndex::from_script_data(&script_data(
+        ScriptDataValue::StrictArray(vec![ScriptDataValue::Number(0.0), ScriptDataValue::Null]),
+        numbers(&[13.0, 47201.0])
+      )),
+      Some(KeyframeIndex {
+        times: vec![0.0],
+        file_positions: vec![13.0],
+        truncated: true,
+      })
+    );
+    assert_eq!(
+      KeyframeIndex::from_script_data(&script_data(ScriptDataValue::Null, numbers(&[13.0]))),
+      None
+    );
+  }
+}
@@ -0,0 +1,222 @@
+use nom::bits::bits;
+use nom::bits::streaming::take;
+use nom::combinator::{map, verify};
+use nom::sequence::tuple;
+use nom::IResult;
+
+use crate::error::FlvError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MpegVersion {
+  Mpeg1,
+  Mpeg2,
+  Mpeg25,
+}
+
+/// The 4-byte header that starts every MPEG audio frame in the MP3
+/// `sound_data`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mp3FrameHeader {
+  pub version: MpegVersion,
+  /// 1, 2 or 3 for Layer I, II or III.
+  pub layer: u8,
+  pub protected: bool,
+  pub bitrate_index: u8,
+  pub sampling_rate_index: u8,
+  pub padding: bool,
+  pub channel_mode: u8,
+}
+
+impl Mp3FrameHeader {
+  /// Free-format streams use a bitrate that is not in the standard table,
+  /// signaled by a bitrate index of 0.
+  pub fn is_free_format(&self) -> bool {
+    self.bitrate_index == 0
+  }
+
+  pub fn is_mono(&self) -> bool {
+    self.channel_mode == 3
+  }
+
+  /// Offset of the Xing/Info tag from the start of the frame: it sits right
+  /// after the Layer III side information.
+  fn xing_offset(&self) -> usize {
+    let side_info = match (self.version, self.is_mono()) {
+      (MpegVersion::Mpeg1, true) => 17,
+      (MpegVersion::Mpeg1, false) => 32,
+      (_, true) => 9,
+      (_, false) => 17,
+    };
+    4 + if self.protected { 2 } else { 0 } + side_info
+  }
+}
+
+pub fn mp3_frame_header(input: &[u8]) -> IResult<&[u8], Mp3FrameHeader, FlvError> {
+  let take_bits = tuple((
+    verify(take::<_, u16, _, _>(11usize), |sync| *sync == 0x7ff),
+    verify(take::<_, u8, _, _>(2usize), |version| *version != 1),
+    verify(take::<_, u8, _, _>(2usize), |layer| *layer != 0),
+    take::<_, u8, _, _>(1usize),
+    verify(take::<_, u8, _, _>(4usize), |bitrate| *bitrate != 15),
+    verify(take::<_, u8, _, _>(2usize), |rate| *rate != 3),
+    take::<_, u8, _, _>(1usize),
+    take::<_, u8, _, _>(1usize),
+    take::<_, u8, _, _>(2usize),
+    take::<_, u8, _, _>(6usize),
+  ));
+  map(
+    bits::<_, _, FlvError, _, _>(take_bits),
+    |(
+      _,
+      version,
+      layer,
+      protection,
+      bitrate_index,
+      sampling_rate_index,
+      padding,
+      _,
+      channel_mode,
+      _,
+    )| {
+      Mp3FrameHeader {
+        version: match version {
+          0 => MpegVersion::Mpeg25,
+          2 => MpegVersion::Mpeg2,
+          _ => MpegVersion::Mpeg1,
+        },
+        layer: 4 - layer,
+        // the protection bit is set when there is *no* CRC
+        protected: protection == 0,
+        bitrate_index,
+        sampling_rate_index,
+        padding: padding == 1,
+        channel_mode,
+      }
+    },
+  )(input)
+}
+
+/// The VBR header an encoder can put in the first frame of an MP3 stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mp3VbrHeader {
+  /// LAME/Xing VBR header.
+  Xing,
+  /// Same layout as `Xing`, written by LAME for CBR streams.
+  Info,
+  /// Fraunhofer VBR header.
+  Vbri,
+}
+
+/// Looks for a Xing, Info or VBRI header in the first MPEG audio frame of
+/// `input`.
+pub fn mp3_vbr_header(input: &[u8]) -> Option<Mp3VbrHeader> {
+  let (_, header) = mp3_frame_header(input).ok()?;
+  if header.layer != 3 {
+    return None;
+  }
+
+  let xing_offset = header.xing_offset();
+  match input.get(xing_offset..xing_offset + 4) {
+    Some(b"Xing") => return Some(Mp3VbrHeader::Xing),
+    Some(b"Info") => return Some(Mp3VbrHeader::Info),
+    _ => {}
+  }
+
+  // VBRI is always 32 bytes after the frame header
+  match input.get(36..40) {
+    Some(b"VBRI") => Some(Mp3VbrHeader::Vbri),
+    _ => None,
+  }
+}
+
+/// Tells whether the MP3 stream starting with `input` (usually the
+/// `sound_data` of the first MP3 audio tag) is VBR.
+///
+/// Returns `None` if `input` does not start with a valid MPEG audio frame.
+/// A frame carrying neither a Xing nor a VBRI header is reported as CBR, as
+/// decoders do.
+pub fn mp3_is_vbr(input: &[u8]) -> Option<bool> {
+  mp3_frame_header(input).ok()?;
+  Some(matches!(
+    mp3_vbr_header(input),
+    Some(Mp3VbrHeader::Xing) | Some(Mp3VbrHeader::Vbri)
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  #[cfg(feature = "alloc")]
+  use alloc::vec::Vec;
+
+  #[cfg(feature = "alloc")]
+  fn frame(header: [u8; 4], tag_offset: usize, tag: &[u8]) -> Vec<u8> {
+    let mut frame = header.to_vec();
+    frame.resize(tag_offset, 0);
+    frame.extend_from_slice(tag);
+    frame.resize(417, 0);
+    frame
+  }
+
+  #[test]
+  fn frame_header() {
+    assert_eq!(
+      mp3_frame_header(&[0xff, 0xfb, 0x90, 0x64]),
+      Ok((
+        &b""[..],
+        Mp3FrameHeader {
+          version: MpegVersion::Mpeg1,
+          layer: 3,
+          protected: false,
+          bitrate_index: 9,
+          sampling_rate_index: 0,
+          padding: false,
+          channel_mode: 1,
+        }
+      ))
+    );
+    assert!(mp3_frame_header(&[0xff, 0xfb, 0x00, 0x64])
+      .unwrap()
+      .1
+      .is_free_format());
+    assert!(mp3_frame_header(&[0xff, 0x0b, 0x90, 0x64]).is_err());
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn vbr_detection() {
+    // MPEG-1 Layer III, stereo: 32 bytes of side info
+    let xing = frame([0xff, 0xfb, 0x90, 0x64], 36, b"Xing");
+    assert_eq!(mp3_vbr_header(&xing), Some(Mp3VbrHeader::Xing));
+    assert_eq!(mp3_is_vbr(&xing), Some(true));
+
+    // MPEG-1 Layer III, mono: 17 bytes of side info
+    let info = frame([0xff, 0xfb, 0x90, 0xc4], 21, b"Info");
+    assert_eq!(mp3_vbr_header(&info), Some(Mp3VbrHeader::Info));
+    assert_eq!(mp3_is_vbr(&info), Some(false));
+
+    // MPEG-2 Layer III, stereo: 17 bytes of side info
+    let xing = frame([0xff, 0xf3, 0x90, 0x64], 21, b"Xing");
+    assert_eq!(mp3_is_vbr(&xing), Some(true));
+
+    let vbri = frame([0xff, 0xfb, 0x90, 0x64], 36, b"VBRI");
+    assert_eq!(mp3_vbr_header(&vbri), Some(Mp3VbrHeader::Vbri));
+    assert_eq!(mp3_is_vbr(&vbri), Some(true));
+
+    let plain = frame([0xff, 0xfb, 0x90, 0x64], 36, b"");
+    assert_eq!(mp3_vbr_header(&plain), None);
+    assert_eq!(mp3_is_vbr(&plain), Some(false));
+
+    assert_eq!(mp3_is_vbr(b"not an mp3 frame"), None);
+  }
+
+  #[test]
+  fn commercials_is_cbr() {
+    let commercials = include_bytes!("../assets/asian-commercials-are-weird.flv");
+    // first audio tag starts at 301, its MP3 payload after the 11 byte tag
+    // header and the sound format byte
+    let sound_data = &commercials[313..301 + 11 + 183];
+    assert_eq!(mp3_frame_header(sound_data).unwrap().1.layer, 3);
+    assert_eq!(mp3_is_vbr(sound_data), Some(false));
+  }
+}
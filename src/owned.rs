@@ -0,0 +1,321 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::amf3::{AMF3Property, AMF3Value};
+use crate::parser::{
+  AudioData, CodecId, FrameType, ScriptData, ScriptDataDate, ScriptDataObject, ScriptDataValue,
+  SoundFormat, SoundRate, SoundSize, SoundType, Tag, TagData, TagHeader, VideoData,
+};
+
+/// A `Tag` that owns its data, to be kept after the input buffer is reused.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OwnedTag {
+  pub header: TagHeader,
+  pub data: OwnedTagData,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum OwnedTagData {
+  Audio(OwnedAudioData),
+  Video(OwnedVideoData),
+  /// The script tag body, see `TagData::Script`.
+  Script(Vec<u8>),
+  /// See `TagData::ExAudio`.
+  ExAudio(Vec<u8>),
+  /// See `TagData::ExVideo`.
+  ExVideo(Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OwnedAudioData {
+  pub sound_format: SoundFormat,
+  pub sound_rate: SoundRate,
+  pub sound_size: SoundSize,
+  pub sound_type: SoundType,
+  pub sound_data: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OwnedVideoData {
+  pub frame_type: FrameType,
+  pub codec_id: CodecId,
+  pub video_data: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OwnedScriptData {
+  pub name: String,
+  pub arguments: OwnedScriptDataValue,
+}
+
+/// An owned `ScriptDataValue`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OwnedScriptDataValue {
+  Number(f64),
+  Boolean(bool),
+  String(String),
+  Object(Vec<OwnedScriptDataObject>),
+  MovieClip(String),
+  Null,
+  Undefined,
+  Reference(u16),
+  ECMAArray {
+    count: u32,
+    entries: Vec<OwnedScriptDataObject>,
+  },
+  StrictArray(Vec<OwnedScriptDataValue>),
+  Date(ScriptDataDate),
+  LongString(String),
+  TypedObject {
+    class_name: String,
+    properties: Vec<OwnedScriptDataObject>,
+  },
+  AMF3(OwnedAMF3Value),
+  RawString(Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OwnedScriptDataObject {
+  pub name: String,
+  pub data: OwnedScriptDataValue,
+}
+
+/// An owned `AMF3Value`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OwnedAMF3Value {
+  Undefined,
+  Null,
+  Boolean(bool),
+  Integer(i32),
+  Double(f64),
+  String(String),
+  RawString(Vec<u8>),
+  Array {
+    associative: Vec<OwnedAMF3Property>,
+    dense: Vec<OwnedAMF3Value>,
+  },
+  Object {
+    class_name: String,
+    sealed: Vec<OwnedAMF3Property>,
+    dynamic: Option<Vec<OwnedAMF3Property>>,
+  },
+  Reference(u32),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OwnedAMF3Property {
+  pub name: String,
+  pub value: OwnedAMF3Value,
+}
+
+impl From<Tag<'_>> for OwnedTag {
+  fn from(tag: Tag<'_>) -> Self {
+    OwnedTag {
+      header: tag.header,
+      data: tag.data.into(),
+    }
+  }
+}
+
+impl From<TagData<'_>> for OwnedTagData {
+  fn from(data: TagData<'_>) -> Self {
+    match data {
+      TagData::Audio(audio) => OwnedTagData::Audio(audio.into()),
+      TagData::Video(video) => OwnedTagData::Video(video.into()),
+      TagData::Script(body) => OwnedTagData::Script(body.to_vec()),
+      TagData::ExAudio(body) => OwnedTagData::ExAudio(body.to_vec()),
+      TagData::ExVideo(body) => OwnedTagData::ExVideo(body.to_vec()),
+    }
+  }
+}
+
+impl From<AudioData<'_>> for OwnedAudioData {
+  fn from(audio: AudioData<'_>) -> Self {
+    OwnedAudioData {
+      sound_format: audio.sound_format,
+      sound_rate: audio.sound_rate,
+      sound_size: audio.sound_size,
+      sound_type: audio.sound_type,
+      sound_data: audio.sound_data.to_vec(),
+    }
+  }
+}
+
+impl From<VideoData<'_>> for OwnedVideoData {
+  fn from(video: VideoData<'_>) -> Self {
+    OwnedVideoData {
+      frame_type: video.frame_type,
+      codec_id: video.codec_id,
+      video_data: video.video_data.to_vec(),
+    }
+  }
+}
+
+impl From<ScriptData<'_>> for OwnedScriptData {
+  fn from(script: ScriptData<'_>) -> Self {
+    OwnedScriptData {
+      name: script.name.to_string(),
+      arguments: script.arguments.into(),
+    }
+  }
+}
+
+fn owned_objects(objects: Vec<ScriptDataObject<'_>>) -> Vec<OwnedScriptDataObject> {
+  objects.into_iter().map(Into::into).collect()
+}
+
+impl From<ScriptDataObject<'_>> for OwnedScriptDataObject {
+  fn from(object: ScriptDataObject<'_>) -> Self {
+    OwnedScriptDataObject {
+      name: object.name.to_string(),
+      data: object.data.into(),
+    }
+  }
+}
+
+impl From<ScriptDataValue<'_>> for OwnedScriptDataValue {
+  fn from(value: ScriptDataValue<'_>) -> Self {
+    match value {
+      ScriptDataValue::Number(n) => OwnedScriptDataValue::Number(n),
+      ScriptDataValue::Boolean(b) => OwnedScriptDataValue::Boolean(b),
+      ScriptDataValue::String(s) => OwnedScriptDataValue::String(s.to_string()),
+      ScriptDataValue::Object(o) => OwnedScriptDataValue::Object(owned_objects(o)),
+      ScriptDataValue::MovieClip(s) => OwnedScriptDataValue::MovieClip(s.to_string()),
+      ScriptDataValue::Null => OwnedScriptDataValue::Null,
+      ScriptDataValue::Undefined => OwnedScriptDataValue::Undefined,
+      ScriptDataValue::Reference(r) => OwnedScriptDataValue::Reference(r),
+      ScriptDataValue::ECMAArray { count, entries } => OwnedScriptDataValue::ECMAArray {
+        count,
+        entries: owned_objects(entries),
+      },
+      ScriptDataValue::StrictArray(v) => {
+        OwnedScriptDataValue::StrictArray(v.into_iter().map(Into::into).collect())
+      }
+      ScriptDataValue::Date(d) => OwnedScriptDataValue::Date(d),
+      ScriptDataValue::LongString(s) => OwnedScriptDataValue::LongString(s.to_string()),
+      ScriptDataValue::TypedObject {
+        class_name,
+        properties,
+      } => OwnedScriptDataValue::TypedObject {
+        class_name: class_name.to_string(),
+        properties: owned_objects(properties),
+      },
+      ScriptDataValue::AMF3(v) => OwnedScriptDataValue::AMF3(v.into()),
+      ScriptDataValue::RawString(b) => OwnedScriptDataValue::RawString(b.to_vec()),
+    }
+  }
+}
+
+fn owned_properties(properties: Vec<AMF3Property<'_>>) -> Vec<OwnedAMF3Property> {
+  properties.into_iter().map(Into::into).collect()
+}
+
+impl From<AMF3Property<'_>> for OwnedAMF3Property {
+  fn from(property: AMF3Property<'_>) -> Self {
+    OwnedAMF3Property {
+      name: property.name.to_string(),
+      value: property.value.into(),
+    }
+  }
+}
+
+impl From<AMF3Value<'_>> for OwnedAMF3Value {
+  fn from(value: AMF3Value<'_>) -> Self {
+    match value {
+      AMF3Value::Undefined => OwnedAMF3Value::Undefined,
+      AMF3Value::Null => OwnedAMF3Value::Null,
+      AMF3Value::Boolean(b) => OwnedAMF3Value::Boolean(b),
+      AMF3Value::Integer(n) => OwnedAMF3Value::Integer(n),
+      AMF3Value::Double(n) => OwnedAMF3Value::Double(n),
+      AMF3Value::String(s) => OwnedAMF3Value::String(s.to_string()),
+      AMF3Value::RawString(b) => OwnedAMF3Value::RawString(b.to_vec()),
+      AMF3Value::Array { associative, dense } => OwnedAMF3Value::Array {
+        associative: owned_properties(associative),
+        dense: dense.into_iter().map(Into::into).collect(),
+      },
+      AMF3Value::Object {
+        class_name,
+        sealed,
+        dynamic,
+      } => OwnedAMF3Value::Object {
+        class_name: class_name.to_string(),
+        sealed: owned_properties(sealed),
+        dynamic: dynamic.map(owned_properties),
+      },
+      AMF3Value::Reference(r) => OwnedAMF3Value::Reference(r),
+    }
+  }
+}
+
+#[allow(non_upper_case_globals)]
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::{raw_tags, script_data, tags};
+  use alloc::vec;
+
+  const zelda: &[u8] = include_bytes!("../assets/zelda.flv");
+  const commercials: &[u8] = include_bytes!("../assets/asian-commercials-are-weird.flv");
+
+  #[test]
+  fn owned_tags() {
+    let buffer = zelda.to_vec();
+    let owned: Vec<OwnedTag> = tags(&buffer).map(|tag| tag.unwrap().into()).collect();
+    drop(buffer);
+
+    assert_eq!(owned.len(), 998);
+    match &owned[0].data {
+      OwnedTagData::Video(video) => {
+        assert_eq!(video.frame_type, FrameType::Key);
+        assert_eq!(video.video_data.len(), 536);
+        let body = raw_tags(zelda).next().unwrap().body().unwrap();
+        assert_eq!(&video.video_data[..], &body[1..]);
+      }
+      _ => panic!("expected a video tag"),
+    }
+    assert!(owned
+      .iter()
+      .any(|tag| matches!(tag.data, OwnedTagData::Audio(_))));
+  }
+
+  #[test]
+  fn owned_script_data() {
+    let body = raw_tags(commercials).next().unwrap().body().unwrap();
+    let (_, script) = script_data(body).unwrap();
+    let owned = OwnedScriptData::from(script);
+    assert_eq!(owned.name, "onMetaData");
+    match owned.arguments {
+      OwnedScriptDataValue::ECMAArray {
+        count,
+        entries: properties,
+      } => {
+        assert_eq!(count, 7);
+        assert_eq!(properties[0].name, "duration");
+        assert!(properties
+          .iter()
+          .any(|p| p.name == "creationdate" && matches!(p.data, OwnedScriptDataValue::String(_))));
+      }
+      _ => panic!("expected an ECMA array"),
+    }
+
+    let value = AMF3Value::Object {
+      class_name: "",
+      sealed: vec![],
+      dynamic: Some(vec![AMF3Property {
+        name: "a",
+        value: AMF3Value::String("b"),
+      }]),
+    };
+    assert_eq!(
+      OwnedScriptDataValue::from(ScriptDataValue::AMF3(value)),
+      OwnedScriptDataValue::AMF3(OwnedAMF3Value::Object {
+        class_name: String::new(),
+        sealed: vec![],
+        dynamic: Some(vec![OwnedAMF3Property {
+          name: "a".to_string(),
+          value: OwnedAMF3Value::String("b".to_string()),
+        }]),
+      })
+    );
+  }
+}
@@ -1,15 +1,235 @@
-use std::str::from_utf8;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::str::from_utf8;
 
 use nom::bits::bits;
 use nom::bits::streaming::take;
-use nom::bytes::streaming::tag;
+use nom::bytes::streaming::{tag, take as take_bytes};
 use nom::combinator::{flat_map, map, map_res};
-use nom::error::{Error, ErrorKind};
-use nom::multi::{length_data, many0, many_m_n};
+use nom::error::ErrorKind;
+#[cfg(feature = "alloc")]
+use nom::multi::{count, length_count, length_data};
 use nom::number::streaming::{be_f64, be_i16, be_i24, be_u16, be_u24, be_u32, be_u8};
-use nom::sequence::{pair, terminated, tuple};
+use nom::sequence::{pair, tuple};
 use nom::{Err, IResult, Needed};
 
+#[cfg(feature = "alloc")]
+use crate::amf3::{amf3_value, AMF3Value};
+use crate::error::FlvError;
+#[cfg(feature = "alloc")]
+use crate::h264::sps_timing;
+use crate::screen::{screen_video_packet, ScreenVideoPacket};
+use crate::vp6::{vp6_video_packet, Vp6VideoPacket};
+
+/// Options changing how the parsers deal with malformed input. The default
+/// options are the most lenient.
+///
+/// The options are applied by `Parser` and `stream::FlvParser`. The whole
+/// file parsers are `Parser::tags`, `Parser::tags_of_type`,
+/// `Parser::keyframe_tags`, `Parser::parse_flv` and `stream::FlvParser`.
+/// More options may be added, so set the fields of
+/// `ParserOptions::default()` instead of building the struct.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ParserOptions {
+  /// Report AMF0 string lengths going past the end of the input as a
+  /// recoverable `FlvError::BadLength`, instead of asking for more data with
+  /// `Incomplete`. Use this on complete script data, so that a bad value can
+  /// be told apart from a truncated stream and skipped. Script tag bodies
+  /// parsed with `script_tag_records` are always checked, see
+  /// `FlvError::StringOverrun`.
+  pub strict_lengths: bool,
+  /// Report reserved bits and fields that are not zero as
+  /// `FlvError::ReservedBitsSet`, in the file and tag headers: the reserved
+  /// flags of the file header, the 2 upper bits of the tag type byte and the
+  /// stream id. Use this to reject non conforming files.
+  pub strict_reserved: bool,
+  /// In the whole file parsers, stop before a last tag cut short, or missing
+  /// its PreviousTagSize, instead of returning `Incomplete`, or
+  /// `SizeMismatch` with `check_data_size`. The remaining input of
+  /// `Parser::parse_flv` starts at that tag. Use this on files from an
+  /// interrupted recording.
+  pub allow_truncated_tag: bool,
+  /// Largest element count accepted for AMF0 strict arrays and the dense
+  /// part of AMF3 arrays, above which they fail with
+  /// `FlvError::ArrayTooLarge` before reading any element. Defaults to
+  /// `ParserOptions::DEFAULT_MAX_ARRAY_LEN`. The count of ECMA arrays is not
+  /// checked: it is only a hint, their properties are read up to the object
+  /// end marker.
+  pub max_array_len: u32,
+  /// Report a tag body shorter than its `data_size` as
+  /// `FlvError::SizeMismatch` instead of asking for more data with
+  /// `Incomplete`, for inputs known to be complete, like a file held in
+  /// memory. This applies to every `Parser` method reading tags.
+  pub check_data_size: bool,
+  /// Reject AVC sequence headers and end of sequence packets with a non zero
+  /// composition time, as the specification requires, with
+  /// `FlvError::NonZeroCompositionTime`.
+  pub strict_composition_time: bool,
+  /// Keep script data string values that are not UTF-8 as
+  /// `ScriptDataValue::RawString`, or `AMF3Value::RawString` in AMF3 values,
+  /// instead of failing the whole script data. Property names and the script
+  /// data name must still be UTF-8.
+  pub raw_invalid_strings: bool,
+  /// Reject file headers that version 1 of the format does not allow: a
+  /// version other than 1, as `FlvError::UnsupportedVersion`, a data offset
+  /// smaller than the 9 bytes of the header, as `FlvError::BadDataOffset`,
+  /// and reserved flags, as with `strict_reserved`. A larger offset is
+  /// accepted, the whole file parsers skip the bytes up to it.
+  pub strict_header: bool,
+  /// In the whole file parsers, reject a PreviousTagSize right after the file
+  /// header that is not 0, as `FlvError::NonZeroInitialTagSize`. Spliced
+  /// files often break this.
+  pub strict_initial_tag_size: bool,
+  /// In the whole file parsers, reject audio tags when the file header clears
+  /// the audio flag, and video tags when it clears the video flag, as
+  /// `FlvError::UnexpectedTagType`. Script tags are always accepted.
+  pub strict_tag_types: bool,
+  /// In `Parser::tags` and the other tag iterators, wrap the errors other
+  /// than `Incomplete` in `FlvError::Context` with the offset of the tag that
+  /// failed. This is off by default, so that another crate enabling the
+  /// `debug` feature does not change the errors this one gets.
+  #[cfg(feature = "debug")]
+  pub error_context: bool,
+}
+
+impl ParserOptions {
+  /// Far more than any real file, while keeping a forged count from
+  /// reserving gigabytes.
+  pub const DEFAULT_MAX_ARRAY_LEN: u32 = 1 << 22;
+}
+
+impl Default for ParserOptions {
+  fn default() -> Self {
+    ParserOptions {
+      strict_lengths: false,
+      strict_reserved: false,
+      allow_truncated_tag: false,
+      max_array_len: ParserOptions::DEFAULT_MAX_ARRAY_LEN,
+      check_data_size: false,
+      strict_composition_time: false,
+      raw_invalid_strings: false,
+      strict_header: false,
+      strict_initial_tag_size: false,
+      strict_tag_types: false,
+      #[cfg(feature = "debug")]
+      error_context: false,
+    }
+  }
+}
+
+/// Entry point to the parsers reading `ParserOptions`.
+///
+/// `Parser::new()` uses the default options and behaves like the free
+/// functions of the same names; `Parser::with_options` applies every option
+/// relevant to each structure.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Parser {
+  options: ParserOptions,
+}
+
+impl Parser {
+  pub fn new() -> Self {
+    Parser::default()
+  }
+
+  pub fn with_options(options: ParserOptions) -> Self {
+    Parser { options }
+  }
+
+  pub fn options(&self) -> &ParserOptions {
+    &self.options
+  }
+
+  pub fn header<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], Header, FlvError> {
+    flv_header(input, &self.options)
+  }
+
+  pub fn tag_header<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], TagHeader, FlvError> {
+    flv_tag_header(input, &self.options)
+  }
+
+  pub fn complete_tag<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], Tag<'a>, FlvError> {
+    flv_tag(input, &self.options)
+  }
+
+  pub fn tag_with_size<'a>(
+    &self,
+    input: &'a [u8],
+  ) -> IResult<&'a [u8], (Tag<'a>, PreviousTagSize), FlvError> {
+    flv_tag_with_size(input, &self.options)
+  }
+
+  pub fn avc_video_packet_header<'a>(
+    &self,
+    input: &'a [u8],
+  ) -> IResult<&'a [u8], AVCVideoPacketHeader, FlvError> {
+    let (i, header) = avc_video_packet_header(input)?;
+    if self.options.strict_composition_time {
+      check_composition_time(header.packet_type, header.composition_time)?;
+    }
+    Ok((i, header))
+  }
+
+  pub fn avc_video_packet<'a>(
+    &self,
+    input: &'a [u8],
+    size: usize,
+  ) -> IResult<&'a [u8], AVCVideoPacket<'a>, FlvError> {
+    let (i, packet) = avc_video_packet(input, size)?;
+    if self.options.strict_composition_time {
+      check_composition_time(packet.packet_type, packet.composition_time)?;
+    }
+    Ok((i, packet))
+  }
+
+  pub fn tags<'a>(&self, input: &'a [u8]) -> TagIter<'a> {
+    TagIter::new(input, self.options)
+  }
+
+  pub fn tags_of_type<'a>(&self, input: &'a [u8], tag_type: TagType) -> TagsOfType<'a> {
+    TagsOfType {
+      tags: self.tags(input),
+      tag_type,
+    }
+  }
+
+  pub fn keyframe_tags<'a>(&self, input: &'a [u8]) -> KeyframeTags<'a> {
+    KeyframeTags {
+      tags: self.tags(input),
+    }
+  }
+
+  #[cfg(feature = "alloc")]
+  pub fn script_data<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], ScriptData<'a>, FlvError> {
+    amf_script_data(input, &self.options)
+  }
+
+  #[cfg(feature = "alloc")]
+  pub fn script_data_value<'a>(
+    &self,
+    input: &'a [u8],
+  ) -> IResult<&'a [u8], ScriptDataValue<'a>, FlvError> {
+    amf_value(input, &self.options)
+  }
+
+  #[cfg(feature = "alloc")]
+  pub fn script_tag_records<'a>(
+    &self,
+    body: &'a [u8],
+  ) -> IResult<&'a [u8], Vec<ScriptData<'a>>, FlvError> {
+    amf_script_tag_records(body, &self.options)
+  }
+
+  #[cfg(feature = "alloc")]
+  pub fn parse_flv<'a>(
+    &self,
+    input: &'a [u8],
+  ) -> IResult<&'a [u8], (Header, Vec<Tag<'a>>), FlvError> {
+    parse_flv_while(input, |_, _| true, 0, |_| {}, &self.options)
+  }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Header {
   pub version: u8,
@@ -18,16 +238,49 @@ pub struct Header {
   pub offset: u32,
 }
 
-pub fn header(input: &[u8]) -> IResult<&[u8], Header> {
-  map(
-    tuple((tag("FLV"), be_u8, be_u8, be_u32)),
-    |(_, version, flags, offset)| Header {
+pub fn header(input: &[u8]) -> IResult<&[u8], Header, FlvError> {
+  flv_header(input, &ParserOptions::default())
+}
+
+fn flv_header<'a>(input: &'a [u8], options: &ParserOptions) -> IResult<&'a [u8], Header, FlvError> {
+  let (i, (_, version, flags, offset)) = tuple((tag("FLV"), be_u8, be_u8, be_u32))(input)?;
+  if options.strict_header && version != 1 {
+    return Err(Err::Error(FlvError::UnsupportedVersion(version)));
+  }
+  if options.strict_header && offset < 9 {
+    return Err(Err::Error(FlvError::BadDataOffset(offset)));
+  }
+  if (options.strict_reserved || options.strict_header) && flags & 0xfa != 0 {
+    return Err(Err::Error(FlvError::ReservedBitsSet("TypeFlagsReserved")));
+  }
+  Ok((
+    i,
+    Header {
       version,
       audio: flags & 4 == 4,
       video: flags & 1 == 1,
       offset,
     },
-  )(input)
+  ))
+}
+
+/// Offset of the first tag in the file: the header `offset` plus the first
+/// PreviousTagSize.
+pub fn body_start(header: &Header) -> usize {
+  header.offset as usize + 4
+}
+
+/// Returns the input from the first tag on, after the bytes up to the header
+/// `offset` and the first PreviousTagSize, or an empty slice if the input
+/// ends before.
+pub fn skip_to_first_tag<'a>(input: &'a [u8], header: &Header) -> &'a [u8] {
+  input.get(body_start(header)..).unwrap_or(&[])
+}
+
+/// Offset of the tag following the one at `offset`, after its header, body
+/// and PreviousTagSize.
+fn next_tag_offset(offset: usize, header: &TagHeader) -> usize {
+  offset + 11 + header.data_size as usize + 4
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -37,19 +290,197 @@ pub enum TagType {
   Script,
 }
 
+impl TryFrom<u8> for TagType {
+  type Error = FlvError;
+
+  fn try_from(tag_type: u8) -> Result<Self, Self::Error> {
+    match tag_type {
+      8 => Ok(TagType::Audio),
+      9 => Ok(TagType::Video),
+      18 => Ok(TagType::Script),
+      _ => Err(FlvError::UnknownTagType(tag_type)),
+    }
+  }
+}
+
+impl From<TagType> for u8 {
+  fn from(tag_type: TagType) -> Self {
+    match tag_type {
+      TagType::Audio => 8,
+      TagType::Video => 9,
+      TagType::Script => 18,
+    }
+  }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct TagHeader {
   pub tag_type: TagType,
   pub data_size: u32,
+  /// In milliseconds, already combined from its two fields, see `Timestamp`.
   pub timestamp: u32,
   pub stream_id: u32,
+  /// The filter bit of the tag type byte: the body is encrypted, and an
+  /// encryption header follows the audio or video tag header, see
+  /// `encryption_header`.
+  pub filtered: bool,
+}
+
+/// A tag timestamp in milliseconds.
+///
+/// On the wire it is a 24-bit big endian base followed by an 8-bit extension
+/// holding the upper bits, so the full value is
+/// `(extended << 24) + base` and wraps around after 2^32 ms, about 49 days.
+/// Some muxers leave the extension at 0, and their timestamps wrap after
+/// 2^24 ms, about 4.6 hours, see `WrappingTimestamps`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(pub u32);
+
+impl Timestamp {
+  /// Combines the 24-bit base and the 8-bit extension of a tag header.
+  pub fn from_parts(base: u32, extended: u8) -> Timestamp {
+    Timestamp((u32::from(extended) << 24) + (base & 0x00ff_ffff))
+  }
+
+  pub fn as_millis(self) -> u32 {
+    self.0
+  }
+
+  /// The lower 24 bits, written in the `Timestamp` field.
+  pub fn base(self) -> u32 {
+    self.0 & 0x00ff_ffff
+  }
+
+  /// The upper 8 bits, written in the `TimestampExtended` field.
+  pub fn extended(self) -> u8 {
+    (self.0 >> 24) as u8
+  }
+}
+
+impl From<u32> for Timestamp {
+  fn from(millis: u32) -> Self {
+    Timestamp(millis)
+  }
+}
+
+/// Turns the timestamps of a tag sequence into monotonic 64-bit ones, by
+/// counting how many times they wrapped around.
+///
+/// A timestamp going back by more than half the range is a wraparound, a
+/// smaller step back is kept as is, since audio and video tags are not
+/// always strictly interleaved. A late tag from before the last wraparound
+/// is placed back in the previous period.
+#[derive(Clone, Debug)]
+pub struct WrappingTimestamps {
+  mask: u32,
+  last: Option<u32>,
+  offset: u64,
+}
+
+impl WrappingTimestamps {
+  /// For timestamps using all 32 bits.
+  pub fn new() -> WrappingTimestamps {
+    WrappingTimestamps {
+      mask: u32::MAX,
+      last: None,
+      offset: 0,
+    }
+  }
+
+  /// For muxers that never write the extension byte: timestamps wrap after
+  /// 2^24 ms, and the extension is ignored.
+  pub fn without_extension() -> WrappingTimestamps {
+    WrappingTimestamps {
+      mask: 0x00ff_ffff,
+      last: None,
+      offset: 0,
+    }
+  }
+
+  fn period(&self) -> u64 {
+    u64::from(self.mask) + 1
+  }
+
+  /// Returns the monotonic timestamp in milliseconds of the next tag.
+  pub fn push(&mut self, timestamp: Timestamp) -> u64 {
+    let timestamp = timestamp.as_millis() & self.mask;
+    let half = self.mask / 2;
+    match self.last {
+      Some(last) if last > timestamp && last - timestamp > half => {
+        self.offset += self.period();
+      }
+      Some(last) if timestamp > last && timestamp - last > half && self.offset > 0 => {
+        return self.offset - self.period() + u64::from(timestamp);
+      }
+      _ => {}
+    }
+    self.last = Some(timestamp);
+    self.offset + u64::from(timestamp)
+  }
+}
+
+impl Default for WrappingTimestamps {
+  fn default() -> Self {
+    WrappingTimestamps::new()
+  }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum TagData<'a> {
   Audio(AudioData<'a>),
   Video(VideoData<'a>),
-  Script,
+  /// The body of a script tag, left for `script_tag_records` to parse, so
+  /// that a bad script tag does not stop the iteration over the media tags.
+  /// The body of a filtered tag is encrypted, see `TagHeader::filtered`.
+  ///
+  /// It is not parsed into `ScriptData`, which needs `alloc` and can't be
+  /// compared with `Eq`, so that `TagData` has the same shape in every
+  /// configuration.
+  Script(&'a [u8]),
+  /// The body of an enhanced RTMP audio tag, first byte included, see
+  /// `is_ex_audio_header`. Parse it with `ex_audio_multitrack` or the parser
+  /// for its FourCC, like `opus_audio_packet`.
+  ExAudio(&'a [u8]),
+  /// The body of an enhanced RTMP video tag, first byte included, see
+  /// `is_ex_video_header`. Parse it with `ex_video_multitrack` or the parser
+  /// for its FourCC, like `hevc_video_packet`.
+  ExVideo(&'a [u8]),
+}
+
+impl<'a> TagData<'a> {
+  pub fn as_audio(&self) -> Option<&AudioData<'a>> {
+    match self {
+      TagData::Audio(audio) => Some(audio),
+      _ => None,
+    }
+  }
+
+  pub fn as_video(&self) -> Option<&VideoData<'a>> {
+    match self {
+      TagData::Video(video) => Some(video),
+      _ => None,
+    }
+  }
+
+  /// The raw body of a script tag, see `TagData::Script`.
+  pub fn as_script(&self) -> Option<&'a [u8]> {
+    match self {
+      TagData::Script(body) => Some(body),
+      _ => None,
+    }
+  }
+}
+
+impl<'a> From<AudioData<'a>> for TagData<'a> {
+  fn from(audio: AudioData<'a>) -> Self {
+    TagData::Audio(audio)
+  }
+}
+
+impl<'a> From<VideoData<'a>> for TagData<'a> {
+  fn from(video: VideoData<'a>) -> Self {
+    TagData::Video(video)
+  }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -58,875 +489,5678 @@ pub struct Tag<'a> {
   pub data: TagData<'a>,
 }
 
-fn tag_type(input: &[u8]) -> IResult<&[u8], TagType> {
-  map_res(be_u8, |tag_type| {
-    Ok(match tag_type {
-      8 => TagType::Audio,
-      9 => TagType::Video,
-      18 => TagType::Script,
-      _ => return Err(Err::Error(Error::new(input, ErrorKind::Alt))),
-    })
+impl<'a> Tag<'a> {
+  /// Key giving the order in which muxers interleave tags: by timestamp, then
+  /// script tags first, video sequence headers next, and media data last.
+  pub fn sort_key(&self) -> (u32, u8) {
+    let priority = match &self.data {
+      TagData::Script(_) => 0,
+      TagData::Video(video) if video.is_sequence_header() => 1,
+      TagData::ExVideo(body) if matches!(body.first(), Some(first) if first & 0x0f == 0) => 1,
+      TagData::Audio(_) | TagData::Video(_) | TagData::ExAudio(_) | TagData::ExVideo(_) => 2,
+    };
+    (self.header.timestamp, priority)
+  }
+
+  /// The track id of an enhanced RTMP multitrack tag, of the first track if
+  /// it carries several. Returns `None` for legacy and single track tags,
+  /// see `ex_track_id`.
+  pub fn track_id(&self) -> Option<u8> {
+    match self.data {
+      TagData::ExAudio(body) => ex_track_id(TagType::Audio, body),
+      TagData::ExVideo(body) => ex_track_id(TagType::Video, body),
+      _ => None,
+    }
+  }
+
+  /// Whether both tags belong to the same stream configuration: same tag
+  /// type and stream id, and the same codec parameters. Payloads, sizes and
+  /// timestamps are ignored, so a difference means the stream was
+  /// reconfigured mid-file.
+  ///
+  /// For video, key and inter frames are the same class, but command frames
+  /// are told apart from picture frames.
+  pub fn same_stream_config(&self, other: &Tag<'_>) -> bool {
+    if self.header.tag_type != other.header.tag_type
+      || self.header.stream_id != other.header.stream_id
+    {
+      return false;
+    }
+
+    match (&self.data, &other.data) {
+      (TagData::Audio(a), TagData::Audio(b)) => {
+        a.sound_format == b.sound_format
+          && a.sound_rate == b.sound_rate
+          && a.sound_size == b.sound_size
+          && a.sound_type == b.sound_type
+      }
+      (TagData::Video(a), TagData::Video(b)) => {
+        a.codec_id == b.codec_id
+          && (a.frame_type == FrameType::Command) == (b.frame_type == FrameType::Command)
+      }
+      (TagData::Script(_), TagData::Script(_)) => true,
+      (TagData::ExAudio(a), TagData::ExAudio(b)) => {
+        ex_audio_tag_header(a).ok().map(|(_, h)| h.fourcc)
+          == ex_audio_tag_header(b).ok().map(|(_, h)| h.fourcc)
+      }
+      (TagData::ExVideo(a), TagData::ExVideo(b)) => {
+        ex_video_tag_header(a).ok().map(|(_, h)| h.fourcc)
+          == ex_video_tag_header(b).ok().map(|(_, h)| h.fourcc)
+      }
+      _ => false,
+    }
+  }
+}
+
+/// Reads the track id from the raw body of an enhanced RTMP multitrack audio
+/// or video tag, as found in `TagData::ExAudio` and `TagData::ExVideo`, see
+/// `Tag::track_id`. For tags carrying
+/// several tracks, this is the id of the first one. Returns `None` for legacy
+/// and single track enhanced tags.
+pub fn ex_track_id(tag_type: TagType, body: &[u8]) -> Option<u8> {
+  let first = *body.first()?;
+  let multitrack = match tag_type {
+    TagType::Audio => is_ex_audio_header(first) && first & 0x0f == 5,
+    TagType::Video => is_ex_video_header(first) && first & 0x0f == 6,
+    TagType::Script => false,
+  };
+  if !multitrack {
+    return None;
+  }
+  // the multitrack type and packet type byte, then a FourCC, either shared
+  // or the first track's, then the track id
+  body.get(6).copied()
+}
+
+/// How the tracks of an enhanced RTMP multitrack packet are laid out, the
+/// high nibble of the byte after the first one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AvMultitrackType {
+  /// A single track, with the rest of the body as payload.
+  OneTrack,
+  /// Several tracks of the same codec, each with its size.
+  ManyTracks,
+  /// Several tracks, each with its FourCC and size.
+  ManyTracksManyCodecs,
+}
+
+/// One track of an enhanced RTMP multitrack packet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MultitrackPacket<'a> {
+  pub track_id: u8,
+  /// The codec FourCC, shared by all tracks unless they have many codecs.
+  pub fourcc: [u8; 4],
+  /// The codec payload, as in a single track packet of the same packet
+  /// type, without the enhanced header.
+  pub payload: &'a [u8],
+}
+
+/// Iterator over the tracks of a multitrack packet, see
+/// `ex_audio_multitrack` and `ex_video_multitrack`. It stops after the
+/// first error, a `FlvError::BadLength` for a track going past the body.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MultitrackPackets<'a> {
+  multitrack_type: AvMultitrackType,
+  /// The shared FourCC, `None` for many codecs.
+  fourcc: Option<[u8; 4]>,
+  input: &'a [u8],
+  failed: bool,
+}
+
+impl<'a> MultitrackPackets<'a> {
+  fn track(&self) -> Result<(&'a [u8], MultitrackPacket<'a>), FlvError> {
+    let truncated = |declared: usize, available: usize| FlvError::BadLength {
+      field: "track",
+      declared,
+      available,
+    };
+    let mut i = self.input;
+    let fourcc = match self.fourcc {
+      Some(fourcc) => fourcc,
+      None => {
+        if i.len() < 4 {
+          return Err(truncated(4, i.len()));
+        }
+        let (fourcc, rest) = i.split_at(4);
+        i = rest;
+        [fourcc[0], fourcc[1], fourcc[2], fourcc[3]]
+      }
+    };
+    let (&track_id, i) = i.split_first().ok_or_else(|| truncated(1, 0))?;
+    let (i, payload) = match self.multitrack_type {
+      AvMultitrackType::OneTrack => (&i[i.len()..], i),
+      _ => {
+        if i.len() < 3 {
+          return Err(truncated(3, i.len()));
+        }
+        let size = (usize::from(i[0]) << 16) | (usize::from(i[1]) << 8) | usize::from(i[2]);
+        let i = &i[3..];
+        if i.len() < size {
+          return Err(truncated(size, i.len()));
+        }
+        (&i[size..], &i[..size])
+      }
+    };
+    Ok((
+      i,
+      MultitrackPacket {
+        track_id,
+        fourcc,
+        payload,
+      },
+    ))
+  }
+}
+
+impl<'a> Iterator for MultitrackPackets<'a> {
+  type Item = Result<MultitrackPacket<'a>, FlvError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.failed || self.input.is_empty() {
+      return None;
+    }
+    match self.track() {
+      Ok((rest, packet)) => {
+        self.input = rest;
+        Some(Ok(packet))
+      }
+      Err(e) => {
+        self.failed = true;
+        Some(Err(e))
+      }
+    }
+  }
+}
+
+/// Parses the multitrack type and inner packet type byte after the first
+/// byte of a multitrack body, then the shared FourCC if any.
+fn multitrack_header(input: &[u8]) -> Result<(u8, MultitrackPackets<'_>), Err<FlvError>> {
+  let (i, byte) = be_u8(input)?;
+  let multitrack_type = match byte >> 4 {
+    0 => AvMultitrackType::OneTrack,
+    1 => AvMultitrackType::ManyTracks,
+    2 => AvMultitrackType::ManyTracksManyCodecs,
+    multitrack_type => return Err(Err::Error(FlvError::UnknownMultitrackType(multitrack_type))),
+  };
+  let (i, fourcc) = match multitrack_type {
+    AvMultitrackType::ManyTracksManyCodecs => (i, None),
+    _ => {
+      let (i, fourcc) = take_bytes(4usize)(i)?;
+      (i, Some([fourcc[0], fourcc[1], fourcc[2], fourcc[3]]))
+    }
+  };
+
+  Ok((
+    byte & 0x0f,
+    MultitrackPackets {
+      multitrack_type,
+      fourcc,
+      input: i,
+      failed: false,
+    },
+  ))
+}
+
+/// The tag type byte is `[reserved:2][filter:1][tag type:5]`: returns the
+/// tag type and the filter bit, the reserved bits are ignored.
+fn tag_type(input: &[u8]) -> IResult<&[u8], (TagType, bool), FlvError> {
+  map_res(be_u8, |byte| {
+    TagType::try_from(byte & 0x1f).map(|tag_type| (tag_type, byte & 0x20 != 0))
   })(input)
 }
 
-pub fn tag_header(input: &[u8]) -> IResult<&[u8], TagHeader> {
+pub fn tag_header(input: &[u8]) -> IResult<&[u8], TagHeader, FlvError> {
   map(
     tuple((tag_type, be_u24, be_u24, be_u8, be_u24)),
-    |(tag_type, data_size, timestamp, timestamp_extended, stream_id)| TagHeader {
+    |((tag_type, filtered), data_size, timestamp, timestamp_extended, stream_id)| TagHeader {
       tag_type,
       data_size,
-      timestamp: (u32::from(timestamp_extended) << 24) + timestamp,
+      timestamp: Timestamp::from_parts(timestamp, timestamp_extended).as_millis(),
       stream_id,
+      filtered,
     },
   )(input)
 }
 
-pub fn complete_tag(input: &[u8]) -> IResult<&[u8], Tag> {
-  flat_map(pair(tag_type, be_u24), |(tag_type, data_size)| {
-    map(
-      tuple((
-        be_u24,
-        be_u8,
-        be_u24,
-        tag_data(tag_type, data_size as usize),
-      )),
-      move |(timestamp, timestamp_extended, stream_id, data)| Tag {
-        header: TagHeader {
-          tag_type,
-          data_size,
-          timestamp: (u32::from(timestamp_extended) << 24) + timestamp,
-          stream_id,
-        },
-        data,
-      },
-    )
-  })(input)
+fn flv_tag_header<'a>(
+  input: &'a [u8],
+  options: &ParserOptions,
+) -> IResult<&'a [u8], TagHeader, FlvError> {
+  if options.strict_reserved {
+    let (_, first) = be_u8(input)?;
+    if first & 0xc0 != 0 {
+      return Err(Err::Error(FlvError::ReservedBitsSet("TagType")));
+    }
+  }
+  let (i, header) = tag_header(input)?;
+  if options.strict_reserved && header.stream_id != 0 {
+    return Err(Err::Error(FlvError::ReservedBitsSet("StreamID")));
+  }
+  Ok((i, header))
 }
 
-pub fn tag_data(tag_type: TagType, size: usize) -> impl Fn(&[u8]) -> IResult<&[u8], TagData> {
-  move |input| match tag_type {
-    TagType::Video => map(|i| video_data(i, size), TagData::Video)(input),
-    TagType::Audio => map(|i| audio_data(i, size), TagData::Audio)(input),
-    TagType::Script => Ok((input, TagData::Script)),
-  }
+/// The filter of a tag with the `Filter` bit set, from its encryption
+/// header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Filter<'a> {
+  /// The `Encryption` filter: the whole payload is encrypted with this
+  /// initialization vector.
+  Encryption { iv: [u8; 16] },
+  /// The `SE` selective encryption filter: `iv` is only given when this
+  /// access unit is encrypted.
+  SelectiveEncryption { iv: Option<[u8; 16]> },
+  /// Another filter, with its raw parameters.
+  Other { name: &'a str, params: &'a [u8] },
 }
 
-#[allow(non_camel_case_types)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum SoundFormat {
-  PCM_NE, // native endianness...
-  ADPCM,
-  MP3,
-  PCM_LE,
-  NELLYMOSER_16KHZ_MONO,
-  NELLYMOSER_8KHZ_MONO,
-  NELLYMOSER,
-  PCM_ALAW,
-  PCM_ULAW,
-  AAC,
-  SPEEX,
-  MP3_8KHZ,
-  DEVICE_SPECIFIC,
+/// Parses the encryption header and filter parameters of a filtered tag,
+/// returning the encrypted payload as the remaining input.
+///
+/// They follow the audio or video tag header: the first byte of the body,
+/// plus the `AACPacketType` of AAC tags, or the `AVCPacketType` and
+/// composition time of AVC tags. Script tags start with them.
+pub fn encryption_header(input: &[u8]) -> IResult<&[u8], Filter<'_>, FlvError> {
+  let (i, num_filters) = be_u8(input)?;
+  // the specification only defines a single filter
+  if num_filters != 1 {
+    return Err(Err::Error(FlvError::UnsupportedFilterCount(num_filters)));
+  }
+  let (i, name) = script_data_string(i)?;
+  let (i, params) = flat_map(be_u24, take_bytes)(i)?;
+
+  let iv = |params: &[u8]| -> Result<[u8; 16], Err<FlvError>> {
+    params
+      .try_into()
+      .map_err(|_| Err::Error(FlvError::Nom(ErrorKind::LengthValue)))
+  };
+  let filter = match name {
+    "Encryption" => Filter::Encryption { iv: iv(params)? },
+    "SE" => match params.split_first() {
+      Some((&flags, rest)) if flags & 0x80 != 0 => Filter::SelectiveEncryption {
+        iv: Some(iv(rest)?),
+      },
+      Some((_, [])) => Filter::SelectiveEncryption { iv: None },
+      _ => return Err(Err::Error(FlvError::Nom(ErrorKind::LengthValue))),
+    },
+    _ => Filter::Other { name, params },
+  };
+  Ok((i, filter))
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum SoundRate {
-  _5_5KHZ,
-  _11KHZ,
-  _22KHZ,
-  _44KHZ,
+pub fn complete_tag(input: &[u8]) -> IResult<&[u8], Tag<'_>, FlvError> {
+  flat_map(
+    pair(tag_type, be_u24),
+    |((tag_type, filtered), data_size)| {
+      map(
+        tuple((
+          be_u24,
+          be_u8,
+          be_u24,
+          tag_data(tag_type, data_size as usize),
+        )),
+        move |(timestamp, timestamp_extended, stream_id, data)| Tag {
+          header: TagHeader {
+            tag_type,
+            data_size,
+            timestamp: Timestamp::from_parts(timestamp, timestamp_extended).as_millis(),
+            stream_id,
+            filtered,
+          },
+          data,
+        },
+      )
+    },
+  )(input)
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum SoundSize {
-  Snd8bit,
-  Snd16bit,
+fn flv_tag<'a>(input: &'a [u8], options: &ParserOptions) -> IResult<&'a [u8], Tag<'a>, FlvError> {
+  let (body, header) = flv_tag_header(input, options)?;
+  let size = header.data_size as usize;
+  if options.check_data_size && body.len() < size {
+    return Err(Err::Error(short_body(size, body.len(), options)));
+  }
+  let (i, data) = flv_tag_data(body, header.tag_type, size)?;
+  Ok((i, Tag { header, data }))
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum SoundType {
-  SndMono,
-  SndStereo,
+/// The error for a tag body of `available` bytes out of the `declared`
+/// `data_size`: `SizeMismatch` with `ParserOptions::check_data_size`,
+/// `Incomplete` otherwise.
+fn short_body(declared: usize, available: usize, options: &ParserOptions) -> FlvError {
+  if options.check_data_size {
+    FlvError::SizeMismatch {
+      declared,
+      available,
+    }
+  } else {
+    FlvError::Incomplete(Needed::new(declared - available))
+  }
 }
 
+/// The size of the previous tag, header included, that follows every tag.
+/// The one right after the file header is 0.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum AACPacketType {
-  SequenceHeader,
-  Raw,
-}
+pub struct PreviousTagSize(pub u32);
 
-#[derive(Clone, Debug, PartialEq, Eq)]
-pub struct AACAudioPacketHeader {
-  pub packet_type: AACPacketType,
+impl PreviousTagSize {
+  /// Whether this is the size of a tag with this header, `11 + data_size`.
+  pub fn matches(&self, header: &TagHeader) -> bool {
+    u64::from(self.0) == 11 + u64::from(header.data_size)
+  }
 }
 
-pub fn aac_audio_packet_header(input: &[u8]) -> IResult<&[u8], AACAudioPacketHeader> {
-  map_res(be_u8, |packet_type| {
-    Ok(AACAudioPacketHeader {
-      packet_type: match packet_type {
-        0 => AACPacketType::SequenceHeader,
-        1 => AACPacketType::Raw,
-        _ => return Err(Err::Error(Error::new(input, ErrorKind::Alt))),
-      },
-    })
-  })(input)
+pub fn previous_tag_size(input: &[u8]) -> IResult<&[u8], u32, FlvError> {
+  be_u32(input)
 }
 
-#[derive(Debug, PartialEq, Eq)]
-pub struct AACAudioPacket<'a> {
-  pub packet_type: AACPacketType,
-  pub aac_data: &'a [u8],
+/// Parses a complete tag followed by its PreviousTagSize. The tag body is
+/// delimited by `data_size`.
+pub fn tag_with_size(input: &[u8]) -> IResult<&[u8], (Tag<'_>, PreviousTagSize), FlvError> {
+  flv_tag_with_size(input, &ParserOptions::default())
 }
 
-pub fn aac_audio_packet(input: &[u8], size: usize) -> IResult<&[u8], AACAudioPacket> {
+fn flv_tag_with_size<'a>(
+  input: &'a [u8],
+  options: &ParserOptions,
+) -> IResult<&'a [u8], (Tag<'a>, PreviousTagSize), FlvError> {
+  let (_, header) = flv_tag_header(input, options)?;
+  let size = 11 + header.data_size as usize;
   if input.len() < size {
-    return Err(Err::Incomplete(Needed::new(size)));
+    return Err(match short_body(size - 11, input.len() - 11, options) {
+      FlvError::Incomplete(needed) => Err::Incomplete(needed),
+      e => Err::Error(e),
+    });
   }
+  let (_, tag) = flv_tag(&input[..size], options)?;
+  let (i, previous) = previous_tag_size(&input[size..])?;
+  Ok((i, (tag, PreviousTagSize(previous))))
+}
 
-  if size < 1 {
-    return Err(Err::Incomplete(Needed::new(1)));
-  }
+/// Parses a tag body of `size` bytes. The remaining input always starts
+/// right after the body, script data ending before it included.
+///
+/// Script tags and enhanced RTMP audio and video tags are kept as their raw
+/// body, see `TagData::Script`, `TagData::ExAudio` and `TagData::ExVideo`.
+pub fn tag_data(
+  tag_type: TagType,
+  size: usize,
+) -> impl Fn(&[u8]) -> IResult<&[u8], TagData<'_>, FlvError> {
+  move |input| flv_tag_data(input, tag_type, size)
+}
 
-  be_u8(input).and_then(|(_, packet_type)| {
-    Ok((
-      &input[size..],
-      AACAudioPacket {
-        packet_type: match packet_type {
-          0 => AACPacketType::SequenceHeader,
-          1 => AACPacketType::Raw,
-          _ => return Err(Err::Error(Error::new(input, ErrorKind::Alt))),
-        },
-        aac_data: &input[1..size],
-      },
-    ))
-  })
+fn flv_tag_data<'a>(
+  input: &'a [u8],
+  tag_type: TagType,
+  size: usize,
+) -> IResult<&'a [u8], TagData<'a>, FlvError> {
+  let raw = |data: fn(&'a [u8]) -> TagData<'a>| {
+    if input.len() < size {
+      return Err(Err::Incomplete(Needed::new(size - input.len())));
+    }
+    Ok((&input[size..], data(&input[..size])))
+  };
+  let first = if size > 0 {
+    input.first().copied()
+  } else {
+    None
+  };
+  match tag_type {
+    TagType::Video if matches!(first, Some(byte) if is_ex_video_header(byte)) => {
+      raw(TagData::ExVideo)
+    }
+    TagType::Audio if matches!(first, Some(byte) if is_ex_audio_header(byte)) => {
+      raw(TagData::ExAudio)
+    }
+    TagType::Video => map(|i| video_data(i, size), TagData::Video)(input),
+    TagType::Audio => map(|i| audio_data(i, size), TagData::Audio)(input),
+    TagType::Script => raw(TagData::Script),
+  }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
-pub struct AudioData<'a> {
-  pub sound_format: SoundFormat,
-  pub sound_rate: SoundRate,
-  pub sound_size: SoundSize,
-  pub sound_type: SoundType,
-  pub sound_data: &'a [u8],
+/// Parses every script data record of a script tag body, as found in
+/// `TagData::Script`: `onMetaData`, and the cue points some encoders write
+/// after it. A string going past the end of the body is
+/// `FlvError::StringOverrun` rather than `Incomplete`.
+#[cfg(feature = "alloc")]
+pub fn script_tag_records(body: &[u8]) -> IResult<&[u8], Vec<ScriptData<'_>>, FlvError> {
+  amf_script_tag_records(body, &ParserOptions::default())
 }
 
-pub fn audio_data(input: &[u8], size: usize) -> IResult<&[u8], AudioData> {
-  if input.len() < size {
-    return Err(Err::Incomplete(Needed::new(size)));
+#[cfg(feature = "alloc")]
+fn amf_script_tag_records<'a>(
+  body: &'a [u8],
+  options: &ParserOptions,
+) -> IResult<&'a [u8], Vec<ScriptData<'a>>, FlvError> {
+  let bounded = ParserOptions {
+    strict_lengths: true,
+    ..*options
+  };
+  let mut records = Vec::new();
+  let mut input = body;
+  while !input.is_empty() {
+    let (i, record) = script_tag_record(input, &bounded)?;
+    records.push(record);
+    input = i;
   }
+  Ok((input, records))
+}
 
-  if size < 1 {
-    return Err(Err::Incomplete(Needed::new(1)));
+#[cfg(feature = "alloc")]
+fn script_tag_record<'a>(
+  input: &'a [u8],
+  options: &ParserOptions,
+) -> IResult<&'a [u8], ScriptData<'a>, FlvError> {
+  match amf_script_data(input, options) {
+    Ok(record) => Ok(record),
+    Err(Err::Error(FlvError::BadLength {
+      field: "string" | "long string",
+      declared,
+      available,
+    })) => Err(Err::Error(FlvError::StringOverrun {
+      declared,
+      available,
+    })),
+    Err(e) => Err(e),
   }
+}
 
-  let take_bits = tuple((take(4usize), take(2usize), take(1usize), take(1usize)));
-  bits::<_, _, Error<_>, _, _>(take_bits)(input).and_then(|(_, (sformat, srate, ssize, stype))| {
-    let sformat = match sformat {
-      0 => SoundFormat::PCM_NE,
-      1 => SoundFormat::ADPCM,
-      2 => SoundFormat::MP3,
-      3 => SoundFormat::PCM_LE,
-      4 => SoundFormat::NELLYMOSER_16KHZ_MONO,
-      5 => SoundFormat::NELLYMOSER_8KHZ_MONO,
-      6 => SoundFormat::NELLYMOSER,
-      7 => SoundFormat::PCM_ALAW,
-      8 => SoundFormat::PCM_ULAW,
-      10 => SoundFormat::AAC,
-      11 => SoundFormat::SPEEX,
-      14 => SoundFormat::MP3_8KHZ,
-      15 => SoundFormat::DEVICE_SPECIFIC,
-      _ => return Err(Err::Error(Error::new(input, ErrorKind::Alt))),
-    };
-    let srate = match srate {
-      0 => SoundRate::_5_5KHZ,
-      1 => SoundRate::_11KHZ,
-      2 => SoundRate::_22KHZ,
-      3 => SoundRate::_44KHZ,
-      _ => return Err(Err::Error(Error::new(input, ErrorKind::Alt))),
-    };
-    let ssize = match ssize {
-      0 => SoundSize::Snd8bit,
-      1 => SoundSize::Snd16bit,
-      _ => return Err(Err::Error(Error::new(input, ErrorKind::Alt))),
-    };
-    let stype = match stype {
-      0 => SoundType::SndMono,
-      1 => SoundType::SndStereo,
-      _ => return Err(Err::Error(Error::new(input, ErrorKind::Alt))),
-    };
+/// The first byte of a tag body, parsed by `tag_header_with_data_header`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TagDataHeader {
+  Audio(AudioDataHeader),
+  Video(VideoDataHeader),
+  Script,
+}
 
-    Ok((
-      &input[size..],
-      AudioData {
-        sound_format: sformat,
-        sound_rate: srate,
-        sound_size: ssize,
-        sound_type: stype,
-        sound_data: &input[1..size],
-      },
-    ))
-  })
+/// Parses a tag header and the audio or video data header at the start of
+/// the body, then skips the rest of the body using `data_size`, without
+/// returning it. This is enough to index codecs and keyframes cheaply.
+///
+/// Enhanced RTMP audio and video headers are rejected like in
+/// `audio_data_header` and `video_data_header`.
+pub fn tag_header_with_data_header(
+  input: &[u8],
+) -> IResult<&[u8], (TagHeader, TagDataHeader), FlvError> {
+  let (body, header) = tag_header(input)?;
+  let size = header.data_size as usize;
+  if body.len() < size {
+    return Err(Err::Incomplete(Needed::new(size - body.len())));
+  }
+  let data_header = match header.tag_type {
+    TagType::Audio => TagDataHeader::Audio(audio_data_header(&body[..size])?.1),
+    TagType::Video => TagDataHeader::Video(video_data_header(&body[..size])?.1),
+    TagType::Script => TagDataHeader::Script,
+  };
+  Ok((&body[size..], (header, data_header)))
 }
 
-#[derive(Debug, PartialEq, Eq)]
-pub struct AudioDataHeader {
-  pub sound_format: SoundFormat,
-  pub sound_rate: SoundRate,
-  pub sound_size: SoundSize,
-  pub sound_type: SoundType,
+/// Iterator over the tags of a complete FLV file, see `tags`.
+#[derive(Clone, Debug)]
+pub struct TagIter<'a> {
+  input: &'a [u8],
+  /// Remaining input, `None` until the file header was skipped.
+  remaining: Option<&'a [u8]>,
+  /// The file header, once it was parsed.
+  header: Option<Header>,
+  failed: bool,
+  options: ParserOptions,
 }
 
-pub fn audio_data_header(input: &[u8]) -> IResult<&[u8], AudioDataHeader> {
-  if input.is_empty() {
-    return Err(Err::Incomplete(Needed::new(1)));
+/// Iterates over the tags of a complete FLV file held in memory: skips the
+/// file header and the first PreviousTagSize, then yields each tag, skipping
+/// the PreviousTagSize after it.
+///
+/// Iteration stops at the end of the input, or after yielding the first
+/// error, which is `FlvError::Incomplete` for a truncated last tag. With the
+/// `debug` feature, `ParserOptions::error_context` makes the other errors
+/// carry the offset of the tag that failed.
+pub fn tags(input: &[u8]) -> TagIter<'_> {
+  Parser::new().tags(input)
+}
+
+impl<'a> TagIter<'a> {
+  fn new(input: &'a [u8], options: ParserOptions) -> Self {
+    TagIter {
+      input,
+      remaining: None,
+      header: None,
+      failed: false,
+      options,
+    }
   }
 
-  let take_bits = tuple((take(4usize), take(2usize), take(1usize), take(1usize)));
-  map_res(
-    bits::<_, _, Error<_>, _, _>(take_bits),
-    |(sformat, srate, ssize, stype)| {
-      let sformat = match sformat {
-        0 => SoundFormat::PCM_NE,
-        1 => SoundFormat::ADPCM,
-        2 => SoundFormat::MP3,
-        3 => SoundFormat::PCM_LE,
-        4 => SoundFormat::NELLYMOSER_16KHZ_MONO,
-        5 => SoundFormat::NELLYMOSER_8KHZ_MONO,
-        6 => SoundFormat::NELLYMOSER,
-        7 => SoundFormat::PCM_ALAW,
-        8 => SoundFormat::PCM_ULAW,
-        10 => SoundFormat::AAC,
-        11 => SoundFormat::SPEEX,
-        14 => SoundFormat::MP3_8KHZ,
-        15 => SoundFormat::DEVICE_SPECIFIC,
-        _ => return Err(Err::Error(Error::new(input, ErrorKind::Alt))),
-      };
-      let srate = match srate {
-        0 => SoundRate::_5_5KHZ,
-        1 => SoundRate::_11KHZ,
-        2 => SoundRate::_22KHZ,
-        3 => SoundRate::_44KHZ,
-        _ => return Err(Err::Error(Error::new(input, ErrorKind::Alt))),
-      };
-      let ssize = match ssize {
-        0 => SoundSize::Snd8bit,
-        1 => SoundSize::Snd16bit,
-        _ => return Err(Err::Error(Error::new(input, ErrorKind::Alt))),
-      };
-      let stype = match stype {
-        0 => SoundType::SndMono,
-        1 => SoundType::SndStereo,
-        _ => return Err(Err::Error(Error::new(input, ErrorKind::Alt))),
-      };
+  fn skip_header(&mut self) -> Result<&'a [u8], FlvError> {
+    let (header, i) = first_tag(self.input, &self.options)?;
+    self.header = Some(header);
+    Ok(i)
+  }
 
-      Ok(AudioDataHeader {
-        sound_format: sformat,
-        sound_rate: srate,
-        sound_size: ssize,
-        sound_type: stype,
-      })
-    },
-  )(input)
+  fn next_tag(&mut self, i: &'a [u8]) -> Result<Tag<'a>, FlvError> {
+    let (rest, (tag, _)) = flv_tag_with_size(i, &self.options)?;
+    self.remaining = Some(rest);
+    Ok(tag)
+  }
+
+  /// Parses the next tag for which `keep` returns true, given its header and
+  /// body, skipping the other tags by `data_size`.
+  fn next_where(
+    &mut self,
+    mut keep: impl FnMut(&TagHeader, &'a [u8]) -> bool,
+  ) -> Option<Result<Tag<'a>, FlvError>> {
+    if self.failed {
+      return None;
+    }
+
+    let mut i = match self.remaining {
+      Some(i) => i,
+      None => match self.skip_header() {
+        Ok(i) => i,
+        Err(e) => {
+          self.failed = true;
+          return Some(Err(e));
+        }
+      },
+    };
+    loop {
+      if i.is_empty() {
+        self.remaining = Some(i);
+        return None;
+      }
+      match self.skip_tag_unless(i, &mut keep) {
+        Ok(Some(rest)) => i = rest,
+        Ok(None) => break,
+        Err(e) => return self.fail(i, e).map(Err),
+      }
+    }
+
+    match self.next_tag(i) {
+      Ok(tag) => Some(Ok(tag)),
+      Err(e) => self.fail(i, e).map(Err),
+    }
+  }
+
+  /// Returns the input after the tag at the start of `i` and its
+  /// PreviousTagSize, or `None` if `keep` wants the tag parsed.
+  fn skip_tag_unless(
+    &self,
+    i: &'a [u8],
+    keep: &mut impl FnMut(&TagHeader, &'a [u8]) -> bool,
+  ) -> Result<Option<&'a [u8]>, FlvError> {
+    let (body, header) = flv_tag_header(i, &self.options)?;
+    if let Some(file_header) = &self.header {
+      if self.options.strict_tag_types && !announced(file_header, header.tag_type) {
+        return Err(FlvError::UnexpectedTagType(header.tag_type));
+      }
+    }
+    let size = header.data_size as usize;
+    if body.len() < size {
+      return Err(short_body(size, body.len(), &self.options));
+    }
+    if keep(&header, &body[..size]) {
+      return Ok(None);
+    }
+    let (rest, _) = previous_tag_size(&body[size..])?;
+    Ok(Some(rest))
+  }
+
+  /// Stops the iteration after an error in the tag at the start of `i`,
+  /// which is dropped if it is a truncated last tag and
+  /// `ParserOptions::allow_truncated_tag` is set. With
+  /// `ParserOptions::error_context`, the error gets the offset of the tag.
+  fn fail(&mut self, i: &'a [u8], e: FlvError) -> Option<FlvError> {
+    self.failed = true;
+    if let FlvError::Incomplete(_) | FlvError::SizeMismatch { .. } = e {
+      if self.options.allow_truncated_tag {
+        return None;
+      }
+    }
+    #[cfg(feature = "debug")]
+    let e = match e {
+      FlvError::Incomplete(_) => e,
+      e if self.options.error_context => e.with_context(self.input, self.input.len() - i.len()),
+      e => e,
+    };
+    #[cfg(not(feature = "debug"))]
+    let _ = i;
+    Some(e)
+  }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum FrameType {
-  Key,
-  Inter,
-  DisposableInter,
-  Generated,
-  Command,
+/// Parses the file header, returning it along with the input from the first
+/// tag on, after the first PreviousTagSize.
+fn first_tag<'a>(
+  input: &'a [u8],
+  options: &ParserOptions,
+) -> Result<(Header, &'a [u8]), Err<FlvError>> {
+  let (_, header) = flv_header(input, options)?;
+  let start = body_start(&header);
+  if input.len() < start {
+    return Err(Err::Incomplete(Needed::new(start - input.len())));
+  }
+  if options.strict_initial_tag_size {
+    let (_, initial) = previous_tag_size(&input[start - 4..])?;
+    if initial != 0 {
+      return Err(Err::Error(FlvError::NonZeroInitialTagSize(initial)));
+    }
+  }
+  Ok((header, &input[start..]))
 }
 
-#[allow(non_camel_case_types)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum CodecId {
-  JPEG,
-  SORENSON_H263,
-  SCREEN,
-  VP6,
-  VP6A,
-  SCREEN2,
-  H264,
-  // Not in FLV standard
-  H263,
-  MPEG4Part2, // MPEG-4 Part 2
+impl<'a> Iterator for TagIter<'a> {
+  type Item = Result<Tag<'a>, FlvError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.next_where(|_, _| true)
+  }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum AVCPacketType {
-  SequenceHeader,
-  NALU,
-  EndOfSequence,
+/// Iterator over the tags of one type, see `tags_of_type`.
+#[derive(Clone, Debug)]
+pub struct TagsOfType<'a> {
+  tags: TagIter<'a>,
+  tag_type: TagType,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
-pub struct AVCVideoPacketHeader {
-  pub packet_type: AVCPacketType,
-  pub composition_time: i32,
+/// Same as `tags`, yielding only the tags of `tag_type`. The other tags are
+/// skipped by `data_size` after parsing their header, without parsing their
+/// body.
+pub fn tags_of_type(input: &[u8], tag_type: TagType) -> TagsOfType<'_> {
+  Parser::new().tags_of_type(input, tag_type)
 }
 
-fn packet_type(input: &[u8]) -> IResult<&[u8], AVCPacketType> {
-  map_res(be_u8, |packet_type| {
-    Ok(match packet_type {
-      0 => AVCPacketType::SequenceHeader,
-      1 => AVCPacketType::NALU,
-      2 => AVCPacketType::EndOfSequence,
-      _ => return Err(Err::Error(Error::new(input, ErrorKind::Alt))),
-    })
-  })(input)
+impl<'a> Iterator for TagsOfType<'a> {
+  type Item = Result<Tag<'a>, FlvError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let tag_type = self.tag_type;
+    self
+      .tags
+      .next_where(|header, _| header.tag_type == tag_type)
+  }
 }
 
-pub fn avc_video_packet_header(input: &[u8]) -> IResult<&[u8], AVCVideoPacketHeader> {
-  map(
-    pair(packet_type, be_i24),
-    |(packet_type, composition_time)| AVCVideoPacketHeader {
-      packet_type,
-      composition_time,
-    },
-  )(input)
+/// Iterator over the video keyframes of a complete FLV file, see
+/// `keyframe_tags`.
+#[derive(Clone, Debug)]
+pub struct KeyframeTags<'a> {
+  tags: TagIter<'a>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
-pub struct AVCVideoPacket<'a> {
-  pub packet_type: AVCPacketType,
-  pub composition_time: i32,
-  pub avc_data: &'a [u8],
+/// Same as `tags`, yielding only the video tags holding a keyframe. The
+/// other tags are skipped by `data_size` after parsing their header and the
+/// first bytes of video bodies.
+///
+/// AVC and HEVC sequence headers, which are marked as keyframes but only
+/// carry the decoder configuration, are skipped. So are enhanced RTMP video
+/// tags, which are kept as `TagData::ExVideo` without looking at their
+/// frame type.
+pub fn keyframe_tags(input: &[u8]) -> KeyframeTags<'_> {
+  Parser::new().keyframe_tags(input)
 }
 
-pub fn avc_video_packet(input: &[u8], size: usize) -> IResult<&[u8], AVCVideoPacket> {
-  if input.len() < size {
-    return Err(Err::Incomplete(Needed::new(size)));
+impl<'a> Iterator for KeyframeTags<'a> {
+  type Item = Result<Tag<'a>, FlvError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self
+      .tags
+      .next_where(|header, body| header.tag_type == TagType::Video && is_keyframe(body))
   }
+}
 
-  if size < 4 {
-    return Err(Err::Incomplete(Needed::new(4)));
+/// Whether a video tag body holds a displayable keyframe.
+fn is_keyframe(body: &[u8]) -> bool {
+  match video_data_header(body) {
+    Ok((i, header)) if header.frame_type == FrameType::Key => match header.codec_id {
+      // the AVCPacketType, 0 for sequence headers
+      CodecId::H264 | CodecId::HEVC => i.first() == Some(&1),
+      _ => true,
+    },
+    _ => false,
   }
-  pair(packet_type, be_i24)(input).map(|(_, (packet_type, composition_time))| {
-    (
-      &input[size..],
-      AVCVideoPacket {
-        packet_type,
-        composition_time,
-        avc_data: &input[4..size],
-      },
-    )
-  })
 }
 
+/// Iterator over the tags of a complete FLV file, without their bodies, see
+/// `raw_tags`.
+#[derive(Clone, Debug)]
+pub struct RawTags<'a> {
+  input: &'a [u8],
+  /// Offset of the next tag header, `None` once the walk stopped.
+  offset: Option<usize>,
+}
+
+/// A tag found by `raw_tags`, with its position in the input.
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct VideoData<'a> {
-  pub frame_type: FrameType,
-  pub codec_id: CodecId,
-  pub video_data: &'a [u8],
+pub struct RawTag<'a> {
+  /// Byte offset of the tag header in the input.
+  pub offset: usize,
+  pub header: TagHeader,
+  input: &'a [u8],
 }
 
-pub fn video_data(input: &[u8], size: usize) -> IResult<&[u8], VideoData> {
-  if input.len() < size {
-    return Err(Err::Incomplete(Needed::new(size)));
+impl<'a> RawTag<'a> {
+  /// The `data_size` bytes of the body, or `None` if the input ends before.
+  pub fn body(&self) -> Option<&'a [u8]> {
+    let start = self.offset + 11;
+    self
+      .input
+      .get(start..start + self.header.data_size as usize)
   }
 
-  if size < 1 {
-    return Err(Err::Incomplete(Needed::new(1)));
+  /// The tag header, body and PreviousTagSize, as found in the input, or
+  /// `None` if the input ends before.
+  pub fn bytes(&self) -> Option<&'a [u8]> {
+    let end = next_tag_offset(self.offset, &self.header);
+    self.input.get(self.offset..end)
   }
+}
 
-  let take_bits = pair(take(4usize), take(4usize));
-  bits::<_, _, Error<_>, _, _>(take_bits)(input).and_then(|(_, (frame_type, codec_id))| {
-    let frame_type = match frame_type {
-      1 => FrameType::Key,
-      2 => FrameType::Inter,
-      3 => FrameType::DisposableInter,
-      4 => FrameType::Generated,
-      5 => FrameType::Command,
-      _ => return Err(Err::Error(Error::new(input, ErrorKind::Alt))),
-    };
-    let codec_id = match codec_id {
-      1 => CodecId::JPEG,
-      2 => CodecId::SORENSON_H263,
-      3 => CodecId::SCREEN,
-      4 => CodecId::VP6,
-      5 => CodecId::VP6A,
-      6 => CodecId::SCREEN2,
-      7 => CodecId::H264,
-      8 => CodecId::H263,
-      9 => CodecId::MPEG4Part2,
-      _ => return Err(Err::Error(Error::new(input, ErrorKind::Alt))),
-    };
+/// Walks the tags of a complete FLV file with the header-only parsers, from
+/// `body_start`: each tag header is parsed, and the body and PreviousTagSize
+/// after it skipped by `data_size` without being read.
+///
+/// The walk stops at the first tag header that can't be parsed, so a last
+/// tag cut short is still yielded, with `RawTag::body` and `RawTag::bytes`
+/// returning `None`.
+pub fn raw_tags(input: &[u8]) -> RawTags<'_> {
+  RawTags {
+    input,
+    offset: header(input).ok().map(|(_, header)| body_start(&header)),
+  }
+}
 
-    Ok((
-      &input[size..],
-      VideoData {
-        frame_type,
-        codec_id,
-        video_data: &input[1..size],
-      },
-    ))
-  })
+impl<'a> Iterator for RawTags<'a> {
+  type Item = RawTag<'a>;
+
+  fn next(&mut self) -> Option<RawTag<'a>> {
+    let offset = self.offset.take()?;
+    let (_, header) = tag_header(self.input.get(offset..)?).ok()?;
+    self.offset = Some(next_tag_offset(offset, &header));
+    Some(RawTag {
+      offset,
+      header,
+      input: self.input,
+    })
+  }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
-pub struct VideoDataHeader {
-  pub frame_type: FrameType,
-  pub codec_id: CodecId,
+/// Progress of a whole-file parse, as reported by `parse_flv_with_progress`.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseProgress {
+  /// Byte offset in the input of the next tag.
+  pub offset: usize,
+  /// Number of tags parsed so far.
+  pub tags: usize,
 }
 
-pub fn video_data_header(input: &[u8]) -> IResult<&[u8], VideoDataHeader> {
-  if input.is_empty() {
-    return Err(Err::Incomplete(Needed::new(1)));
-  }
+/// Parses a complete FLV file: the header, then every tag along with the
+/// PreviousTagSize following it.
+#[cfg(feature = "alloc")]
+pub fn parse_flv(input: &[u8]) -> IResult<&[u8], (Header, Vec<Tag<'_>>), FlvError> {
+  parse_flv_with_progress(input, 0, |_| {})
+}
 
-  let take_bits = pair(take(4usize), take(4usize));
-  map_res(
-    bits::<_, _, Error<_>, _, _>(take_bits),
-    |(frame_type, codec_id)| {
-      let frame_type = match frame_type {
-        1 => FrameType::Key,
-        2 => FrameType::Inter,
-        3 => FrameType::DisposableInter,
-        4 => FrameType::Generated,
-        5 => FrameType::Command,
-        _ => return Err(Err::Error(Error::new(input, ErrorKind::Alt))),
-      };
-      let codec_id = match codec_id {
-        1 => CodecId::JPEG,
-        2 => CodecId::SORENSON_H263,
-        3 => CodecId::SCREEN,
-        4 => CodecId::VP6,
-        5 => CodecId::VP6A,
-        6 => CodecId::SCREEN2,
-        7 => CodecId::H264,
-        8 => CodecId::H263,
-        9 => CodecId::MPEG4Part2,
-        _ => return Err(Err::Error(Error::new(input, ErrorKind::Alt))),
-      };
+/// Same as `parse_flv`, calling `progress` every `every` tags, so that long
+/// parses can drive a progress bar. No progress is reported if `every` is 0.
+#[cfg(feature = "alloc")]
+pub fn parse_flv_with_progress(
+  input: &[u8],
+  every: usize,
+  progress: impl FnMut(ParseProgress),
+) -> IResult<&[u8], (Header, Vec<Tag<'_>>), FlvError> {
+  parse_flv_while(
+    input,
+    |_, _| true,
+    every,
+    progress,
+    &ParserOptions::default(),
+  )
+}
 
-      Ok(VideoDataHeader {
-        frame_type,
-        codec_id,
-      })
-    },
-  )(input)
+/// Same as `parse_flv`, stopping before the first tag with a timestamp
+/// greater than `max_ms`. The remaining input starts at that tag.
+#[cfg(feature = "alloc")]
+pub fn parse_until_time(
+  input: &[u8],
+  max_ms: u32,
+) -> IResult<&[u8], (Header, Vec<Tag<'_>>), FlvError> {
+  parse_flv_while(
+    input,
+    |_, tag| tag.timestamp <= max_ms,
+    0,
+    |_| {},
+    &ParserOptions::default(),
+  )
 }
 
-#[derive(Debug, PartialEq)]
-pub struct ScriptData<'a> {
-  pub name: &'a str,
-  pub arguments: ScriptDataValue<'a>,
+/// Same as `parse_flv`, stopping before the first tag that, along with its
+/// PreviousTagSize, does not end within the first `max` bytes of the input.
+/// The remaining input starts at that tag.
+#[cfg(feature = "alloc")]
+pub fn parse_until_byte(
+  input: &[u8],
+  max: usize,
+) -> IResult<&[u8], (Header, Vec<Tag<'_>>), FlvError> {
+  parse_flv_while(
+    input,
+    |offset, tag| next_tag_offset(offset, tag) <= max,
+    0,
+    |_| {},
+    &ParserOptions::default(),
+  )
 }
 
-#[derive(Debug, PartialEq)]
-pub enum ScriptDataValue<'a> {
-  Number(f64),
-  Boolean(bool),
-  String(&'a str),
-  Object(Vec<ScriptDataObject<'a>>),
-  MovieClip(&'a str),
-  Null,
-  Undefined,
-  Reference(u16),
-  ECMAArray(Vec<ScriptDataObject<'a>>),
-  StrictArray(Vec<ScriptDataValue<'a>>),
-  Date(ScriptDataDate),
-  LongString(&'a str),
+/// Whether the file header flags allow tags of this type.
+pub(crate) fn announced(header: &Header, tag_type: TagType) -> bool {
+  match tag_type {
+    TagType::Audio => header.audio,
+    TagType::Video => header.video,
+    TagType::Script => true,
+  }
 }
 
-#[derive(Debug, PartialEq)]
-pub struct ScriptDataObject<'a> {
-  pub name: &'a str,
-  pub data: ScriptDataValue<'a>,
+/// Parses tags as long as `keep` returns true for the offset and header of
+/// the next one.
+#[cfg(feature = "alloc")]
+fn parse_flv_while<'a>(
+  input: &'a [u8],
+  mut keep: impl FnMut(usize, &TagHeader) -> bool,
+  every: usize,
+  mut progress: impl FnMut(ParseProgress),
+  options: &ParserOptions,
+) -> IResult<&'a [u8], (Header, Vec<Tag<'a>>), FlvError> {
+  let (header, mut i) = first_tag(input, options)?;
+  let mut tags = Vec::new();
+
+  while !i.is_empty() {
+    let (rest, tag) = match flv_tag_header(i, options) {
+      Ok((_, tag_header)) if !keep(input.len() - i.len(), &tag_header) => break,
+      Ok((_, tag_header))
+        if options.strict_tag_types && !announced(&header, tag_header.tag_type) =>
+      {
+        return Err(Err::Error(FlvError::UnexpectedTagType(tag_header.tag_type)));
+      }
+      Ok(_) => match flv_tag_with_size(i, options) {
+        Ok((rest, (tag, _))) => (rest, tag),
+        Err(Err::Incomplete(_) | Err::Error(FlvError::SizeMismatch { .. }))
+          if options.allow_truncated_tag =>
+        {
+          break
+        }
+        Err(e) => return Err(e),
+      },
+      Err(Err::Incomplete(_)) if options.allow_truncated_tag => break,
+      Err(e) => return Err(e),
+    };
+    tags.push(tag);
+    i = rest;
+
+    if every != 0 && tags.len() % every == 0 {
+      progress(ParseProgress {
+        offset: input.len() - i.len(),
+        tags: tags.len(),
+      });
+    }
+  }
+
+  Ok((i, (header, tags)))
 }
 
-#[derive(Clone, Debug, PartialEq)]
-pub struct ScriptDataDate {
-  pub date_time: f64,
-  pub local_date_time_offset: i16, // SI16
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SoundFormat {
+  /// PCM in the endianness of the platform that wrote the file. Files come
+  /// from little endian platforms in practice, so 16-bit samples should be
+  /// read as little endian, like `PCM_LE`.
+  PCM_NE,
+  ADPCM,
+  MP3,
+  PCM_LE,
+  NELLYMOSER_16KHZ_MONO,
+  NELLYMOSER_8KHZ_MONO,
+  NELLYMOSER,
+  PCM_ALAW,
+  PCM_ULAW,
+  AAC,
+  SPEEX,
+  MP3_8KHZ,
+  DEVICE_SPECIFIC,
+  /// Only signaled by the `Opus` FourCC in enhanced RTMP.
+  OPUS,
+  /// Only signaled by the `fLaC` FourCC in enhanced RTMP.
+  FLAC,
+  /// Only signaled by the `ac-3` FourCC in enhanced RTMP.
+  AC3,
+  /// Only signaled by the `ec-3` FourCC in enhanced RTMP.
+  EAC3,
 }
 
-#[allow(non_upper_case_globals)]
-static script_data_name_tag: &[u8] = &[2];
+impl SoundFormat {
+  /// Maps the FourCC of an enhanced RTMP audio header.
+  pub fn from_fourcc(fourcc: &[u8; 4]) -> Option<SoundFormat> {
+    match fourcc {
+      b".mp3" => Some(SoundFormat::MP3),
+      b"mp4a" => Some(SoundFormat::AAC),
+      b"Opus" => Some(SoundFormat::OPUS),
+      b"fLaC" => Some(SoundFormat::FLAC),
+      b"ac-3" => Some(SoundFormat::AC3),
+      b"ec-3" => Some(SoundFormat::EAC3),
+      _ => None,
+    }
+  }
 
-pub fn script_data(input: &[u8]) -> IResult<&[u8], ScriptData> {
-  // Must start with a string, i.e. 2
-  map(
-    tuple((
-      tag(script_data_name_tag),
-      script_data_string,
-      script_data_value,
-    )),
-    |(_, name, arguments)| ScriptData { name, arguments },
-  )(input)
+  /// The FourCC signaling this format in enhanced RTMP, if there is one.
+  pub fn fourcc(self) -> Option<[u8; 4]> {
+    match self {
+      SoundFormat::MP3 => Some(*b".mp3"),
+      SoundFormat::AAC => Some(*b"mp4a"),
+      SoundFormat::OPUS => Some(*b"Opus"),
+      SoundFormat::FLAC => Some(*b"fLaC"),
+      SoundFormat::AC3 => Some(*b"ac-3"),
+      SoundFormat::EAC3 => Some(*b"ec-3"),
+      _ => None,
+    }
+  }
 }
 
-pub fn script_data_value(input: &[u8]) -> IResult<&[u8], ScriptDataValue> {
-  be_u8(input).and_then(|v| match v {
-    (i, 0) => map(be_f64, ScriptDataValue::Number)(i),
-    (i, 1) => map(be_u8, |n| ScriptDataValue::Boolean(n != 0))(i),
-    (i, 2) => map(script_data_string, ScriptDataValue::String)(i),
-    (i, 3) => map(script_data_objects, ScriptDataValue::Object)(i),
-    (i, 4) => map(script_data_string, ScriptDataValue::MovieClip)(i),
-    (i, 5) => Ok((i, ScriptDataValue::Null)), // to remove
-    (i, 6) => Ok((i, ScriptDataValue::Undefined)), // to remove
-    (i, 7) => map(be_u16, ScriptDataValue::Reference)(i),
-    (i, 8) => map(script_data_ecma_array, ScriptDataValue::ECMAArray)(i),
-    (i, 10) => map(script_data_strict_array, ScriptDataValue::StrictArray)(i),
-    (i, 11) => map(script_data_date, ScriptDataValue::Date)(i),
-    (i, 12) => map(script_data_long_string, ScriptDataValue::LongString)(i),
-    _ => Err(Err::Error(Error::new(input, ErrorKind::Alt))),
+impl TryFrom<u8> for SoundFormat {
+  type Error = FlvError;
+
+  fn try_from(sound_format: u8) -> Result<Self, Self::Error> {
+    Ok(match sound_format {
+      0 => SoundFormat::PCM_NE,
+      1 => SoundFormat::ADPCM,
+      2 => SoundFormat::MP3,
+      3 => SoundFormat::PCM_LE,
+      4 => SoundFormat::NELLYMOSER_16KHZ_MONO,
+      5 => SoundFormat::NELLYMOSER_8KHZ_MONO,
+      6 => SoundFormat::NELLYMOSER,
+      7 => SoundFormat::PCM_ALAW,
+      8 => SoundFormat::PCM_ULAW,
+      10 => SoundFormat::AAC,
+      11 => SoundFormat::SPEEX,
+      14 => SoundFormat::MP3_8KHZ,
+      15 => SoundFormat::DEVICE_SPECIFIC,
+      _ => return Err(FlvError::UnknownSoundFormat(sound_format)),
+    })
+  }
+}
+
+/// The formats without a sound format nibble give 9, the `ExHeader` that
+/// announces a FourCC.
+impl From<SoundFormat> for u8 {
+  fn from(sound_format: SoundFormat) -> Self {
+    match sound_format {
+      SoundFormat::PCM_NE => 0,
+      SoundFormat::ADPCM => 1,
+      SoundFormat::MP3 => 2,
+      SoundFormat::PCM_LE => 3,
+      SoundFormat::NELLYMOSER_16KHZ_MONO => 4,
+      SoundFormat::NELLYMOSER_8KHZ_MONO => 5,
+      SoundFormat::NELLYMOSER => 6,
+      SoundFormat::PCM_ALAW => 7,
+      SoundFormat::PCM_ULAW => 8,
+      SoundFormat::AAC => 10,
+      SoundFormat::SPEEX => 11,
+      SoundFormat::MP3_8KHZ => 14,
+      SoundFormat::DEVICE_SPECIFIC => 15,
+      SoundFormat::OPUS | SoundFormat::FLAC | SoundFormat::AC3 | SoundFormat::EAC3 => 9,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SoundRate {
+  _5_5KHZ,
+  _11KHZ,
+  _22KHZ,
+  _44KHZ,
+}
+
+impl SoundRate {
+  /// The sample rate in Hz. 5.5 kHz is 44100 / 8, 5512.5 Hz, rounded down.
+  pub fn as_hz(&self) -> u32 {
+    match self {
+      SoundRate::_5_5KHZ => 5512,
+      SoundRate::_11KHZ => 11025,
+      SoundRate::_22KHZ => 22050,
+      SoundRate::_44KHZ => 44100,
+    }
+  }
+}
+
+impl core::fmt::Display for SoundRate {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    write!(f, "{} Hz", self.as_hz())
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SoundSize {
+  Snd8bit,
+  Snd16bit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SoundType {
+  SndMono,
+  SndStereo,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AACPacketType {
+  SequenceHeader,
+  Raw,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AACAudioPacketHeader {
+  pub packet_type: AACPacketType,
+}
+
+pub fn aac_audio_packet_header(input: &[u8]) -> IResult<&[u8], AACAudioPacketHeader, FlvError> {
+  map_res(be_u8, |packet_type| {
+    Ok(AACAudioPacketHeader {
+      packet_type: match packet_type {
+        0 => AACPacketType::SequenceHeader,
+        1 => AACPacketType::Raw,
+        packet_type => return Err(FlvError::UnknownPacketType(packet_type)),
+      },
+    })
+  })(input)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct AACAudioPacket<'a> {
+  pub packet_type: AACPacketType,
+  pub aac_data: &'a [u8],
+}
+
+pub fn aac_audio_packet(input: &[u8], size: usize) -> IResult<&[u8], AACAudioPacket<'_>, FlvError> {
+  if input.len() < size {
+    return Err(Err::Incomplete(Needed::new(size - input.len())));
+  }
+
+  if size < 1 {
+    return Err(Err::Incomplete(Needed::new(1)));
+  }
+
+  be_u8(input).and_then(|(_, packet_type)| {
+    Ok((
+      &input[size..],
+      AACAudioPacket {
+        packet_type: match packet_type {
+          0 => AACPacketType::SequenceHeader,
+          1 => AACPacketType::Raw,
+          packet_type => return Err(Err::Error(FlvError::UnknownPacketType(packet_type))),
+        },
+        aac_data: &input[1..size],
+      },
+    ))
   })
 }
 
-pub fn script_data_objects(input: &[u8]) -> IResult<&[u8], Vec<ScriptDataObject>> {
-  terminated(many0(script_data_object), script_data_object_end)(input)
+/// The MPEG-4 AudioSpecificConfig (ISO/IEC 14496-3) carried in the
+/// `aac_data` of an `AACPacketType::SequenceHeader` packet. Only the leading
+/// fields common to every object type are read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AudioSpecificConfig {
+  /// 2 for AAC LC, 5 for SBR, 29 for PS. Escaped values above 31 are read.
+  pub object_type: u8,
+  pub sampling_frequency_index: u8,
+  /// Set when `sampling_frequency_index` is 15, the escape value.
+  pub sampling_frequency: Option<u32>,
+  /// 1 to 6 for as many channels, 7 for 8 channels, 0 when the layout is in
+  /// a program config element.
+  pub channel_configuration: u8,
 }
 
-pub fn script_data_object(input: &[u8]) -> IResult<&[u8], ScriptDataObject> {
-  map(
-    pair(script_data_string, script_data_value),
-    |(name, data)| ScriptDataObject { name, data },
-  )(input)
+const AAC_SAMPLING_FREQUENCIES: [u32; 13] = [
+  96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+impl AudioSpecificConfig {
+  /// The real sample rate of the stream, where the FLV sound rate of AAC
+  /// tags is always 44 kHz. For SBR streams signaled implicitly, this is
+  /// half the output rate.
+  pub fn sample_rate(&self) -> Option<u32> {
+    self.sampling_frequency.or_else(|| {
+      AAC_SAMPLING_FREQUENCIES
+        .get(self.sampling_frequency_index as usize)
+        .copied()
+    })
+  }
+
+  /// Number of channels, if given by `channel_configuration`.
+  pub fn channels(&self) -> Option<u8> {
+    match self.channel_configuration {
+      channels @ 1..=6 => Some(channels),
+      7 => Some(8),
+      _ => None,
+    }
+  }
+}
+
+pub fn audio_specific_config(input: &[u8]) -> IResult<&[u8], AudioSpecificConfig, FlvError> {
+  bits::<_, _, FlvError, _, _>(|i| {
+    let (i, object_type) = take::<_, u8, _, _>(5usize)(i)?;
+    let (i, object_type) = if object_type == 31 {
+      let (i, escaped) = take::<_, u8, _, _>(6usize)(i)?;
+      (i, 32 + escaped)
+    } else {
+      (i, object_type)
+    };
+    let (i, sampling_frequency_index) = take::<_, u8, _, _>(4usize)(i)?;
+    let (i, sampling_frequency) = if sampling_frequency_index == 15 {
+      let (i, frequency) = take::<_, u32, _, _>(24usize)(i)?;
+      (i, Some(frequency))
+    } else {
+      (i, None)
+    };
+    let (i, channel_configuration) = take::<_, u8, _, _>(4usize)(i)?;
+
+    Ok((
+      i,
+      AudioSpecificConfig {
+        object_type,
+        sampling_frequency_index,
+        sampling_frequency,
+        channel_configuration,
+      },
+    ))
+  })(input)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AudioData<'a> {
+  pub sound_format: SoundFormat,
+  pub sound_rate: SoundRate,
+  pub sound_size: SoundSize,
+  pub sound_type: SoundType,
+  /// Everything after the flags byte. For AAC, this starts with the
+  /// `AACPacketType` byte, see `aac_packet`.
+  pub sound_data: &'a [u8],
+}
+
+impl<'a> AudioData<'a> {
+  /// Splits the `AACPacketType` byte off the payload of AAC audio. Returns
+  /// `None` for other formats, or if the packet type is invalid.
+  pub fn aac_packet(&self) -> Option<AACAudioPacket<'a>> {
+    if self.sound_format != SoundFormat::AAC {
+      return None;
+    }
+    let (_, packet) = aac_audio_packet(self.sound_data, self.sound_data.len()).ok()?;
+    Some(packet)
+  }
+
+  /// PCM audio data, with an explicit choice of sound format so the output
+  /// does not depend on the platform: `PCM_LE` if `le` is set, the platform
+  /// endianness `PCM_NE` otherwise.
+  pub fn new_pcm(
+    le: bool,
+    sound_rate: SoundRate,
+    sound_size: SoundSize,
+    sound_type: SoundType,
+    sound_data: &'a [u8],
+  ) -> AudioData<'a> {
+    AudioData {
+      sound_format: if le {
+        SoundFormat::PCM_LE
+      } else {
+        SoundFormat::PCM_NE
+      },
+      sound_rate,
+      sound_size,
+      sound_type,
+      sound_data,
+    }
+  }
+
+  /// Rebuilds the first byte of the audio tag body, as it was on the wire.
+  pub fn raw_flags(&self) -> u8 {
+    let sound_format = u8::from(self.sound_format);
+    let sound_rate = match self.sound_rate {
+      SoundRate::_5_5KHZ => 0,
+      SoundRate::_11KHZ => 1,
+      SoundRate::_22KHZ => 2,
+      SoundRate::_44KHZ => 3,
+    };
+    let sound_size = match self.sound_size {
+      SoundSize::Snd8bit => 0,
+      SoundSize::Snd16bit => 1,
+    };
+    let sound_type = match self.sound_type {
+      SoundType::SndMono => 0,
+      SoundType::SndStereo => 1,
+    };
+
+    (sound_format << 4) | (sound_rate << 2) | (sound_size << 1) | sound_type
+  }
+
+  /// Whether the tag is only made of the flags byte, with no sound data after
+  /// it.
+  pub fn is_empty_payload(&self) -> bool {
+    self.sound_data.is_empty()
+  }
+
+  /// Checks the flags against the conventions of the sound format, to catch
+  /// mislabeled audio. Only AAC is checked so far: its flags must say 44 kHz
+  /// stereo, whatever the actual configuration, and its payload must start
+  /// with a packet type.
+  pub fn validate(&self) -> Result<(), AudioAnomaly> {
+    if self.sound_format != SoundFormat::AAC {
+      return Ok(());
+    }
+    if self.sound_rate != SoundRate::_44KHZ {
+      return Err(AudioAnomaly::AacSoundRate(self.sound_rate));
+    }
+    if self.sound_type != SoundType::SndStereo {
+      return Err(AudioAnomaly::AacSoundType(self.sound_type));
+    }
+
+    match self.sound_data {
+      [] => Err(AudioAnomaly::MissingAacPacketType),
+      [0, config @ ..] => match config {
+        [first, second, rest @ ..] => {
+          // the AudioSpecificConfig of AAC main, LC, SSR or LTP with a
+          // sampling frequency index fits in 2 bytes, other object types
+          // carry more fields
+          let object_type = first >> 3;
+          let frequency_index = ((first & 0x07) << 1) | (second >> 7);
+          let two_bytes = (1..=4).contains(&object_type) && frequency_index != 15;
+          match rest {
+            _ if !two_bytes => Ok(()),
+            // explicit SBR signaling: the 0x2b7 sync extension then 3 more bytes
+            [0x56, sync, _] if sync & 0xe0 == 0xe0 => Ok(()),
+            _ if rest.iter().all(|b| *b == 0) => Ok(()),
+            _ => Err(AudioAnomaly::AacSequenceHeaderTrailingData),
+          }
+        }
+        _ => Err(AudioAnomaly::AacSequenceHeaderTooShort),
+      },
+      _ => Ok(()),
+    }
+  }
+}
+
+/// An inconsistency found by `AudioData::validate`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioAnomaly {
+  /// AAC tags must have a 44 kHz sound rate.
+  AacSoundRate(SoundRate),
+  /// AAC tags must be marked stereo.
+  AacSoundType(SoundType),
+  /// The AAC payload is empty, without even an `AACPacketType`.
+  MissingAacPacketType,
+  /// The sequence header is shorter than the 2 bytes of an
+  /// AudioSpecificConfig.
+  AacSequenceHeaderTooShort,
+  /// The sequence header has non zero bytes after a 2-byte
+  /// AudioSpecificConfig that are not an SBR extension.
+  AacSequenceHeaderTrailingData,
+}
+
+/// Whether the first byte of an audio tag body signals an enhanced RTMP
+/// header: the sound format nibble is then 9 (`ExHeader`) and the rest of the
+/// byte is an audio packet type instead of rate/size/type flags.
+pub fn is_ex_audio_header(byte: u8) -> bool {
+  byte >> 4 == 9
+}
+
+/// Parses an audio tag body of `size` bytes.
+///
+/// The codec specific header is left in `sound_data`, like the
+/// `AACPacketType` of AAC, see `AudioData::aac_packet`.
+///
+/// A 1-byte body is accepted: it yields an empty `sound_data` slice, see
+/// `AudioData::is_empty_payload`.
+pub fn audio_data(input: &[u8], size: usize) -> IResult<&[u8], AudioData<'_>, FlvError> {
+  if input.len() < size {
+    return Err(Err::Incomplete(Needed::new(size - input.len())));
+  }
+
+  if size < 1 {
+    return Err(Err::Incomplete(Needed::new(1)));
+  }
+
+  if is_ex_audio_header(input[0]) {
+    return Err(Err::Error(FlvError::Nom(ErrorKind::Alt)));
+  }
+
+  let take_bits = tuple((
+    take::<_, u8, _, _>(4usize),
+    take(2usize),
+    take(1usize),
+    take(1usize),
+  ));
+  bits::<_, _, FlvError, _, _>(take_bits)(input).and_then(|(_, (sformat, srate, ssize, stype))| {
+    let sformat = SoundFormat::try_from(sformat).map_err(Err::Error)?;
+    let srate = match srate {
+      0 => SoundRate::_5_5KHZ,
+      1 => SoundRate::_11KHZ,
+      2 => SoundRate::_22KHZ,
+      3 => SoundRate::_44KHZ,
+      _ => return Err(Err::Error(FlvError::Nom(ErrorKind::Alt))),
+    };
+    let ssize = match ssize {
+      0 => SoundSize::Snd8bit,
+      1 => SoundSize::Snd16bit,
+      _ => return Err(Err::Error(FlvError::Nom(ErrorKind::Alt))),
+    };
+    let stype = match stype {
+      0 => SoundType::SndMono,
+      1 => SoundType::SndStereo,
+      _ => return Err(Err::Error(FlvError::Nom(ErrorKind::Alt))),
+    };
+
+    Ok((
+      &input[size..],
+      AudioData {
+        sound_format: sformat,
+        sound_rate: srate,
+        sound_size: ssize,
+        sound_type: stype,
+        sound_data: &input[1..size],
+      },
+    ))
+  })
 }
 
-#[allow(non_upper_case_globals)]
-static script_data_object_end_terminator: &[u8] = &[0, 0, 9];
+/// An audio tag body parsed according to its sound format, by `audio_tag`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum AudioTag<'a> {
+  Aac(AACAudioPacket<'a>),
+  /// MPEG audio frames, of `SoundFormat::MP3` or `SoundFormat::MP3_8KHZ`. A
+  /// frame can be split across tags.
+  Mp3(&'a [u8]),
+  /// The other formats, with the data after the first byte.
+  Raw {
+    format: SoundFormat,
+    data: &'a [u8],
+  },
+}
+
+/// Parses an audio tag body of `size` bytes like `audio_data`, then splits
+/// the packet type off AAC payloads with `aac_audio_packet`. The rate, size
+/// and type flags are left out, read them with `audio_data_header`.
+///
+/// Since the whole body is there, an AAC payload without its packet type is
+/// an error instead of `Err::Incomplete`.
+pub fn audio_tag(input: &[u8], size: usize) -> IResult<&[u8], AudioTag<'_>, FlvError> {
+  let (i, audio) = audio_data(input, size)?;
+  let data = audio.sound_data;
+  let tag = match audio.sound_format {
+    SoundFormat::AAC if data.is_empty() => {
+      return Err(Err::Error(FlvError::BadLength {
+        field: "AACPacketType",
+        declared: 1,
+        available: 0,
+      }))
+    }
+    SoundFormat::AAC => AudioTag::Aac(aac_audio_packet(data, data.len())?.1),
+    SoundFormat::MP3 | SoundFormat::MP3_8KHZ => AudioTag::Mp3(data),
+    format => AudioTag::Raw { format, data },
+  };
+  Ok((i, tag))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AudioDataHeader {
+  pub sound_format: SoundFormat,
+  pub sound_rate: SoundRate,
+  pub sound_size: SoundSize,
+  pub sound_type: SoundType,
+}
+
+pub fn audio_data_header(input: &[u8]) -> IResult<&[u8], AudioDataHeader, FlvError> {
+  if input.is_empty() {
+    return Err(Err::Incomplete(Needed::new(1)));
+  }
+
+  if is_ex_audio_header(input[0]) {
+    return Err(Err::Error(FlvError::Nom(ErrorKind::Alt)));
+  }
+
+  let take_bits = tuple((
+    take::<_, u8, _, _>(4usize),
+    take(2usize),
+    take(1usize),
+    take(1usize),
+  ));
+  map_res(
+    bits::<_, _, FlvError, _, _>(take_bits),
+    |(sformat, srate, ssize, stype)| {
+      let sformat = SoundFormat::try_from(sformat)?;
+      let srate = match srate {
+        0 => SoundRate::_5_5KHZ,
+        1 => SoundRate::_11KHZ,
+        2 => SoundRate::_22KHZ,
+        3 => SoundRate::_44KHZ,
+        _ => return Err(FlvError::Nom(ErrorKind::Alt)),
+      };
+      let ssize = match ssize {
+        0 => SoundSize::Snd8bit,
+        1 => SoundSize::Snd16bit,
+        _ => return Err(FlvError::Nom(ErrorKind::Alt)),
+      };
+      let stype = match stype {
+        0 => SoundType::SndMono,
+        1 => SoundType::SndStereo,
+        _ => return Err(FlvError::Nom(ErrorKind::Alt)),
+      };
+
+      Ok(AudioDataHeader {
+        sound_format: sformat,
+        sound_rate: srate,
+        sound_size: ssize,
+        sound_type: stype,
+      })
+    },
+  )(input)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SpeexAudioPacket<'a> {
+  /// One or more wideband Speex frames, not aligned on bytes.
+  pub speex_data: &'a [u8],
+}
+
+impl<'a> SpeexAudioPacket<'a> {
+  /// The frames of the packet, up to the terminator or the padding bits.
+  pub fn frames(&self) -> SpeexFrames<'a> {
+    SpeexFrames {
+      data: self.speex_data,
+      position: 0,
+    }
+  }
+}
+
+/// A Speex frame: a narrowband layer, optionally followed by the wideband
+/// layer that takes it to 16 kHz.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpeexFrame {
+  /// Position of the frame in `speex_data`, in bits.
+  pub bit_offset: usize,
+  pub bit_len: usize,
+  pub narrowband_mode: u8,
+  pub wideband_mode: Option<u8>,
+}
+
+/// Sizes of the narrowband and wideband layers by mode, in bits, header
+/// included.
+const SPEEX_NB_FRAME_SIZE: [usize; 9] = [5, 43, 119, 160, 220, 300, 364, 492, 79];
+const SPEEX_WB_FRAME_SIZE: [usize; 5] = [4, 36, 112, 192, 352];
+
+/// Iterator over the frames of a `SpeexAudioPacket`, which stops at the
+/// first frame that it can't delimit, like in-band signaling.
+#[derive(Clone, Debug)]
+pub struct SpeexFrames<'a> {
+  data: &'a [u8],
+  position: usize,
+}
+
+impl<'a> SpeexFrames<'a> {
+  fn read(&mut self, bits: usize) -> Option<u8> {
+    let mut value = 0;
+    for _ in 0..bits {
+      let byte = self.data.get(self.position / 8)?;
+      value = (value << 1) | ((byte >> (7 - self.position % 8)) & 1);
+      self.position += 1;
+    }
+    Some(value)
+  }
+
+  fn advance(&mut self, bits: usize) -> Option<()> {
+    if self.position + bits > self.data.len() * 8 {
+      return None;
+    }
+    self.position += bits;
+    Some(())
+  }
+}
+
+impl<'a> Iterator for SpeexFrames<'a> {
+  type Item = SpeexFrame;
+
+  fn next(&mut self) -> Option<SpeexFrame> {
+    let bit_offset = self.position;
+    // a frame starts with a narrowband layer, the wideband bit unset
+    if self.data.len() * 8 - bit_offset < 5 || self.read(1)? != 0 {
+      return None;
+    }
+    // 15 is the terminator, which also pads the last byte
+    let narrowband_mode = self.read(4)?;
+    let size = *SPEEX_NB_FRAME_SIZE.get(narrowband_mode as usize)?;
+    self.advance(size - 5)?;
+
+    let mut wideband_mode = None;
+    let mut peek = self.clone();
+    if peek.read(1) == Some(1) {
+      let mode = peek.read(3)?;
+      let size = *SPEEX_WB_FRAME_SIZE.get(mode as usize)?;
+      peek.advance(size - 4)?;
+      *self = peek;
+      wideband_mode = Some(mode);
+    }
+
+    Some(SpeexFrame {
+      bit_offset,
+      bit_len: self.position - bit_offset,
+      narrowband_mode,
+      wideband_mode,
+    })
+  }
+}
+
+/// Parses a Speex audio tag body of `size` bytes, first byte included.
+///
+/// FLV only carries Speex in wideband mode, mono at 16 kHz, which is flagged
+/// with a sound rate of 0, 16-bit samples and mono: other flags are rejected.
+pub fn speex_audio_packet(
+  input: &[u8],
+  size: usize,
+) -> IResult<&[u8], SpeexAudioPacket<'_>, FlvError> {
+  let (i, audio) = audio_data(input, size)?;
+  if audio.sound_format != SoundFormat::SPEEX {
+    return Err(Err::Error(FlvError::UnexpectedSoundFormat(
+      audio.sound_format,
+    )));
+  }
+  if (audio.sound_rate, audio.sound_size, audio.sound_type)
+    != (SoundRate::_5_5KHZ, SoundSize::Snd16bit, SoundType::SndMono)
+  {
+    return Err(Err::Error(FlvError::UnexpectedSoundFlags(
+      SoundFormat::SPEEX,
+    )));
+  }
+
+  Ok((
+    i,
+    SpeexAudioPacket {
+      speex_data: audio.sound_data,
+    },
+  ))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct NellymoserAudioPacket<'a> {
+  pub sound_format: SoundFormat,
+  pub sound_rate: SoundRate,
+  /// A whole number of 64-byte blocks.
+  pub nellymoser_data: &'a [u8],
+}
+
+impl<'a> NellymoserAudioPacket<'a> {
+  /// The 64-byte blocks of the packet, each coding 256 samples.
+  pub fn blocks(&self) -> core::slice::ChunksExact<'a, u8> {
+    self.nellymoser_data.chunks_exact(64)
+  }
+
+  /// Number of samples coded by the packet.
+  pub fn samples(&self) -> usize {
+    self.nellymoser_data.len() / 64 * 256
+  }
+
+  /// In Hz. The dedicated sound formats override the sound rate.
+  pub fn sample_rate(&self) -> u32 {
+    match (self.sound_format, self.sound_rate) {
+      (SoundFormat::NELLYMOSER_16KHZ_MONO, _) => 16000,
+      (SoundFormat::NELLYMOSER_8KHZ_MONO, _) => 8000,
+      (_, sound_rate) => sound_rate.as_hz(),
+    }
+  }
+}
+
+/// Parses a Nellymoser audio tag body of `size` bytes, first byte included.
+/// The payload must be a non empty sequence of 64-byte blocks.
+pub fn nellymoser_audio_packet(
+  input: &[u8],
+  size: usize,
+) -> IResult<&[u8], NellymoserAudioPacket<'_>, FlvError> {
+  let (i, audio) = audio_data(input, size)?;
+  match audio.sound_format {
+    SoundFormat::NELLYMOSER
+    | SoundFormat::NELLYMOSER_8KHZ_MONO
+    | SoundFormat::NELLYMOSER_16KHZ_MONO => {}
+    sound_format => return Err(Err::Error(FlvError::UnexpectedSoundFormat(sound_format))),
+  }
+  if audio.sound_data.is_empty() || audio.sound_data.len() % 64 != 0 {
+    return Err(Err::Error(FlvError::PartialBlock {
+      len: audio.sound_data.len(),
+      block_size: 64,
+    }));
+  }
+
+  Ok((
+    i,
+    NellymoserAudioPacket {
+      sound_format: audio.sound_format,
+      sound_rate: audio.sound_rate,
+      nellymoser_data: audio.sound_data,
+    },
+  ))
+}
+
+/// Enhanced RTMP audio packet type, the low nibble of the first byte after
+/// an enhanced header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioPacketType {
+  SequenceStart,
+  CodedFrames,
+  SequenceEnd,
+  MultichannelConfig,
+  Multitrack,
+  ModEx,
+}
+
+/// The header of an enhanced RTMP audio tag body.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExAudioTagHeader {
+  pub packet_type: AudioPacketType,
+  /// Codec FourCC, like `Opus`, `fLaC`, `.mp3`, `ac-3` or `mp4a`.
+  pub fourcc: [u8; 4],
+}
+
+impl ExAudioTagHeader {
+  /// The sound format of `fourcc`, if it is a known one.
+  pub fn sound_format(&self) -> Option<SoundFormat> {
+    SoundFormat::from_fourcc(&self.fourcc)
+  }
+}
+
+/// The packet type of a nibble followed by a FourCC, which `Multitrack` and
+/// `ModEx` are not.
+fn ex_audio_packet_type(nibble: u8) -> Result<AudioPacketType, Err<FlvError>> {
+  Ok(match nibble {
+    0 => AudioPacketType::SequenceStart,
+    1 => AudioPacketType::CodedFrames,
+    2 => AudioPacketType::SequenceEnd,
+    4 => AudioPacketType::MultichannelConfig,
+    5 | 7 => return Err(Err::Error(FlvError::UnexpectedPacketType(nibble))),
+    _ => return Err(Err::Error(FlvError::UnknownPacketType(nibble))),
+  })
+}
+
+/// Parses the first byte and FourCC of an enhanced RTMP audio tag body.
+///
+/// This is the counterpart of `audio_data_header` for tags with the
+/// `ExHeader` sound format, which `audio_data_header` rejects, so AAC and the
+/// other legacy formats parse as before. `Multitrack` and `ModEx` packets,
+/// whose FourCC is not right after the first byte, are rejected: parse
+/// multitrack packets with `ex_audio_multitrack`.
+pub fn ex_audio_tag_header(input: &[u8]) -> IResult<&[u8], ExAudioTagHeader, FlvError> {
+  let (i, first) = be_u8(input)?;
+  if !is_ex_audio_header(first) {
+    return Err(Err::Error(FlvError::Nom(ErrorKind::Alt)));
+  }
+  let packet_type = ex_audio_packet_type(first & 0x0f)?;
+  let (i, fourcc) = take_bytes(4usize)(i)?;
+
+  Ok((
+    i,
+    ExAudioTagHeader {
+      packet_type,
+      fourcc: [fourcc[0], fourcc[1], fourcc[2], fourcc[3]],
+    },
+  ))
+}
+
+/// The enhanced RTMP audio and video tag headers, parsed by `ex_body`.
+trait ExTagHeader: Sized {
+  type PacketType: Copy + PartialEq;
+
+  fn parse(input: &[u8]) -> IResult<&[u8], Self, FlvError>;
+  fn fourcc(&self) -> &[u8; 4];
+  fn packet_type(&self) -> Self::PacketType;
+}
+
+impl ExTagHeader for ExAudioTagHeader {
+  type PacketType = AudioPacketType;
+
+  fn parse(input: &[u8]) -> IResult<&[u8], Self, FlvError> {
+    ex_audio_tag_header(input)
+  }
+
+  fn fourcc(&self) -> &[u8; 4] {
+    &self.fourcc
+  }
+
+  fn packet_type(&self) -> AudioPacketType {
+    self.packet_type
+  }
+}
+
+/// Parses the enhanced header of an audio or video tag body of `size` bytes,
+/// checking its FourCC and that its packet type is one of `packet_types`,
+/// and returns it along with the rest of the body.
+fn ex_body<'a, H: ExTagHeader>(
+  input: &'a [u8],
+  size: usize,
+  fourcc: &[u8; 4],
+  packet_types: &[H::PacketType],
+) -> Result<(H, &'a [u8]), Err<FlvError>> {
+  if input.len() < size {
+    return Err(Err::Incomplete(Needed::new(size - input.len())));
+  }
+  let (i, header) = H::parse(&input[..size])?;
+  if header.fourcc() != fourcc {
+    return Err(Err::Error(FlvError::FourCcMismatch {
+      expected: *fourcc,
+      found: *header.fourcc(),
+    }));
+  }
+  if !packet_types.contains(&header.packet_type()) {
+    return Err(Err::Error(FlvError::UnexpectedPacketType(input[0] & 0x0f)));
+  }
+  Ok((header, i))
+}
+
+/// The packet types of the enhanced audio codecs without a codec specific
+/// header.
+const EX_AUDIO_PACKET_TYPES: [AudioPacketType; 3] = [
+  AudioPacketType::SequenceStart,
+  AudioPacketType::CodedFrames,
+  AudioPacketType::SequenceEnd,
+];
+
+/// An enhanced RTMP multitrack audio packet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AudioMultitrack<'a> {
+  /// The packet type of every track.
+  pub packet_type: AudioPacketType,
+  pub multitrack_type: AvMultitrackType,
+  pub tracks: MultitrackPackets<'a>,
+}
+
+/// Parses an enhanced RTMP audio tag body of `size` bytes with the
+/// `Multitrack` packet type, first byte included. The tracks are read as
+/// `tracks` is iterated.
+pub fn ex_audio_multitrack(
+  input: &[u8],
+  size: usize,
+) -> IResult<&[u8], AudioMultitrack<'_>, FlvError> {
+  if input.len() < size {
+    return Err(Err::Incomplete(Needed::new(size - input.len())));
+  }
+  let (i, first) = be_u8(&input[..size])?;
+  if !is_ex_audio_header(first) {
+    return Err(Err::Error(FlvError::Nom(ErrorKind::Alt)));
+  }
+  if first & 0x0f != 5 {
+    return Err(Err::Error(FlvError::UnexpectedPacketType(first & 0x0f)));
+  }
+  let (packet_type, tracks) = multitrack_header(i)?;
+  let packet_type = ex_audio_packet_type(packet_type)?;
+
+  Ok((
+    &input[size..],
+    AudioMultitrack {
+      packet_type,
+      multitrack_type: tracks.multitrack_type,
+      tracks,
+    },
+  ))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct OpusAudioPacket<'a> {
+  /// `SequenceStart`, `CodedFrames` or `SequenceEnd`.
+  pub packet_type: AudioPacketType,
+  /// The `OpusHead` identification header for sequence starts, Opus packets
+  /// otherwise.
+  pub opus_data: &'a [u8],
+}
+
+/// Parses an enhanced RTMP Opus audio tag body of `size` bytes, first byte
+/// included.
+pub fn opus_audio_packet(
+  input: &[u8],
+  size: usize,
+) -> IResult<&[u8], OpusAudioPacket<'_>, FlvError> {
+  let (header, opus_data) =
+    ex_body::<ExAudioTagHeader>(input, size, b"Opus", &EX_AUDIO_PACKET_TYPES)?;
+
+  Ok((
+    &input[size..],
+    OpusAudioPacket {
+      packet_type: header.packet_type,
+      opus_data,
+    },
+  ))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct FlacAudioPacket<'a> {
+  /// `SequenceStart`, `CodedFrames` or `SequenceEnd`.
+  pub packet_type: AudioPacketType,
+  /// The `FLACSpecificBox` content for sequence starts: version and flags
+  /// then the metadata blocks, STREAMINFO first. FLAC frames otherwise.
+  pub flac_data: &'a [u8],
+}
+
+impl<'a> FlacAudioPacket<'a> {
+  /// The 34-byte STREAMINFO metadata block of a sequence start, without its
+  /// block header.
+  pub fn stream_info(&self) -> Option<&'a [u8]> {
+    if self.packet_type != AudioPacketType::SequenceStart {
+      return None;
+    }
+    // version and flags, then the block header: last block flag and type 0
+    let block = self.flac_data.get(4..)?;
+    let (&kind, size) = block.split_first()?;
+    if kind & 0x7f != 0 {
+      return None;
+    }
+    let size = u32::from_be_bytes([0, *size.first()?, *size.get(1)?, *size.get(2)?]) as usize;
+    if size != 34 {
+      return None;
+    }
+    block.get(4..4 + size)
+  }
+}
+
+/// Parses an enhanced RTMP FLAC audio tag body of `size` bytes, first byte
+/// included.
+pub fn flac_audio_packet(
+  input: &[u8],
+  size: usize,
+) -> IResult<&[u8], FlacAudioPacket<'_>, FlvError> {
+  let (header, flac_data) =
+    ex_body::<ExAudioTagHeader>(input, size, b"fLaC", &EX_AUDIO_PACKET_TYPES)?;
+
+  Ok((
+    &input[size..],
+    FlacAudioPacket {
+      packet_type: header.packet_type,
+      flac_data,
+    },
+  ))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Ac3AudioPacket<'a> {
+  /// `SequenceStart`, `CodedFrames` or `SequenceEnd`.
+  pub packet_type: AudioPacketType,
+  /// The `AC3SpecificBox` content for sequence starts, AC-3 sync frames
+  /// otherwise.
+  pub ac3_data: &'a [u8],
+}
+
+/// Parses an enhanced RTMP AC-3 audio tag body of `size` bytes, first byte
+/// included.
+pub fn ac3_audio_packet(input: &[u8], size: usize) -> IResult<&[u8], Ac3AudioPacket<'_>, FlvError> {
+  let (header, ac3_data) =
+    ex_body::<ExAudioTagHeader>(input, size, b"ac-3", &EX_AUDIO_PACKET_TYPES)?;
+
+  Ok((
+    &input[size..],
+    Ac3AudioPacket {
+      packet_type: header.packet_type,
+      ac3_data,
+    },
+  ))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameType {
+  Key,
+  Inter,
+  DisposableInter,
+  Generated,
+  Command,
+}
+
+impl FrameType {
+  fn from_nibble(frame_type: u8) -> Option<FrameType> {
+    match frame_type {
+      1 => Some(FrameType::Key),
+      2 => Some(FrameType::Inter),
+      3 => Some(FrameType::DisposableInter),
+      4 => Some(FrameType::Generated),
+      5 => Some(FrameType::Command),
+      _ => None,
+    }
+  }
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CodecId {
+  JPEG,
+  SORENSON_H263,
+  SCREEN,
+  VP6,
+  VP6A,
+  SCREEN2,
+  H264,
+  // Not in FLV standard
+  H263,
+  MPEG4Part2, // MPEG-4 Part 2
+  /// H.265, on codec id 12 as written by some muxers outside the
+  /// specification, or signaled by the `hvc1` FourCC in enhanced RTMP.
+  HEVC,
+  /// Only signaled by the `vp09` FourCC in enhanced RTMP.
+  VP9,
+  /// Only signaled by the `av01` FourCC in enhanced RTMP.
+  AV1,
+  /// A reserved codec id, like the 0 some screen recorders write, kept so
+  /// that such tags can be skipped instead of failing the parse.
+  Unknown(u8),
+}
+
+impl CodecId {
+  /// Maps the low nibble of the first video tag byte, with `H263` on 8 and
+  /// `MPEG4Part2` on 9 outside of the FLV specification.
+  fn from_nibble(codec_id: u8) -> CodecId {
+    match codec_id {
+      1 => CodecId::JPEG,
+      2 => CodecId::SORENSON_H263,
+      3 => CodecId::SCREEN,
+      4 => CodecId::VP6,
+      5 => CodecId::VP6A,
+      6 => CodecId::SCREEN2,
+      7 => CodecId::H264,
+      8 => CodecId::H263,
+      9 => CodecId::MPEG4Part2,
+      12 => CodecId::HEVC,
+      n => CodecId::Unknown(n),
+    }
+  }
+
+  /// Maps the FourCC of an enhanced RTMP video header.
+  pub fn from_fourcc(fourcc: &[u8; 4]) -> Option<CodecId> {
+    match fourcc {
+      b"avc1" => Some(CodecId::H264),
+      b"hvc1" => Some(CodecId::HEVC),
+      b"vp09" => Some(CodecId::VP9),
+      b"av01" => Some(CodecId::AV1),
+      _ => None,
+    }
+  }
+
+  /// The FourCC signaling this codec in enhanced RTMP, if there is one.
+  pub fn fourcc(self) -> Option<[u8; 4]> {
+    match self {
+      CodecId::H264 => Some(*b"avc1"),
+      CodecId::HEVC => Some(*b"hvc1"),
+      CodecId::VP9 => Some(*b"vp09"),
+      CodecId::AV1 => Some(*b"av01"),
+      _ => None,
+    }
+  }
+}
+
+/// Codec ids are 4 bits, bytes above 15 are rejected.
+impl TryFrom<u8> for CodecId {
+  type Error = FlvError;
+
+  fn try_from(codec_id: u8) -> Result<Self, Self::Error> {
+    if codec_id > 15 {
+      return Err(FlvError::UnknownCodecId(codec_id));
+    }
+    Ok(CodecId::from_nibble(codec_id))
+  }
+}
+
+/// `VP9` and `AV1`, which have no codec id, are rejected with
+/// `FlvError::NoCodecId`.
+impl TryFrom<CodecId> for u8 {
+  type Error = FlvError;
+
+  fn try_from(codec_id: CodecId) -> Result<Self, Self::Error> {
+    Ok(match codec_id {
+      CodecId::JPEG => 1,
+      CodecId::SORENSON_H263 => 2,
+      CodecId::SCREEN => 3,
+      CodecId::VP6 => 4,
+      CodecId::VP6A => 5,
+      CodecId::SCREEN2 => 6,
+      CodecId::H264 => 7,
+      CodecId::H263 => 8,
+      CodecId::MPEG4Part2 => 9,
+      CodecId::HEVC => 12,
+      CodecId::VP9 | CodecId::AV1 => return Err(FlvError::NoCodecId(codec_id)),
+      CodecId::Unknown(n) => n,
+    })
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AVCPacketType {
+  SequenceHeader,
+  NALU,
+  EndOfSequence,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AVCVideoPacketHeader {
+  pub packet_type: AVCPacketType,
+  pub composition_time: i32,
+}
+
+fn packet_type(input: &[u8]) -> IResult<&[u8], AVCPacketType, FlvError> {
+  map_res(be_u8, |packet_type| {
+    Ok(match packet_type {
+      0 => AVCPacketType::SequenceHeader,
+      1 => AVCPacketType::NALU,
+      2 => AVCPacketType::EndOfSequence,
+      packet_type => return Err(FlvError::UnknownPacketType(packet_type)),
+    })
+  })(input)
+}
+
+pub fn avc_video_packet_header(input: &[u8]) -> IResult<&[u8], AVCVideoPacketHeader, FlvError> {
+  map(
+    pair(packet_type, be_i24),
+    |(packet_type, composition_time)| AVCVideoPacketHeader {
+      packet_type,
+      composition_time,
+    },
+  )(input)
+}
+
+fn check_composition_time(
+  packet_type: AVCPacketType,
+  composition_time: i32,
+) -> Result<(), Err<FlvError>> {
+  if packet_type != AVCPacketType::NALU && composition_time != 0 {
+    return Err(Err::Error(FlvError::NonZeroCompositionTime(
+      composition_time,
+    )));
+  }
+  Ok(())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct AVCVideoPacket<'a> {
+  pub packet_type: AVCPacketType,
+  pub composition_time: i32,
+  pub avc_data: &'a [u8],
+}
+
+pub fn avc_video_packet(input: &[u8], size: usize) -> IResult<&[u8], AVCVideoPacket<'_>, FlvError> {
+  if input.len() < size {
+    return Err(Err::Incomplete(Needed::new(size - input.len())));
+  }
+
+  if size < 4 {
+    return Err(Err::Incomplete(Needed::new(4)));
+  }
+  pair(packet_type, be_i24)(input).map(|(_, (packet_type, composition_time))| {
+    (
+      &input[size..],
+      AVCVideoPacket {
+        packet_type,
+        composition_time,
+        avc_data: &input[4..size],
+      },
+    )
+  })
+}
+
+/// How the codec of a video tag is signaled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CodecSignaling {
+  /// With the codec id nibble of legacy FLV.
+  CodecId,
+  /// With a FourCC after an enhanced RTMP header, see `is_ex_video_header`.
+  FourCc,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HEVCPacketType {
+  SequenceHeader,
+  NALU,
+  EndOfSequence,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct HEVCVideoPacket<'a> {
+  pub signaling: CodecSignaling,
+  pub frame_type: FrameType,
+  pub packet_type: HEVCPacketType,
+  /// Always 0 for enhanced `CodedFramesX` packets, which don't carry it.
+  pub composition_time: i32,
+  /// The `HEVCDecoderConfigurationRecord` for sequence headers, NAL units
+  /// otherwise.
+  pub hevc_data: &'a [u8],
+}
+
+/// Parses an HEVC video tag body of `size` bytes.
+///
+/// Unlike `avc_video_packet`, this takes the whole body, first byte
+/// included, since HEVC is signaled either with the legacy codec id 12,
+/// followed by the same packet type and composition time as AVC, or with an
+/// enhanced RTMP header and the `hvc1` FourCC, where the packet type is in
+/// the first byte.
+pub fn hevc_video_packet(
+  input: &[u8],
+  size: usize,
+) -> IResult<&[u8], HEVCVideoPacket<'_>, FlvError> {
+  if input.len() < size {
+    return Err(Err::Incomplete(Needed::new(size - input.len())));
+  }
+  let body = &input[..size];
+  let first = *body.first().ok_or(Err::Incomplete(Needed::new(1)))?;
+
+  let (frame_type, signaling, packet_type, composition_time, hevc_data) =
+    if is_ex_video_header(first) {
+      let (header, i) = ex_body::<ExVideoTagHeader>(
+        body,
+        size,
+        b"hvc1",
+        &[
+          VideoPacketType::SequenceStart,
+          VideoPacketType::CodedFrames,
+          VideoPacketType::SequenceEnd,
+          VideoPacketType::CodedFramesX,
+        ],
+      )?;
+      let (i, packet_type, composition_time) = match header.packet_type {
+        VideoPacketType::SequenceStart => (i, HEVCPacketType::SequenceHeader, 0),
+        VideoPacketType::CodedFrames => {
+          let (i, composition_time) = be_i24(i)?;
+          (i, HEVCPacketType::NALU, composition_time)
+        }
+        VideoPacketType::SequenceEnd => (i, HEVCPacketType::EndOfSequence, 0),
+        _ => (i, HEVCPacketType::NALU, 0),
+      };
+      (
+        header.frame_type,
+        CodecSignaling::FourCc,
+        packet_type,
+        composition_time,
+        i,
+      )
+    } else {
+      let (i, header) = video_data_header(body)?;
+      match header.codec_id {
+        CodecId::HEVC => {}
+        CodecId::Unknown(codec_id) => return Err(Err::Error(FlvError::UnknownCodecId(codec_id))),
+        codec_id => return Err(Err::Error(FlvError::UnexpectedCodecId(codec_id))),
+      }
+      let (i, (packet_type, composition_time)) = pair(be_u8, be_i24)(i)?;
+      let packet_type = match packet_type {
+        0 => HEVCPacketType::SequenceHeader,
+        1 => HEVCPacketType::NALU,
+        2 => HEVCPacketType::EndOfSequence,
+        packet_type => return Err(Err::Error(FlvError::UnknownPacketType(packet_type))),
+      };
+      (
+        header.frame_type,
+        CodecSignaling::CodecId,
+        packet_type,
+        composition_time,
+        i,
+      )
+    };
+
+  Ok((
+    &input[size..],
+    HEVCVideoPacket {
+      signaling,
+      frame_type,
+      packet_type,
+      composition_time,
+      hevc_data,
+    },
+  ))
+}
+
+/// The `AVCDecoderConfigurationRecord` (ISO/IEC 14496-15) carried in the
+/// `avc_data` of an `AVCPacketType::SequenceHeader` packet.
+///
+/// A record can hold several SPS and PPS, so every one of them is kept, in
+/// stream order.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AVCDecoderConfigurationRecord<'a> {
+  pub configuration_version: u8,
+  pub profile_indication: u8,
+  pub profile_compatibility: u8,
+  pub level_indication: u8,
+  pub length_size_minus_one: u8,
+  pub sequence_parameter_sets: Vec<&'a [u8]>,
+  pub picture_parameter_sets: Vec<&'a [u8]>,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> AVCDecoderConfigurationRecord<'a> {
+  /// Frame rate from the VUI timing information of the first SPS, which is
+  /// more reliable than the `framerate` of `onMetaData`.
+  pub fn frame_rate(&self) -> Option<f64> {
+    sps_timing(self.sequence_parameter_sets.first()?)?.frame_rate()
+  }
+}
+
+#[cfg(feature = "alloc")]
+pub fn avc_decoder_configuration_record(
+  input: &[u8],
+) -> IResult<&[u8], AVCDecoderConfigurationRecord<'_>, FlvError> {
+  map(
+    tuple((
+      be_u8,
+      be_u8,
+      be_u8,
+      be_u8,
+      map(be_u8, |b| b & 0x03),
+      length_count(map(be_u8, |n| n & 0x1f), length_data(be_u16)),
+      length_count(be_u8, length_data(be_u16)),
+    )),
+    |(
+      configuration_version,
+      profile_indication,
+      profile_compatibility,
+      level_indication,
+      length_size_minus_one,
+      sequence_parameter_sets,
+      picture_parameter_sets,
+    )| AVCDecoderConfigurationRecord {
+      configuration_version,
+      profile_indication,
+      profile_compatibility,
+      level_indication,
+      length_size_minus_one,
+      sequence_parameter_sets,
+      picture_parameter_sets,
+    },
+  )(input)
+}
+
+/// Splits the `avc_data` of an `AVCPacketType::NALU` packet into its NAL
+/// units, each prefixed by its length on `length_size` bytes, which is the
+/// `length_size_minus_one` of the configuration record plus one.
+///
+/// The units borrow the input. A truncated last unit gives `Incomplete`.
+#[cfg(feature = "alloc")]
+pub fn avc_nal_units(data: &[u8], length_size: u8) -> IResult<&[u8], Vec<&[u8]>, FlvError> {
+  if !(1..=4).contains(&length_size) {
+    return Err(Err::Error(FlvError::Nom(ErrorKind::Verify)));
+  }
+
+  let mut units = Vec::new();
+  let mut i = data;
+  while !i.is_empty() {
+    let (rest, length) = take_bytes(length_size)(i)?;
+    let length = length
+      .iter()
+      .fold(0usize, |acc, &byte| (acc << 8) | usize::from(byte));
+    let (rest, unit) = take_bytes(length)(rest)?;
+    units.push(unit);
+    i = rest;
+  }
+  Ok((i, units))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VideoData<'a> {
+  pub frame_type: FrameType,
+  pub codec_id: CodecId,
+  /// Everything after the frame type and codec id byte. For AVC, this
+  /// starts with the `AVCPacketType` and composition time, see
+  /// `avc_packet`.
+  pub video_data: &'a [u8],
+}
+
+impl<'a> VideoData<'a> {
+  /// Splits the `AVCPacketType` and composition time off the payload of AVC
+  /// video, leaving the NAL units or the decoder configuration record.
+  /// Returns `None` for other codecs, or if the header is invalid.
+  pub fn avc_packet(&self) -> Option<AVCVideoPacket<'a>> {
+    if self.codec_id != CodecId::H264 {
+      return None;
+    }
+    let (_, packet) = avc_video_packet(self.video_data, self.video_data.len()).ok()?;
+    Some(packet)
+  }
+
+  /// Returns the frame type and codec id nibbles of the first byte of the
+  /// video tag body, as they were on the wire. Fails for the codecs that
+  /// only have a FourCC, see `FlvError::NoCodecId`.
+  pub fn raw_nibbles(&self) -> Result<(u8, u8), FlvError> {
+    let frame_type = match self.frame_type {
+      FrameType::Key => 1,
+      FrameType::Inter => 2,
+      FrameType::DisposableInter => 3,
+      FrameType::Generated => 4,
+      FrameType::Command => 5,
+    };
+    let codec_id = u8::try_from(self.codec_id)?;
+
+    Ok((frame_type, codec_id))
+  }
+
+  /// Whether the tag is only made of the frame type / codec id byte, with no
+  /// codec data after it. This is valid for some command or end of sequence
+  /// packets.
+  pub fn is_empty_payload(&self) -> bool {
+    self.video_data.is_empty()
+  }
+
+  /// Whether this is an AVC or legacy HEVC sequence header, carrying the
+  /// decoder configuration record instead of a frame.
+  pub fn is_sequence_header(&self) -> bool {
+    matches!(self.codec_id, CodecId::H264 | CodecId::HEVC) && self.video_data.first() == Some(&0)
+  }
+}
+
+/// Whether the first byte of a video tag body signals an enhanced RTMP
+/// header: bit 7 (`IsExHeader`) is set, the next 3 bits are the frame type
+/// and the low nibble a video packet type instead of a codec id.
+pub fn is_ex_video_header(byte: u8) -> bool {
+  byte & 0x80 != 0
+}
+
+/// Enhanced RTMP video packet type, the low nibble of the first byte after
+/// an enhanced header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VideoPacketType {
+  SequenceStart,
+  CodedFrames,
+  SequenceEnd,
+  /// Coded frames without composition time, which is then 0.
+  CodedFramesX,
+  Metadata,
+  MPEG2TSSequenceStart,
+  Multitrack,
+  ModEx,
+}
+
+/// The header of an enhanced RTMP video tag body.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExVideoTagHeader {
+  pub frame_type: FrameType,
+  pub packet_type: VideoPacketType,
+  /// Codec FourCC, like `avc1`, `hvc1`, `vp09` or `av01`.
+  pub fourcc: [u8; 4],
+}
+
+impl ExVideoTagHeader {
+  /// The codec of `fourcc`, if it is a known one.
+  pub fn codec_id(&self) -> Option<CodecId> {
+    CodecId::from_fourcc(&self.fourcc)
+  }
+}
+
+/// The packet type of a nibble followed by a FourCC, which `Multitrack` and
+/// `ModEx` are not.
+fn ex_video_packet_type(nibble: u8) -> Result<VideoPacketType, Err<FlvError>> {
+  Ok(match nibble {
+    0 => VideoPacketType::SequenceStart,
+    1 => VideoPacketType::CodedFrames,
+    2 => VideoPacketType::SequenceEnd,
+    3 => VideoPacketType::CodedFramesX,
+    4 => VideoPacketType::Metadata,
+    5 => VideoPacketType::MPEG2TSSequenceStart,
+    6 | 7 => return Err(Err::Error(FlvError::UnexpectedPacketType(nibble))),
+    _ => return Err(Err::Error(FlvError::UnknownPacketType(nibble))),
+  })
+}
+
+/// Parses the first byte and FourCC of an enhanced RTMP video tag body.
+///
+/// This is the counterpart of `video_data_header` for tags with the
+/// `IsExHeader` bit set: each parser rejects the tags of the other, so the
+/// two can be tried in turn. `Multitrack` and `ModEx` packets, whose FourCC
+/// is not right after the first byte, are rejected: parse multitrack
+/// packets with `ex_video_multitrack`.
+pub fn ex_video_tag_header(input: &[u8]) -> IResult<&[u8], ExVideoTagHeader, FlvError> {
+  let (i, first) = be_u8(input)?;
+  if !is_ex_video_header(first) {
+    return Err(Err::Error(FlvError::Nom(ErrorKind::Alt)));
+  }
+  let frame_type = (first >> 4) & 0x07;
+  let frame_type =
+    FrameType::from_nibble(frame_type).ok_or(Err::Error(FlvError::UnknownFrameType(frame_type)))?;
+  let packet_type = ex_video_packet_type(first & 0x0f)?;
+  let (i, fourcc) = take_bytes(4usize)(i)?;
+
+  Ok((
+    i,
+    ExVideoTagHeader {
+      frame_type,
+      packet_type,
+      fourcc: [fourcc[0], fourcc[1], fourcc[2], fourcc[3]],
+    },
+  ))
+}
+
+impl ExTagHeader for ExVideoTagHeader {
+  type PacketType = VideoPacketType;
+
+  fn parse(input: &[u8]) -> IResult<&[u8], Self, FlvError> {
+    ex_video_tag_header(input)
+  }
+
+  fn fourcc(&self) -> &[u8; 4] {
+    &self.fourcc
+  }
+
+  fn packet_type(&self) -> VideoPacketType {
+    self.packet_type
+  }
+}
+
+/// An enhanced RTMP multitrack video packet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VideoMultitrack<'a> {
+  pub frame_type: FrameType,
+  /// The packet type of every track.
+  pub packet_type: VideoPacketType,
+  pub multitrack_type: AvMultitrackType,
+  pub tracks: MultitrackPackets<'a>,
+}
+
+/// Parses an enhanced RTMP video tag body of `size` bytes with the
+/// `Multitrack` packet type, first byte included. The tracks are read as
+/// `tracks` is iterated.
+pub fn ex_video_multitrack(
+  input: &[u8],
+  size: usize,
+) -> IResult<&[u8], VideoMultitrack<'_>, FlvError> {
+  if input.len() < size {
+    return Err(Err::Incomplete(Needed::new(size - input.len())));
+  }
+  let (i, first) = be_u8(&input[..size])?;
+  if !is_ex_video_header(first) {
+    return Err(Err::Error(FlvError::Nom(ErrorKind::Alt)));
+  }
+  if first & 0x0f != 6 {
+    return Err(Err::Error(FlvError::UnexpectedPacketType(first & 0x0f)));
+  }
+  let frame_type = (first >> 4) & 0x07;
+  let frame_type =
+    FrameType::from_nibble(frame_type).ok_or(Err::Error(FlvError::UnknownFrameType(frame_type)))?;
+  let (packet_type, tracks) = multitrack_header(i)?;
+  let packet_type = ex_video_packet_type(packet_type)?;
+
+  Ok((
+    &input[size..],
+    VideoMultitrack {
+      frame_type,
+      packet_type,
+      multitrack_type: tracks.multitrack_type,
+      tracks,
+    },
+  ))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Av1VideoPacket<'a> {
+  pub frame_type: FrameType,
+  /// `SequenceStart`, `CodedFrames`, `SequenceEnd` or
+  /// `MPEG2TSSequenceStart`.
+  pub packet_type: VideoPacketType,
+  /// The `AV1CodecConfigurationRecord` for sequence starts, OBUs otherwise.
+  pub av1_data: &'a [u8],
+}
+
+impl<'a> Av1VideoPacket<'a> {
+  /// The configuration OBUs of a sequence start, which follow the 4 fixed
+  /// bytes of the `AV1CodecConfigurationRecord`.
+  pub fn config_obus(&self) -> Option<&'a [u8]> {
+    match self.packet_type {
+      VideoPacketType::SequenceStart => self.av1_data.get(4..),
+      _ => None,
+    }
+  }
+}
+
+/// Parses an enhanced RTMP AV1 video tag body of `size` bytes, first byte
+/// included.
+pub fn av1_video_packet(input: &[u8], size: usize) -> IResult<&[u8], Av1VideoPacket<'_>, FlvError> {
+  let (header, av1_data) = ex_body::<ExVideoTagHeader>(
+    input,
+    size,
+    b"av01",
+    &[
+      VideoPacketType::SequenceStart,
+      VideoPacketType::CodedFrames,
+      VideoPacketType::SequenceEnd,
+      VideoPacketType::MPEG2TSSequenceStart,
+    ],
+  )?;
+
+  Ok((
+    &input[size..],
+    Av1VideoPacket {
+      frame_type: header.frame_type,
+      packet_type: header.packet_type,
+      av1_data,
+    },
+  ))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Vp9VideoPacket<'a> {
+  pub frame_type: FrameType,
+  /// `SequenceStart`, `CodedFrames`, `CodedFramesX` or `SequenceEnd`: VP9
+  /// has no composition time, so both kinds of coded frames are the same.
+  pub packet_type: VideoPacketType,
+  /// The `VPCodecConfigurationRecord` for sequence starts, VP9 frames
+  /// otherwise.
+  pub vp9_data: &'a [u8],
+}
+
+impl<'a> Vp9VideoPacket<'a> {
+  /// The `VPCodecConfigurationRecord` of a sequence start.
+  pub fn codec_configuration(&self) -> Option<&'a [u8]> {
+    match self.packet_type {
+      VideoPacketType::SequenceStart => Some(self.vp9_data),
+      _ => None,
+    }
+  }
+
+  /// The VP9 frame of a coded frames packet.
+  pub fn frame(&self) -> Option<&'a [u8]> {
+    match self.packet_type {
+      VideoPacketType::CodedFrames | VideoPacketType::CodedFramesX => Some(self.vp9_data),
+      _ => None,
+    }
+  }
+}
+
+/// Parses an enhanced RTMP VP9 video tag body of `size` bytes, first byte
+/// included.
+pub fn vp9_video_packet(input: &[u8], size: usize) -> IResult<&[u8], Vp9VideoPacket<'_>, FlvError> {
+  let (header, vp9_data) = ex_body::<ExVideoTagHeader>(
+    input,
+    size,
+    b"vp09",
+    &[
+      VideoPacketType::SequenceStart,
+      VideoPacketType::CodedFrames,
+      VideoPacketType::CodedFramesX,
+      VideoPacketType::SequenceEnd,
+    ],
+  )?;
+
+  Ok((
+    &input[size..],
+    Vp9VideoPacket {
+      frame_type: header.frame_type,
+      packet_type: header.packet_type,
+      vp9_data,
+    },
+  ))
+}
+
+/// Parses a video tag body of `size` bytes.
+///
+/// The codec specific header is left in `video_data`, like the
+/// `AVCPacketType` and composition time of AVC, see `VideoData::avc_packet`.
+///
+/// A 1-byte body is accepted: it yields an empty `video_data` slice, see
+/// `VideoData::is_empty_payload`.
+pub fn video_data(input: &[u8], size: usize) -> IResult<&[u8], VideoData<'_>, FlvError> {
+  if input.len() < size {
+    return Err(Err::Incomplete(Needed::new(size - input.len())));
+  }
+
+  if size < 1 {
+    return Err(Err::Incomplete(Needed::new(1)));
+  }
+
+  if is_ex_video_header(input[0]) {
+    return Err(Err::Error(FlvError::Nom(ErrorKind::Alt)));
+  }
+
+  let take_bits = pair(take(4usize), take(4usize));
+  bits::<_, _, FlvError, _, _>(take_bits)(input).and_then(|(_, (frame_type, codec_id))| {
+    let frame_type = FrameType::from_nibble(frame_type)
+      .ok_or(Err::Error(FlvError::UnknownFrameType(frame_type)))?;
+    let codec_id = CodecId::from_nibble(codec_id);
+
+    Ok((
+      &input[size..],
+      VideoData {
+        frame_type,
+        codec_id,
+        video_data: &input[1..size],
+      },
+    ))
+  })
+}
+
+/// A video tag body parsed according to its codec, by `video_tag`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum VideoTag<'a> {
+  Avc(AVCVideoPacket<'a>),
+  /// HEVC signaled with the legacy codec id 12.
+  Hevc(HEVCVideoPacket<'a>),
+  /// VP6, or VP6 with alpha when `alpha_offset` is set.
+  Vp6(Vp6VideoPacket<'a>),
+  Screen(ScreenVideoPacket<'a>),
+  /// A command frame of any codec, whose data after the first byte is a
+  /// single byte instead of a picture: 0 to start seeking on the client
+  /// side, 1 to end it.
+  Command {
+    codec_id: CodecId,
+    data: &'a [u8],
+  },
+  /// The other codecs, like Sorenson H.263, with the data after the first
+  /// byte.
+  Other {
+    codec_id: CodecId,
+    data: &'a [u8],
+  },
+}
+
+/// Parses a video tag body of `size` bytes like `video_data`, then its
+/// payload with the parser for its codec: `avc_video_packet`,
+/// `hevc_video_packet`, `vp6_video_packet` or `screen_video_packet`.
+/// Command frames are not passed to them. The frame type is left out, read
+/// it with `video_data_header`.
+///
+/// Since the whole body is there, an AVC payload too short for its packet
+/// type and composition time is an error instead of `Err::Incomplete`.
+pub fn video_tag(input: &[u8], size: usize) -> IResult<&[u8], VideoTag<'_>, FlvError> {
+  let (i, video) = video_data(input, size)?;
+  let data = video.video_data;
+  let tag = match video.codec_id {
+    codec_id if video.frame_type == FrameType::Command => VideoTag::Command { codec_id, data },
+    CodecId::H264 if data.len() < 4 => {
+      return Err(Err::Error(FlvError::BadLength {
+        field: "AVC packet header",
+        declared: 4,
+        available: data.len(),
+      }))
+    }
+    CodecId::H264 => VideoTag::Avc(avc_video_packet(data, data.len())?.1),
+    CodecId::HEVC => VideoTag::Hevc(hevc_video_packet(input, size)?.1),
+    CodecId::VP6 | CodecId::VP6A => VideoTag::Vp6(vp6_video_packet(data, video.codec_id)?.1),
+    CodecId::SCREEN => VideoTag::Screen(screen_video_packet(data)?.1),
+    codec_id => VideoTag::Other { codec_id, data },
+  };
+  Ok((i, tag))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VideoDataHeader {
+  pub frame_type: FrameType,
+  pub codec_id: CodecId,
+}
+
+pub fn video_data_header(input: &[u8]) -> IResult<&[u8], VideoDataHeader, FlvError> {
+  if input.is_empty() {
+    return Err(Err::Incomplete(Needed::new(1)));
+  }
+
+  if is_ex_video_header(input[0]) {
+    return Err(Err::Error(FlvError::Nom(ErrorKind::Alt)));
+  }
+
+  let take_bits = pair(take(4usize), take(4usize));
+  map_res(
+    bits::<_, _, FlvError, _, _>(take_bits),
+    |(frame_type, codec_id)| {
+      let frame_type =
+        FrameType::from_nibble(frame_type).ok_or(FlvError::UnknownFrameType(frame_type))?;
+      let codec_id = CodecId::from_nibble(codec_id);
+
+      Ok::<_, FlvError>(VideoDataHeader {
+        frame_type,
+        codec_id,
+      })
+    },
+  )(input)
+}
+
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScriptData<'a> {
+  pub name: &'a str,
+  pub arguments: ScriptDataValue<'a>,
+}
+
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScriptDataValue<'a> {
+  Number(f64),
+  Boolean(bool),
+  String(&'a str),
+  Object(Vec<ScriptDataObject<'a>>),
+  MovieClip(&'a str),
+  /// Marker 5, with no payload.
+  Null,
+  /// Marker 6, with no payload.
+  Undefined,
+  Reference(u16),
+  /// An associative array. `count` is the number of entries the file
+  /// declares, which is only approximate: it is kept to be written back as
+  /// is, and is often not `entries.len()`.
+  ECMAArray {
+    count: u32,
+    entries: Vec<ScriptDataObject<'a>>,
+  },
+  StrictArray(Vec<ScriptDataValue<'a>>),
+  Date(ScriptDataDate),
+  LongString(&'a str),
+  /// An object with the name of its class, as registered by the authoring
+  /// tool.
+  TypedObject {
+    class_name: &'a str,
+    properties: Vec<ScriptDataObject<'a>>,
+  },
+  /// A value switched to AMF3 with the `avmplus-object-marker`.
+  AMF3(AMF3Value<'a>),
+  /// A string or long string that is not UTF-8, with
+  /// `ParserOptions::raw_invalid_strings`. Some encoders write Latin-1, or
+  /// it can be decoded with `String::from_utf8_lossy`. It is written back as
+  /// a long string only if it does not fit in a string.
+  RawString(&'a [u8]),
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> ScriptDataValue<'a> {
+  /// An ECMA array whose count is the number of `entries`.
+  pub fn ecma_array(entries: Vec<ScriptDataObject<'a>>) -> Self {
+    ScriptDataValue::ECMAArray {
+      count: entries.len() as u32,
+      entries,
+    }
+  }
+}
+
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScriptDataObject<'a> {
+  pub name: &'a str,
+  pub data: ScriptDataValue<'a>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScriptDataDate {
+  pub date_time: f64,
+  pub local_date_time_offset: i16, // SI16
+}
+
+#[cfg(feature = "alloc")]
+#[allow(non_upper_case_globals)]
+static script_data_name_tag: &[u8] = &[2];
+
+#[cfg(feature = "alloc")]
+pub fn script_data(input: &[u8]) -> IResult<&[u8], ScriptData<'_>, FlvError> {
+  amf_script_data(input, &ParserOptions::default())
+}
+
+#[cfg(feature = "alloc")]
+fn amf_script_data<'a>(
+  input: &'a [u8],
+  options: &ParserOptions,
+) -> IResult<&'a [u8], ScriptData<'a>, FlvError> {
+  // Must start with a string, i.e. 2
+  let (i, _) = tag(script_data_name_tag)(input)?;
+  let (i, name) = amf_string(i, false, options)?;
+  let (i, arguments) = amf_value(i, options)?;
+  Ok((i, ScriptData { name, arguments }))
+}
+
+#[cfg(feature = "alloc")]
+pub fn script_data_value(input: &[u8]) -> IResult<&[u8], ScriptDataValue<'_>, FlvError> {
+  amf_value(input, &ParserOptions::default())
+}
+
+#[cfg(feature = "alloc")]
+fn amf_value<'a>(
+  input: &'a [u8],
+  options: &ParserOptions,
+) -> IResult<&'a [u8], ScriptDataValue<'a>, FlvError> {
+  be_u8(input).and_then(|v| match v {
+    (i, 0) => map(be_f64, ScriptDataValue::Number)(i),
+    (i, 1) => map(be_u8, |n| ScriptDataValue::Boolean(n != 0))(i),
+    (i, 2) => amf_string_value(i, false, options),
+    (i, 3) => map(|i| amf_objects(i, options), ScriptDataValue::Object)(i),
+    (i, 4) => map(
+      |i| amf_string(i, false, options),
+      ScriptDataValue::MovieClip,
+    )(i),
+    (i, 5) => Ok((i, ScriptDataValue::Null)),
+    (i, 6) => Ok((i, ScriptDataValue::Undefined)),
+    (i, 7) => map(be_u16, ScriptDataValue::Reference)(i),
+    (i, 8) => map(
+      |i| amf_ecma_array(i, options),
+      |(count, entries)| ScriptDataValue::ECMAArray { count, entries },
+    )(i),
+    (i, 10) => map(
+      |i| amf_strict_array(i, options),
+      ScriptDataValue::StrictArray,
+    )(i),
+    (i, 11) => map(script_data_date, ScriptDataValue::Date)(i),
+    (i, 12) => amf_string_value(i, true, options),
+    (i, 16) => map(
+      pair(
+        |i| amf_string(i, false, options),
+        |i| amf_objects(i, options),
+      ),
+      |(class_name, properties)| ScriptDataValue::TypedObject {
+        class_name,
+        properties,
+      },
+    )(i),
+    (i, 17) => map(|i| amf3_value(i, options), ScriptDataValue::AMF3)(i),
+    _ => Err(Err::Error(FlvError::Nom(ErrorKind::Alt))),
+  })
+}
+
+#[cfg(feature = "alloc")]
+pub fn script_data_objects(input: &[u8]) -> IResult<&[u8], Vec<ScriptDataObject<'_>>, FlvError> {
+  amf_objects(input, &ParserOptions::default())
+}
+
+#[cfg(feature = "alloc")]
+fn amf_objects<'a>(
+  input: &'a [u8],
+  options: &ParserOptions,
+) -> IResult<&'a [u8], Vec<ScriptDataObject<'a>>, FlvError> {
+  let mut objects = Vec::new();
+  let mut input = input;
+  // check for the terminator first, so that an error in a property is
+  // reported as is instead of as a missing terminator
+  loop {
+    match script_data_object_end(input) {
+      Ok((i, _)) => return Ok((i, objects)),
+      Err(Err::Error(_)) => {}
+      Err(e) => return Err(e),
+    }
+    let (i, object) = amf_object(input, options)?;
+    objects.push(object);
+    input = i;
+  }
+}
+
+#[cfg(feature = "alloc")]
+pub fn script_data_object(input: &[u8]) -> IResult<&[u8], ScriptDataObject<'_>, FlvError> {
+  amf_object(input, &ParserOptions::default())
+}
+
+#[cfg(feature = "alloc")]
+fn amf_object<'a>(
+  input: &'a [u8],
+  options: &ParserOptions,
+) -> IResult<&'a [u8], ScriptDataObject<'a>, FlvError> {
+  let (i, name) = amf_string(input, false, options)?;
+  let (i, data) = amf_value(i, options)?;
+  Ok((i, ScriptDataObject { name, data }))
+}
+
+#[allow(non_upper_case_globals)]
+static script_data_object_end_terminator: &[u8] = &[0, 0, 9];
+
+pub fn script_data_object_end(input: &[u8]) -> IResult<&[u8], &[u8], FlvError> {
+  tag(script_data_object_end_terminator)(input)
+}
+
+pub fn script_data_string(input: &[u8]) -> IResult<&[u8], &str, FlvError> {
+  amf_string(input, false, &ParserOptions::default())
+}
+
+pub fn script_data_long_string(input: &[u8]) -> IResult<&[u8], &str, FlvError> {
+  amf_string(input, true, &ParserOptions::default())
+}
+
+/// Parses the bytes of a string without checking they are UTF-8, for
+/// strings in other encodings.
+pub fn script_data_raw_string(input: &[u8]) -> IResult<&[u8], &[u8], FlvError> {
+  amf_bytes(input, false, &ParserOptions::default())
+}
+
+/// Parses a string prefixed by its length, on 32 bits for long strings and
+/// 16 bits otherwise.
+fn amf_string<'a>(
+  input: &'a [u8],
+  long: bool,
+  options: &ParserOptions,
+) -> IResult<&'a [u8], &'a str, FlvError> {
+  map_res(|i| amf_bytes(i, long, options), from_utf8)(input)
+}
+
+/// Parses a string or long string value, which can be kept as
+/// `ScriptDataValue::RawString` if it is not UTF-8.
+#[cfg(feature = "alloc")]
+fn amf_string_value<'a>(
+  input: &'a [u8],
+  long: bool,
+  options: &ParserOptions,
+) -> IResult<&'a [u8], ScriptDataValue<'a>, FlvError> {
+  if !options.raw_invalid_strings {
+    return if long {
+      map(
+        |i| amf_string(i, true, options),
+        ScriptDataValue::LongString,
+      )(input)
+    } else {
+      map(|i| amf_string(i, false, options), ScriptDataValue::String)(input)
+    };
+  }
+  let (i, bytes) = amf_bytes(input, long, options)?;
+  let value = match from_utf8(bytes) {
+    Ok(s) if long => ScriptDataValue::LongString(s),
+    Ok(s) => ScriptDataValue::String(s),
+    Err(_) => ScriptDataValue::RawString(bytes),
+  };
+  Ok((i, value))
+}
+
+fn amf_bytes<'a>(
+  input: &'a [u8],
+  long: bool,
+  options: &ParserOptions,
+) -> IResult<&'a [u8], &'a [u8], FlvError> {
+  let (i, declared) = if long {
+    be_u32(input)?
+  } else {
+    map(be_u16, u32::from)(input)?
+  };
+  let declared = declared as usize;
+
+  if options.strict_lengths && declared > i.len() {
+    return Err(Err::Error(FlvError::BadLength {
+      field: if long { "long string" } else { "string" },
+      declared,
+      available: i.len(),
+    }));
+  }
+
+  take_bytes(declared)(i)
+}
+
+pub fn script_data_date(input: &[u8]) -> IResult<&[u8], ScriptDataDate, FlvError> {
+  map(
+    pair(be_f64, be_i16),
+    |(date_time, local_date_time_offset)| ScriptDataDate {
+      date_time,
+      local_date_time_offset,
+    },
+  )(input)
+}
+
+#[cfg(feature = "alloc")]
+/// Parses an ECMA array body into its declared count and its entries.
+pub fn script_data_ecma_array(
+  input: &[u8],
+) -> IResult<&[u8], (u32, Vec<ScriptDataObject<'_>>), FlvError> {
+  amf_ecma_array(input, &ParserOptions::default())
+}
+
+#[cfg(feature = "alloc")]
+fn amf_ecma_array<'a>(
+  input: &'a [u8],
+  options: &ParserOptions,
+) -> IResult<&'a [u8], (u32, Vec<ScriptDataObject<'a>>), FlvError> {
+  let (i, count) = be_u32(input)?;
+  let (i, entries) = amf_objects(i, options)?;
+  Ok((i, (count, entries)))
+}
+
+#[cfg(feature = "alloc")]
+pub fn script_data_strict_array(
+  input: &[u8],
+) -> IResult<&[u8], Vec<ScriptDataValue<'_>>, FlvError> {
+  amf_strict_array(input, &ParserOptions::default())
+}
+
+#[cfg(feature = "alloc")]
+fn amf_strict_array<'a>(
+  input: &'a [u8],
+  options: &ParserOptions,
+) -> IResult<&'a [u8], Vec<ScriptDataValue<'a>>, FlvError> {
+  let (i, len) = be_u32(input)?;
+  check_array_len(len, options)?;
+  count(|i| amf_value(i, options), len as usize)(i)
+}
+
+/// Checks the declared length of an array whose elements are read by count.
+#[cfg(feature = "alloc")]
+pub(crate) fn check_array_len(len: u32, options: &ParserOptions) -> Result<(), Err<FlvError>> {
+  if len > options.max_array_len {
+    return Err(Err::Error(FlvError::ArrayTooLarge {
+      len,
+      max: options.max_array_len,
+    }));
+  }
+  Ok(())
+}
+
+#[allow(non_upper_case_globals)]
+#[cfg(test)]
+mod tests {
+  use super::*;
+  #[cfg(feature = "alloc")]
+  use crate::amf3::AMF3Property;
+  #[cfg(feature = "alloc")]
+  use alloc::{string::ToString, vec, vec::Vec};
+  #[cfg(feature = "std")]
+  use nom::number::streaming::be_u32;
+  #[cfg(feature = "std")]
+  use nom::HexDisplay;
+
+  const zelda: &[u8] = include_bytes!("../assets/zelda.flv");
+  const zeldaHQ: &[u8] = include_bytes!("../assets/zeldaHQ.flv");
+  const commercials: &[u8] = include_bytes!("../assets/asian-commercials-are-weird.flv");
+
+  /// Offset of the first tag of a test file.
+  fn first_tag_at(input: &[u8]) -> usize {
+    body_start(&header(input).unwrap().1)
+  }
+
+  /// Offset of the body of the first tag of a test file.
+  fn first_body_at(input: &[u8]) -> usize {
+    first_tag_at(input) + 11
+  }
+
+  #[test]
+  fn headers() {
+    assert_eq!(
+      header(&zelda[..9]),
+      Ok((
+        &b""[..],
+        Header {
+          version: 1,
+          audio: true,
+          video: true,
+          offset: 9
+        }
+      ))
+    );
+    assert_eq!(
+      header(&zeldaHQ[..9]),
+      Ok((
+        &b""[..],
+        Header {
+          version: 1,
+          audio: true,
+          video: true,
+          offset: 9
+        }
+      ))
+    );
+    assert_eq!(
+      header(&commercials[..9]),
+      Ok((
+        &b""[..],
+        Header {
+          version: 1,
+          audio: true,
+          video: true,
+          offset: 9
+        }
+      ))
+    );
+  }
+
+  #[test]
+  fn first_tag_headers() {
+    // starts at 9 bytes (header) + 4 (size of previous tag)
+    // header is 11 bytes long
+    let tag_at = first_tag_at(zelda);
+    assert_eq!(
+      tag_header(&zelda[tag_at..tag_at + 11]),
+      Ok((
+        &b""[..],
+        TagHeader {
+          tag_type: TagType::Video,
+          data_size: 537,
+          timestamp: 0,
+          stream_id: 0,
+          filtered: false,
+        }
+      ))
+    );
+    let hq_tag_at = first_tag_at(zeldaHQ);
+    assert_eq!(
+      tag_header(&zeldaHQ[hq_tag_at..hq_tag_at + 11]),
+      Ok((
+        &b""[..],
+        TagHeader {
+          tag_type: TagType::Video,
+          data_size: 2984,
+          timestamp: 0,
+          stream_id: 0,
+          filtered: false,
+        }
+      ))
+    );
+    let script_tag_at = first_tag_at(commercials);
+    assert_eq!(
+      tag_header(&commercials[script_tag_at..script_tag_at + 11]),
+      Ok((
+        &b""[..],
+        TagHeader {
+          tag_type: TagType::Script,
+          data_size: 273,
+          timestamp: 0,
+          stream_id: 0,
+          filtered: false,
+        }
+      ))
+    );
+  }
+
+  #[cfg(feature = "std")]
+  #[test]
+  #[allow(clippy::needless_borrow)]
+  fn audio_tags() {
+    let body_at = first_body_at(zelda);
+    let tag_start = body_at + 537 + 4;
+    println!(
+      "size of previous tag: {:?}",
+      be_u32::<_, ()>(&zelda[body_at + 537..tag_start])
+    );
+    assert_eq!(
+      tag_header(&zelda[tag_start..tag_start + 11]),
+      Ok((
+        &b""[..],
+        TagHeader {
+          tag_type: TagType::Audio,
+          data_size: 642,
+          timestamp: 0,
+          stream_id: 0,
+          filtered: false,
+        }
+      ))
+    );
+
+    let hq_body_at = first_body_at(zeldaHQ);
+    let tag_start2 = hq_body_at + 2984 + 4;
+    println!(
+      "size of previous tag: {:?}",
+      be_u32::<_, ()>(&zeldaHQ[hq_body_at + 2984..tag_start2])
+    );
+    println!(
+      "data:\n{}",
+      (&zeldaHQ[tag_start2..tag_start2 + 11]).to_hex(8)
+    );
+    assert_eq!(
+      tag_header(&zeldaHQ[tag_start2..tag_start2 + 11]),
+      Ok((
+        &b""[..],
+        TagHeader {
+          tag_type: TagType::Audio,
+          data_size: 642,
+          timestamp: 0,
+          stream_id: 0,
+          filtered: false,
+        }
+      ))
+    );
+
+    println!(
+      "data: {:?}",
+      audio_data(&zelda[tag_start + 11..tag_start + 11 + 642], 642)
+    );
+    println!(
+      "data: {:?}",
+      audio_data(&zeldaHQ[tag_start2 + 11..tag_start2 + 11 + 642], 642)
+    );
+    assert_eq!(
+      audio_data(&zelda[tag_start + 11..tag_start + 11 + 642], 642),
+      Ok((
+        &b""[..],
+        AudioData {
+          sound_format: SoundFormat::ADPCM,
+          sound_rate: SoundRate::_22KHZ,
+          sound_size: SoundSize::Snd16bit,
+          sound_type: SoundType::SndMono,
+          sound_data: &zelda[tag_start + 12..tag_start + 11 + 642]
+        }
+      ))
+    );
+
+    assert_eq!(
+      audio_data(&zeldaHQ[tag_start2 + 11..tag_start2 + 11 + 642], 642),
+      Ok((
+        &b""[..],
+        AudioData {
+          sound_format: SoundFormat::ADPCM,
+          sound_rate: SoundRate::_22KHZ,
+          sound_size: SoundSize::Snd16bit,
+          sound_type: SoundType::SndMono,
+          sound_data: &zeldaHQ[tag_start2 + 12..tag_start2 + 11 + 642]
+        }
+      ))
+    );
+  }
+
+  #[test]
+  fn video_tags() {
+    let tag_start = first_body_at(zelda);
+    assert_eq!(
+      video_data(&zelda[tag_start..tag_start + 537], 537),
+      Ok((
+        &b""[..],
+        VideoData {
+          frame_type: FrameType::Key,
+          codec_id: CodecId::SORENSON_H263,
+          video_data: &zelda[tag_start + 1..tag_start + 537]
+        }
+      ))
+    );
+    assert_eq!(
+      video_data(&zeldaHQ[tag_start..tag_start + 2984], 2984),
+      Ok((
+        &b""[..],
+        VideoData {
+          frame_type: FrameType::Key,
+          codec_id: CodecId::SORENSON_H263,
+          video_data: &zeldaHQ[tag_start + 1..tag_start + 2984]
+        }
+      ))
+    );
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn script_tags() {
+    let tag_start = first_body_at(commercials);
+    let tag_end = tag_start + 273;
+
+    match script_data(&commercials[tag_start..tag_end]) {
+      Ok((remaining, script_data)) => {
+        assert_eq!(remaining.len(), 0);
+        assert_eq!(
+          script_data,
+          ScriptData {
+            name: "onMetaData",
+            arguments: ScriptDataValue::ECMAArray {
+              count: 7,
+              entries: vec![
+                ScriptDataObject {
+                  name: "duration",
+                  data: ScriptDataValue::Number(28.133)
+                },
+                ScriptDataObject {
+                  name: "width",
+                  data: ScriptDataValue::Number(464.0)
+                },
+                ScriptDataObject {
+                  name: "height",
+                  data: ScriptDataValue::Number(348.0)
+                },
+                ScriptDataObject {
+                  name: "videodatarate",
+                  data: ScriptDataValue::Number(368.0)
+                },
+                ScriptDataObject {
+                  name: "framerate",
+                  data: ScriptDataValue::Number(30.0)
+                },
+                ScriptDataObject {
+                  name: "videocodecid",
+                  data: ScriptDataValue::Number(4.0)
+                },
+                ScriptDataObject {
+                  name: "audiodatarate",
+                  data: ScriptDataValue::Number(56.0)
+                },
+                ScriptDataObject {
+                  name: "audiodelay",
+                  data: ScriptDataValue::Number(0.0)
+                },
+                ScriptDataObject {
+                  name: "audiocodecid",
+                  data: ScriptDataValue::Number(2.0)
+                },
+                ScriptDataObject {
+                  name: "canSeekToEnd",
+                  data: ScriptDataValue::Number(1.0)
+                },
+                ScriptDataObject {
+                  name: "creationdate",
+                  data: ScriptDataValue::String("Thu Oct 04 18:37:42 2007\n")
+                }
+              ]
+            }
+          }
+        );
+      }
+      _ => unreachable!(),
+    }
+  }
+
+  #[test]
+  fn complete_video_tags() {
+    let tag_start = first_tag_at(zelda);
+    let tag_data_start = tag_start + 11;
+    assert_eq!(
+      complete_tag(&zelda[tag_start..tag_data_start + 537]),
+      Ok((
+        &b""[..],
+        Tag {
+          header: TagHeader {
+            tag_type: TagType::Video,
+            data_size: 537,
+            timestamp: 0,
+            stream_id: 0,
+            filtered: false,
+          },
+          data: TagData::Video(VideoData {
+            frame_type: FrameType::Key,
+            codec_id: CodecId::SORENSON_H263,
+            video_data: &zelda[tag_data_start + 1..tag_data_start + 537]
+          })
+        }
+      ))
+    );
+    assert_eq!(
+      complete_tag(&zeldaHQ[tag_start..tag_data_start + 2984]),
+      Ok((
+        &b""[..],
+        Tag {
+          header: TagHeader {
+            tag_type: TagType::Video,
+            data_size: 2984,
+            timestamp: 0,
+            stream_id: 0,
+            filtered: false,
+          },
+          data: TagData::Video(VideoData {
+            frame_type: FrameType::Key,
+            codec_id: CodecId::SORENSON_H263,
+            video_data: &zeldaHQ[tag_data_start + 1..tag_data_start + 2984]
+          })
+        }
+      ))
+    );
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn avc_config_record_multiple_parameter_sets() {
+    let record = [
+      0x01, 0x64, 0x00, 0x1f, 0xff,
+      0xe2, // version, profile, compat, level, length size, 2 SPS
+      0x00, 0x02, 0x67, 0x64, // SPS #1
+      0x00, 0x03, 0x67, 0x64, 0x01, // SPS #2
+      0x01, // 1 PPS
+      0x00, 0x02, 0x68, 0xee, // PPS
+    ];
+
+    assert_eq!(
+      avc_decoder_configuration_record(&record[..]),
+      Ok((
+        &b""[..],
+        AVCDecoderConfigurationRecord {
+          configuration_version: 1,
+          profile_indication: 0x64,
+          profile_compatibility: 0,
+          level_indication: 0x1f,
+          length_size_minus_one: 3,
+          sequence_parameter_sets: vec![&[0x67, 0x64][..], &[0x67, 0x64, 0x01][..]],
+          picture_parameter_sets: vec![&[0x68, 0xee][..]],
+        }
+      ))
+    );
+  }
+
+  #[test]
+  fn unknown_tag_type_error() {
+    assert_eq!(
+      tag_header(&[0x07, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+      Err(Err::Error(FlvError::UnknownTagType(7)))
+    );
+  }
+
+  #[test]
+  fn discriminant_errors() {
+    assert_eq!(
+      audio_data(&[0xc0, 0], 2),
+      Err(Err::Error(FlvError::UnknownSoundFormat(12)))
+    );
+    assert_eq!(
+      audio_data_header(&[0xd0]),
+      Err(Err::Error(FlvError::UnknownSoundFormat(13)))
+    );
+    assert_eq!(
+      video_data(&[0x02, 0], 2),
+      Err(Err::Error(FlvError::UnknownFrameType(0)))
+    );
+    assert_eq!(
+      video_data_header(&[0x62]),
+      Err(Err::Error(FlvError::UnknownFrameType(6)))
+    );
+    assert_eq!(
+      ex_video_tag_header(&[0xe0, b'h', b'v', b'c', b'1']),
+      Err(Err::Error(FlvError::UnknownFrameType(6)))
+    );
+
+    // codec ids that fit the nibble are kept, the HEVC parser rejects the
+    // reserved ones
+    let body = [0x1d, 0, 0, 0, 0];
+    assert_eq!(
+      video_data(&body, body.len()).unwrap().1.codec_id,
+      CodecId::Unknown(13)
+    );
+    assert_eq!(
+      hevc_video_packet(&body, body.len()),
+      Err(Err::Error(FlvError::UnknownCodecId(13)))
+    );
+    assert_eq!(
+      hevc_video_packet(&[0x17, 0, 0, 0, 0], 5),
+      Err(Err::Error(FlvError::UnexpectedCodecId(CodecId::H264)))
+    );
+  }
+
+  #[test]
+  fn raw_flags_and_nibbles() {
+    let body_at = first_body_at(zelda);
+    let audio_start = body_at + 537 + 4 + 11;
+    let (_, audio) = audio_data(&zelda[audio_start..audio_start + 642], 642).unwrap();
+    assert_eq!(audio.raw_flags(), zelda[audio_start]);
+
+    let (_, video) = video_data(&zelda[body_at..body_at + 537], 537).unwrap();
+    let flags = zelda[body_at];
+    assert_eq!(video.raw_nibbles(), Ok((flags >> 4, flags & 0x0f)));
+    assert_eq!(video.raw_nibbles(), Ok((1, 2)));
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn tag_sort_key() {
+    let header = |tag_type, timestamp| TagHeader {
+      tag_type,
+      data_size: 0,
+      timestamp,
+      stream_id: 0,
+      filtered: false,
+    };
+    let video = |video_data| {
+      TagData::Video(VideoData {
+        frame_type: FrameType::Key,
+        codec_id: CodecId::H264,
+        video_data,
+      })
+    };
+    let audio = TagData::Audio(AudioData {
+      sound_format: SoundFormat::AAC,
+      sound_rate: SoundRate::_44KHZ,
+      sound_size: SoundSize::Snd16bit,
+      sound_type: SoundType::SndStereo,
+      sound_data: &[1],
+    });
+
+    let mut tags = [
+      Tag {
+        header: header(TagType::Video, 40),
+        data: video(&[1, 0, 0, 0]),
+      },
+      Tag {
+        header: header(TagType::Audio, 0),
+        data: audio,
+      },
+      Tag {
+        header: header(TagType::Video, 0),
+        data: video(&[0, 0, 0, 0]),
+      },
+      Tag {
+        header: header(TagType::Script, 0),
+        data: TagData::Script(&[]),
+      },
+    ];
+    tags.sort_by_key(Tag::sort_key);
+
+    let keys: Vec<_> = tags.iter().map(Tag::sort_key).collect();
+    assert_eq!(keys, vec![(0, 0), (0, 1), (0, 2), (40, 2)]);
+    assert_eq!(tags[1].header.tag_type, TagType::Video);
+
+    // legacy HEVC sequence headers come before the media too
+    let hevc = |video_data| VideoData {
+      frame_type: FrameType::Key,
+      codec_id: CodecId::HEVC,
+      video_data,
+    };
+    assert!(hevc(&[0, 0, 0, 0]).is_sequence_header());
+    assert!(!hevc(&[1, 0, 0, 0]).is_sequence_header());
+    let tag = Tag {
+      header: header(TagType::Video, 0),
+      data: TagData::Video(hevc(&[0, 0, 0, 0])),
+    };
+    assert_eq!(tag.sort_key(), (0, 1));
+  }
+
+  #[test]
+  fn video_tag_without_payload() {
+    let (remaining, video) = video_data(&[0x52, 0xff][..], 1).unwrap();
+    assert_eq!(remaining, &[0xff][..]);
+    assert_eq!(video.frame_type, FrameType::Command);
+    assert!(video.is_empty_payload());
+
+    let body_at = first_body_at(zelda);
+    let (_, video) = video_data(&zelda[body_at..body_at + 537], 537).unwrap();
+    assert!(!video.is_empty_payload());
+  }
+
+  #[test]
+  fn audio_tag_without_payload() {
+    let (remaining, audio) = audio_data(&[0x2a, 0xff][..], 1).unwrap();
+    assert_eq!(remaining, &[0xff][..]);
+    assert_eq!(audio.sound_format, SoundFormat::MP3);
+    assert_eq!(audio.sound_data, &b""[..]);
+    assert!(audio.is_empty_payload());
+
+    let (_, audio) = audio_data(&[0x2a, 0xff][..], 2).unwrap();
+    assert!(!audio.is_empty_payload());
+
+    assert_eq!(
+      audio_data(&[0x2a][..], 0),
+      Err(Err::Incomplete(Needed::new(1)))
+    );
+  }
+
+  #[test]
+  fn tag_data_from() {
+    let body_at = first_body_at(zelda);
+    let (_, video) = video_data(&zelda[body_at..body_at + 537], 537).unwrap();
+    assert_eq!(TagData::from(video.clone()), TagData::Video(video));
+
+    let audio_start = body_at + 537 + 4 + 11;
+    let (_, audio) = audio_data(&zelda[audio_start..audio_start + 642], 642).unwrap();
+    assert_eq!(TagData::from(audio.clone()), TagData::Audio(audio));
+  }
+
+  #[test]
+  fn ex_header_detection() {
+    assert!(is_ex_video_header(0x90));
+    assert!(is_ex_video_header(0xff));
+    assert!(!is_ex_video_header(0x17));
+    assert!(!is_ex_video_header(0x7f));
+
+    assert!(is_ex_audio_header(0x90));
+    assert!(is_ex_audio_header(0x9f));
+    assert!(!is_ex_audio_header(0xaf));
+    assert!(!is_ex_audio_header(0x2f));
+
+    // the legacy parsers refuse enhanced headers, through the full and
+    // header-only paths alike
+    let body = [0x90, b'h', b'v', b'c', b'1'];
+    assert!(video_data(&body, body.len()).is_err());
+    assert!(video_data_header(&body).is_err());
+    let body = [0x91, b'O', b'p', b'u', b's'];
+    assert!(audio_data(&body, body.len()).is_err());
+    assert!(audio_data_header(&body).is_err());
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn strict_string_lengths() {
+    // an object whose second property has a string value declaring 16 bytes
+    let input = [
+      3, 0, 1, b'a', 0, 0x3f, 0xf0, 0, 0, 0, 0, 0, 0, 0, 1, b'b', 2, 0, 16, b'x', b'y', 0, 0, 9,
+    ];
+
+    assert_eq!(
+      script_data_value(&input),
+      Err(Err::Incomplete(Needed::new(11)))
+    );
+    assert_eq!(
+      Parser::with_options(ParserOptions {
+        strict_lengths: true,
+        ..ParserOptions::default()
+      })
+      .script_data_value(&input),
+      Err(Err::Error(FlvError::BadLength {
+        field: "string",
+        declared: 16,
+        available: 5,
+      }))
+    );
+  }
+
+  #[test]
+  fn same_stream_config() {
+    let (_, first) = complete_tag(&zelda[first_tag_at(zelda)..]).unwrap();
+    let (_, later) = complete_tag(&zelda[47201..]).unwrap();
+    assert!(first.same_stream_config(&later));
+    assert_ne!(first, later);
+
+    // Sorenson H263 then VP6
+    let (_, other) = complete_tag(&commercials[499..]).unwrap();
+    assert!(!first.same_stream_config(&other));
+
+    // audio is never the same stream as video
+    let (_, audio) = complete_tag(&commercials[301..]).unwrap();
+    assert!(!other.same_stream_config(&audio));
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn parse_progress() {
+    let mut reports = Vec::new();
+    let (rest, (_, tags)) =
+      parse_flv_with_progress(zelda, 400, |progress| reports.push(progress)).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(tags.len(), 998);
+    assert_eq!(reports.len(), 2);
+    assert_eq!(reports[0].tags, 400);
+    assert_eq!(reports[1].tags, 800);
+    assert!(reports[0].offset < reports[1].offset);
+    assert!(reports[1].offset < zelda.len());
+
+    let mut called = false;
+    parse_flv_with_progress(commercials, 0, |_| called = true).unwrap();
+    assert!(!called);
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn tag_data_accessors() {
+    let body_at = first_body_at(zelda);
+    let (_, video) = video_data(&zelda[body_at..body_at + 537], 537).unwrap();
+    let data = TagData::from(video.clone());
+    assert_eq!(data.as_video(), Some(&video));
+    assert_eq!(data.as_audio(), None);
+
+    assert_eq!(data.as_script(), None);
+
+    let script_at = first_body_at(commercials);
+    let body = &commercials[script_at..script_at + 273];
+    let data = TagData::Script(body);
+    assert_eq!(data.as_audio(), None);
+    assert_eq!(data.as_video(), None);
+    assert_eq!(data.as_script(), Some(body));
+  }
+
+  #[test]
+  fn track_ids() {
+    // video multitrack, one track of hvc1 coded frames with track id 2
+    let video = [0x96, 0x01, b'h', b'v', b'c', b'1', 2, 0, 0, 0];
+    assert_eq!(ex_track_id(TagType::Video, &video), Some(2));
+    // audio multitrack, many tracks many codecs, first track is Opus with id 1
+    let audio = [0x95, 0x21, b'O', b'p', b'u', b's', 1, 0, 0, 1, 0];
+    assert_eq!(ex_track_id(TagType::Audio, &audio), Some(1));
+
+    // coded frames without multitrack
+    assert_eq!(
+      ex_track_id(TagType::Video, &[0x91, b'h', b'v', b'c', b'1', 0, 0]),
+      None
+    );
+    assert_eq!(
+      ex_track_id(TagType::Audio, &[0x91, b'O', b'p', b'u', b's', 0, 0]),
+      None
+    );
+    let body_at = first_body_at(zelda);
+    assert_eq!(
+      ex_track_id(TagType::Video, &zelda[body_at..body_at + 537]),
+      None
+    );
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn enhanced_tags() {
+    let video = [0x96, 0x01, b'h', b'v', b'c', b'1', 2, 0, 0, 0];
+    let audio = [0x95, 0x21, b'O', b'p', b'u', b's', 1, 0, 0, 1, 0];
+    // enhanced tags are parsed with their raw body
+    let tag = |tag_type: u8, body: &[u8]| {
+      let mut tag = vec![tag_type, 0, 0, body.len() as u8, 0, 0, 0, 0, 0, 0, 0];
+      tag.extend_from_slice(body);
+      tag
+    };
+    let raw = tag(9, &video);
+    let (_, parsed) = complete_tag(&raw).unwrap();
+    assert_eq!(parsed.data, TagData::ExVideo(&video));
+    assert_eq!(parsed.track_id(), Some(2));
+    let raw = tag(8, &audio);
+    let (_, parsed) = complete_tag(&raw).unwrap();
+    assert_eq!(parsed.data, TagData::ExAudio(&audio));
+    assert_eq!(parsed.track_id(), Some(1));
+    let raw = tag(9, &[0x91, b'h', b'v', b'c', b'1', 0, 0]);
+    assert_eq!(complete_tag(&raw).unwrap().1.track_id(), None);
+    let (_, first) = complete_tag(&zelda[first_tag_at(zelda)..]).unwrap();
+    assert_eq!(first.track_id(), None);
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn bounded_parse() {
+    // zelda has a keyframe at 2000 ms, at offset 47201
+    let (rest, (_, tags)) = parse_until_time(zelda, 1999).unwrap();
+    assert_eq!(zelda.len() - rest.len(), 47201);
+    assert!(tags.iter().all(|tag| tag.header.timestamp <= 1999));
+    assert_eq!(tag_header(rest).unwrap().1.timestamp, 2000);
+
+    let (rest, (_, tags)) = parse_until_byte(zelda, 47201).unwrap();
+    assert_eq!(zelda.len() - rest.len(), 47201);
+    let (_, (_, shorter)) = parse_until_byte(zelda, 47200).unwrap();
+    assert_eq!(shorter.len(), tags.len() - 1);
+
+    let (rest, (_, tags)) = parse_until_time(zelda, u32::MAX).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(tags.len(), 998);
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn avc_frame_rate() {
+    let sps = [
+      0x67, 0x42, 0x00, 0x1e, 0xda, 0x05, 0x07, 0xe8, 0x40, 0x00, 0x00, 0x03, 0x00, 0x40, 0x00,
+      0x00, 0x0c, 0xa8,
+    ];
+    let mut record = AVCDecoderConfigurationRecord {
+      configuration_version: 1,
+      profile_indication: 0x42,
+      profile_compatibility: 0,
+      level_indication: 0x1e,
+      length_size_minus_one: 3,
+      sequence_parameter_sets: vec![&sps[..]],
+      picture_parameter_sets: vec![],
+    };
+    assert_eq!(record.frame_rate(), Some(25.0));
+
+    record.sequence_parameter_sets.clear();
+    assert_eq!(record.frame_rate(), None);
+  }
+
+  #[test]
+  fn unknown_codec_id() {
+    let body = [0x10, 1, 2, 3];
+    assert_eq!(
+      video_data(&body, body.len()),
+      Ok((
+        &b""[..],
+        VideoData {
+          frame_type: FrameType::Key,
+          codec_id: CodecId::Unknown(0),
+          video_data: &body[1..],
+        }
+      ))
+    );
+    assert_eq!(
+      video_data_header(&[0x2f]),
+      Ok((
+        &b""[..],
+        VideoDataHeader {
+          frame_type: FrameType::Inter,
+          codec_id: CodecId::Unknown(15),
+        }
+      ))
+    );
+
+    let (_, video) = video_data(&body, body.len()).unwrap();
+    assert_eq!(video.raw_nibbles(), Ok((1, 0)));
+  }
+
+  #[test]
+  fn audio_anomalies() {
+    let aac = AudioData {
+      sound_format: SoundFormat::AAC,
+      sound_rate: SoundRate::_44KHZ,
+      sound_size: SoundSize::Snd16bit,
+      sound_type: SoundType::SndStereo,
+      sound_data: &[0, 0x12, 0x10],
+    };
+    assert_eq!(aac.validate(), Ok(()));
+    assert_eq!(
+      AudioData {
+        sound_data: &[0, 0x2b, 0x92, 0x08, 0x00],
+        ..aac.clone()
+      }
+      .validate(),
+      Ok(())
+    );
+    assert_eq!(
+      AudioData {
+        sound_data: &[0, 0x13, 0x10, 0x56, 0xe5, 0x98],
+        ..aac.clone()
+      }
+      .validate(),
+      Ok(())
+    );
+
+    // PCM labeled as AAC
+    assert_eq!(
+      AudioData {
+        sound_rate: SoundRate::_22KHZ,
+        sound_type: SoundType::SndMono,
+        ..aac.clone()
+      }
+      .validate(),
+      Err(AudioAnomaly::AacSoundRate(SoundRate::_22KHZ))
+    );
+    assert_eq!(
+      AudioData {
+        sound_type: SoundType::SndMono,
+        ..aac.clone()
+      }
+      .validate(),
+      Err(AudioAnomaly::AacSoundType(SoundType::SndMono))
+    );
+    assert_eq!(
+      AudioData {
+        sound_data: &[],
+        ..aac.clone()
+      }
+      .validate(),
+      Err(AudioAnomaly::MissingAacPacketType)
+    );
+    assert_eq!(
+      AudioData {
+        sound_data: &[0, 0x12],
+        ..aac.clone()
+      }
+      .validate(),
+      Err(AudioAnomaly::AacSequenceHeaderTooShort)
+    );
+    assert_eq!(
+      AudioData {
+        sound_data: &[0, 0x12, 0x10, 0xff],
+        ..aac
+      }
+      .validate(),
+      Err(AudioAnomaly::AacSequenceHeaderTrailingData)
+    );
+
+    // other formats are not checked
+    let audio_start = first_body_at(zelda) + 537 + 4 + 11;
+    let (_, adpcm) = audio_data(&zelda[audio_start..audio_start + 642], 642).unwrap();
+    assert_eq!(adpcm.validate(), Ok(()));
+  }
+
+  #[test]
+  fn tag_type_conversions() {
+    for tag_type in [TagType::Audio, TagType::Video, TagType::Script] {
+      assert_eq!(TagType::try_from(u8::from(tag_type)), Ok(tag_type));
+    }
+    assert_eq!(u8::from(TagType::Script), 18);
+    assert_eq!(TagType::try_from(7), Err(FlvError::UnknownTagType(7)));
+  }
+
+  #[test]
+  fn sound_format_conversions() {
+    for value in (0..16).filter(|v| ![9, 12, 13].contains(v)) {
+      let sound_format = SoundFormat::try_from(value).unwrap();
+      assert_eq!(u8::from(sound_format), value);
+    }
+    assert_eq!(SoundFormat::try_from(10), Ok(SoundFormat::AAC));
+    for value in [9, 12, 13, 16, 255] {
+      assert_eq!(
+        SoundFormat::try_from(value),
+        Err(FlvError::UnknownSoundFormat(value))
+      );
+    }
+  }
+
+  #[test]
+  fn codec_id_conversions() {
+    let table = [
+      (1, CodecId::JPEG),
+      (2, CodecId::SORENSON_H263),
+      (3, CodecId::SCREEN),
+      (4, CodecId::VP6),
+      (5, CodecId::VP6A),
+      (6, CodecId::SCREEN2),
+      (7, CodecId::H264),
+      (8, CodecId::H263),
+      (9, CodecId::MPEG4Part2),
+    ];
+    for (value, codec_id) in table {
+      assert_eq!(CodecId::try_from(value), Ok(codec_id));
+      assert_eq!(u8::try_from(codec_id), Ok(value));
+    }
+    for value in [0, 10, 15] {
+      assert_eq!(CodecId::try_from(value), Ok(CodecId::Unknown(value)));
+      assert_eq!(u8::try_from(CodecId::Unknown(value)), Ok(value));
+    }
+    assert_eq!(
+      u8::try_from(CodecId::AV1),
+      Err(FlvError::NoCodecId(CodecId::AV1))
+    );
+    assert_eq!(CodecId::try_from(16), Err(FlvError::UnknownCodecId(16)));
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn tag_iterator() {
+    assert_eq!(tags(zelda).count(), 998);
+    assert!(tags(zelda).all(|tag| tag.is_ok()));
+    assert_eq!(tags(zeldaHQ).count(), 998);
+    assert_eq!(tags(commercials).count(), 1923);
+
+    // a truncated last tag is reported once
+    let truncated: Vec<_> = tags(&zelda[..1000]).collect();
+    assert!(truncated[..truncated.len() - 1]
+      .iter()
+      .all(|tag| tag.is_ok()));
+    assert!(matches!(
+      truncated.last(),
+      Some(Err(FlvError::Incomplete(_)))
+    ));
+
+    let mut invalid = tags(b"RIFF\x01\x05\x00\x00\x00\x09");
+    assert!(matches!(invalid.next(), Some(Err(FlvError::Nom(_)))));
+    assert!(invalid.next().is_none());
+  }
+
+  #[cfg(feature = "debug")]
+  #[test]
+  fn tag_iterator_context() {
+    // the second tag gets an unknown type
+    let mut input = zelda.to_vec();
+    input[565] = 3;
+    assert_eq!(tags(&input).nth(1), Some(Err(FlvError::UnknownTagType(3))));
+    let parser = Parser::with_options(ParserOptions {
+      error_context: true,
+      ..ParserOptions::default()
+    });
+    let error = parser.tags(&input).nth(1).unwrap().unwrap_err();
+    match &error {
+      FlvError::Context {
+        offset, snippet, ..
+      } => {
+        assert_eq!(*offset, 565);
+        assert_eq!(&snippet[..], &input[557..581]);
+      }
+      _ => panic!("expected a context: {:?}", error),
+    }
+    assert_eq!(error.root(), &FlvError::UnknownTagType(3));
+
+    let truncated: Vec<_> = parser.tags(&zelda[..1000]).collect();
+    assert!(matches!(
+      truncated.last(),
+      Some(Err(FlvError::Incomplete(_)))
+    ));
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn tags_with_sizes() {
+    let tag_at = first_tag_at(zelda);
+    assert_eq!(
+      previous_tag_size(&zelda[tag_at - 4..tag_at]),
+      Ok((&b""[..], 0))
+    );
+
+    let (rest, (tag, size)) = tag_with_size(&zelda[tag_at..]).unwrap();
+    assert_eq!(size, PreviousTagSize(11 + 537));
+    assert!(size.matches(&tag.header));
+    assert_eq!(rest, &zelda[tag_at + 11 + 537 + 4..]);
+
+    // the script tag body is parsed up to the size
+    let (rest, (tag, size)) = tag_with_size(&commercials[first_tag_at(commercials)..]).unwrap();
+    let (_, records) = script_tag_records(tag.data.as_script().unwrap()).unwrap();
+    assert_eq!(records[0].name, "onMetaData");
+    assert!(size.matches(&tag.header));
+    assert_eq!(rest, &commercials[301..]);
+
+    assert!(!PreviousTagSize(0).matches(&tag.header));
+    assert_eq!(
+      tag_with_size(&zelda[tag_at..tag_at + 11 + 537]),
+      Err(Err::Incomplete(Needed::new(4)))
+    );
+  }
+
+  #[test]
+  fn hevc_packets() {
+    // legacy codec id 12, NALU with a composition time of 40
+    let legacy = [0x1c, 1, 0, 0, 40, 0xaa, 0xbb];
+    assert_eq!(
+      hevc_video_packet(&legacy, legacy.len()),
+      Ok((
+        &b""[..],
+        HEVCVideoPacket {
+          signaling: CodecSignaling::CodecId,
+          frame_type: FrameType::Key,
+          packet_type: HEVCPacketType::NALU,
+          composition_time: 40,
+          hevc_data: &[0xaa, 0xbb],
+        }
+      ))
+    );
+    assert_eq!(
+      video_data(&legacy, legacy.len()).unwrap().1.codec_id,
+      CodecId::HEVC
+    );
+
+    // enhanced sequence start, coded frames and coded frames without
+    // composition time
+    let start = [0x90, b'h', b'v', b'c', b'1', 1, 2, 3];
+    let (_, packet) = hevc_video_packet(&start, start.len()).unwrap();
+    assert_eq!(packet.signaling, CodecSignaling::FourCc);
+    assert_eq!(packet.packet_type, HEVCPacketType::SequenceHeader);
+    assert_eq!(packet.hevc_data, &[1, 2, 3]);
+
+    let coded = [0xa1, b'h', b'v', b'c', b'1', 0xff, 0xff, 0xfe, 0xcc];
+    let (_, packet) = hevc_video_packet(&coded, coded.len()).unwrap();
+    assert_eq!(packet.frame_type, FrameType::Inter);
+    assert_eq!(packet.packet_type, HEVCPacketType::NALU);
+    assert_eq!(packet.composition_time, -2);
+    assert_eq!(packet.hevc_data, &[0xcc]);
+
+    let coded_x = [0x93, b'h', b'v', b'c', b'1', 0xcc];
+    let (_, packet) = hevc_video_packet(&coded_x, coded_x.len()).unwrap();
+    assert_eq!(packet.composition_time, 0);
+    assert_eq!(packet.hevc_data, &[0xcc]);
+
+    let end = [0x92, b'h', b'v', b'c', b'1'];
+    let (_, packet) = hevc_video_packet(&end, end.len()).unwrap();
+    assert_eq!(packet.packet_type, HEVCPacketType::EndOfSequence);
+
+    // AVC and other FourCCs are rejected
+    let avc = [0x17, 1, 0, 0, 0];
+    assert!(hevc_video_packet(&avc, avc.len()).is_err());
+    let av1 = [0x91, b'a', b'v', b'0', b'1', 0, 0, 0];
+    assert!(hevc_video_packet(&av1, av1.len()).is_err());
+    let reserved = [0x1c, 3, 0, 0, 0];
+    assert_eq!(
+      hevc_video_packet(&reserved, reserved.len()),
+      Err(Err::Error(FlvError::UnknownPacketType(3)))
+    );
+  }
+
+  #[test]
+  fn ex_video_headers() {
+    let body = [0x91, b'a', b'v', b'0', b'1', 0xaa];
+    assert_eq!(
+      ex_video_tag_header(&body),
+      Ok((
+        &[0xaa][..],
+        ExVideoTagHeader {
+          frame_type: FrameType::Key,
+          packet_type: VideoPacketType::CodedFrames,
+          fourcc: *b"av01",
+        }
+      ))
+    );
+    assert_eq!(
+      ex_video_tag_header(&[0xd4, b'v', b'p', b'0', b'9'])
+        .unwrap()
+        .1,
+      ExVideoTagHeader {
+        frame_type: FrameType::Command,
+        packet_type: VideoPacketType::Metadata,
+        fourcc: *b"vp09",
+      }
+    );
+
+    // legacy tags only go through video_data_header, and the other way round
+    let body_at = first_body_at(zelda);
+    assert!(ex_video_tag_header(&zelda[body_at..body_at + 537]).is_err());
+    assert!(video_data_header(&zelda[body_at..body_at + 537]).is_ok());
+    assert!(video_data_header(&body).is_err());
+
+    // truncated FourCC, multitrack
+    assert_eq!(
+      ex_video_tag_header(&[0x91, b'a', b'v']),
+      Err(Err::Incomplete(Needed::new(2)))
+    );
+    assert!(ex_video_tag_header(&[0x96, 0x00, b'a', b'v', b'0', b'1']).is_err());
+  }
+
+  #[test]
+  fn av1_packets() {
+    // av1C marker and version, profile and level, flags, no presentation
+    // delay, then a sequence header OBU
+    let start = [
+      0x90, b'a', b'v', b'0', b'1', 0x81, 0x08, 0x0c, 0x00, 0x0a, 0x0b,
+    ];
+    let (rest, packet) = av1_video_packet(&start, start.len()).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(packet.frame_type, FrameType::Key);
+    assert_eq!(packet.packet_type, VideoPacketType::SequenceStart);
+    assert_eq!(packet.config_obus(), Some(&[0x0a, 0x0b][..]));
+
+    let coded = [0xa1, b'a', b'v', b'0', b'1', 0x32, 0x00, 0xff];
+    assert_eq!(
+      av1_video_packet(&coded, coded.len() - 1),
+      Ok((
+        &[0xff][..],
+        Av1VideoPacket {
+          frame_type: FrameType::Inter,
+          packet_type: VideoPacketType::CodedFrames,
+          av1_data: &[0x32, 0x00],
+        }
+      ))
+    );
+    assert_eq!(
+      av1_video_packet(&coded, coded.len())
+        .unwrap()
+        .1
+        .config_obus(),
+      None
+    );
+
+    let hevc = [0x91, b'h', b'v', b'c', b'1', 0, 0, 0];
+    assert!(av1_video_packet(&hevc, hevc.len()).is_err());
+  }
+
+  #[test]
+  fn vp9_packets() {
+    // vpcC version and flags, then profile, level, bit depth and color
+    let start = [0x90, b'v', b'p', b'0', b'9', 1, 0, 0, 0, 0, 31, 0x80];
+    let (rest, packet) = vp9_video_packet(&start, start.len()).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(packet.frame_type, FrameType::Key);
+    assert_eq!(packet.packet_type, VideoPacketType::SequenceStart);
+    assert_eq!(packet.codec_configuration(), Some(&start[5..]));
+    assert_eq!(packet.frame(), None);
+
+    let coded = [0xa1, b'v', b'p', b'0', b'9', 0x86, 0x00, 0x40];
+    let (_, packet) = vp9_video_packet(&coded, coded.len()).unwrap();
+    assert_eq!(packet.frame_type, FrameType::Inter);
+    assert_eq!(packet.packet_type, VideoPacketType::CodedFrames);
+    assert_eq!(packet.frame(), Some(&coded[5..]));
+    assert_eq!(packet.codec_configuration(), None);
+
+    let (_, header) = ex_video_tag_header(&coded).unwrap();
+    assert_eq!(header.codec_id(), Some(CodecId::VP9));
+    assert_eq!(CodecId::VP9.fourcc(), Some(*b"vp09"));
+    assert_eq!(CodecId::from_fourcc(b"hvc1"), Some(CodecId::HEVC));
+    assert_eq!(CodecId::from_fourcc(b"xxxx"), None);
+    assert_eq!(CodecId::VP6.fourcc(), None);
+
+    let av1 = [0xa1, b'a', b'v', b'0', b'1', 0x32, 0x00];
+    assert!(vp9_video_packet(&av1, av1.len()).is_err());
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn opus_packets() {
+    let mut start = vec![0x90, b'O', b'p', b'u', b's'];
+    start.extend_from_slice(b"OpusHead\x01\x02\x38\x01\x80\xbb\0\0\0\0\0");
+    assert_eq!(
+      ex_audio_tag_header(&start),
+      Ok((
+        &start[5..],
+        ExAudioTagHeader {
+          packet_type: AudioPacketType::SequenceStart,
+          fourcc: *b"Opus",
+        }
+      ))
+    );
+    let (rest, packet) = opus_audio_packet(&start, start.len()).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(packet.packet_type, AudioPacketType::SequenceStart);
+    assert_eq!(packet.opus_data, &start[5..]);
+
+    let coded = [0x91, b'O', b'p', b'u', b's', 0xfc, 0xff, 0xfe, 0x00];
+    assert_eq!(
+      opus_audio_packet(&coded, coded.len() - 1),
+      Ok((
+        &[0x00][..],
+        OpusAudioPacket {
+          packet_type: AudioPacketType::CodedFrames,
+          opus_data: &[0xfc, 0xff, 0xfe],
+        }
+      ))
+    );
+
+    let flac = [0x91, b'f', b'L', b'a', b'C', 0xff];
+    assert_eq!(
+      opus_audio_packet(&flac, flac.len()),
+      Err(Err::Error(FlvError::FourCcMismatch {
+        expected: *b"Opus",
+        found: *b"fLaC",
+      }))
+    );
+    // multichannel config, and an unknown packet type
+    let config = [0x94, b'O', b'p', b'u', b's', 0];
+    assert_eq!(
+      opus_audio_packet(&config, config.len()),
+      Err(Err::Error(FlvError::UnexpectedPacketType(4)))
+    );
+    assert_eq!(
+      ex_audio_tag_header(&[0x9c, b'O', b'p', b'u', b's']),
+      Err(Err::Error(FlvError::UnknownPacketType(12)))
+    );
+    // multitrack
+    assert_eq!(
+      ex_audio_tag_header(&[0x95, 0x01, b'O', b'p', b'u', b's', 0]),
+      Err(Err::Error(FlvError::UnexpectedPacketType(5)))
+    );
+
+    // legacy AAC is left to audio_data_header
+    let aac = [0xaf, 0x01, 0x21];
+    assert!(ex_audio_tag_header(&aac).is_err());
+    assert_eq!(
+      audio_data_header(&aac).unwrap().1.sound_format,
+      SoundFormat::AAC
+    );
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn flac_and_ac3_packets() {
+    let mut start = vec![0x90, b'f', b'L', b'a', b'C', 0, 0, 0, 0, 0x80, 0, 0, 34];
+    let stream_info: Vec<u8> = (0..34).collect();
+    start.extend_from_slice(&stream_info);
+    let (rest, packet) = flac_audio_packet(&start, start.len()).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(packet.packet_type, AudioPacketType::SequenceStart);
+    assert_eq!(packet.flac_data, &start[5..]);
+    assert_eq!(packet.stream_info(), Some(&stream_info[..]));
+    // truncated STREAMINFO
+    let (_, packet) = flac_audio_packet(&start, start.len() - 1).unwrap();
+    assert_eq!(packet.stream_info(), None);
+
+    let coded = [0x91, b'f', b'L', b'a', b'C', 0xff, 0xf8, 0x69];
+    let (_, packet) = flac_audio_packet(&coded, coded.len()).unwrap();
+    assert_eq!(packet.packet_type, AudioPacketType::CodedFrames);
+    assert_eq!(packet.flac_data, &coded[5..]);
+    assert_eq!(packet.stream_info(), None);
+
+    let ac3 = [0x91, b'a', b'c', b'-', b'3', 0x0b, 0x77, 0x00];
+    assert_eq!(
+      ac3_audio_packet(&ac3, ac3.len() - 1),
+      Ok((
+        &[0x00][..],
+        Ac3AudioPacket {
+          packet_type: AudioPacketType::CodedFrames,
+          ac3_data: &[0x0b, 0x77],
+        }
+      ))
+    );
+    assert!(ac3_audio_packet(&coded, coded.len()).is_err());
+    assert!(flac_audio_packet(&ac3, ac3.len()).is_err());
+
+    let (_, header) = ex_audio_tag_header(&ac3).unwrap();
+    assert_eq!(header.sound_format(), Some(SoundFormat::AC3));
+    assert_eq!(SoundFormat::from_fourcc(b"fLaC"), Some(SoundFormat::FLAC));
+    assert_eq!(SoundFormat::from_fourcc(b"mp4a"), Some(SoundFormat::AAC));
+    assert_eq!(SoundFormat::from_fourcc(b"xxxx"), None);
+    assert_eq!(SoundFormat::OPUS.fourcc(), Some(*b"Opus"));
+    assert_eq!(SoundFormat::ADPCM.fourcc(), None);
+    assert_eq!(u8::from(SoundFormat::FLAC), 9);
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn amf3_on_metadata() {
+    let mut body = b"\x02\x00\x0aonMetaData\x11\x09\x01".to_vec();
+    body.extend_from_slice(b"\x11duration\x05");
+    body.extend_from_slice(&10.5f64.to_be_bytes());
+    body.extend_from_slice(b"\x0bwidth\x04\x85\x00\x0fencoder\x06\x09Lavf\x01");
+
+    let (rest, parsed) = script_data(&body).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(parsed.name, "onMetaData");
+    let property = |name, value| AMF3Property { name, value };
+    assert_eq!(
+      parsed.arguments,
+      ScriptDataValue::AMF3(AMF3Value::Array {
+        associative: vec![
+          property("duration", AMF3Value::Double(10.5)),
+          property("width", AMF3Value::Integer(640)),
+          property("encoder", AMF3Value::String("Lavf")),
+        ],
+        dense: vec![],
+      })
+    );
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn typed_object() {
+    let input = b"\x10\x00\x05Point\x00\x01x\x00\x3f\xf0\0\0\0\0\0\0\x00\x01y\x05\x00\x00\x09";
+    assert_eq!(
+      script_data_value(input),
+      Ok((
+        &b""[..],
+        ScriptDataValue::TypedObject {
+          class_name: "Point",
+          properties: vec![
+            ScriptDataObject {
+              name: "x",
+              data: ScriptDataValue::Number(1.0),
+            },
+            ScriptDataObject {
+              name: "y",
+              data: ScriptDataValue::Null,
+            },
+          ],
+        }
+      ))
+    );
+    // missing object end marker
+    assert!(script_data_value(&input[..input.len() - 3]).is_err());
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn speex_packets() {
+    fn pack(fields: &[(u32, usize)]) -> Vec<u8> {
+      let mut bits = Vec::new();
+      for &(value, len) in fields {
+        bits.extend(
+          (0..len)
+            .rev()
+            .map(|i| value.checked_shr(i as u32).unwrap_or(0) & 1 == 1),
+        );
+      }
+      while bits.len() % 8 != 0 {
+        bits.push(true);
+      }
+      bits
+        .chunks(8)
+        .map(|byte| byte.iter().fold(0, |acc, &bit| (acc << 1) | u8::from(bit)))
+        .collect()
+    }
+
+    let mut body = vec![0xb2];
+    body.extend(pack(&[
+      // narrowband mode 8 and wideband mode 1
+      (0, 1),
+      (8, 4),
+      (0, 74),
+      (1, 1),
+      (1, 3),
+      (0, 32),
+      // narrowband mode 0 alone
+      (0, 1),
+      (0, 4),
+      // terminator
+      (0, 1),
+      (15, 4),
+    ]));
+    let (rest, packet) = speex_audio_packet(&body, body.len()).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(packet.speex_data, &body[1..]);
+    assert_eq!(
+      packet.frames().collect::<Vec<_>>(),
+      vec![
+        SpeexFrame {
+          bit_offset: 0,
+          bit_len: 115,
+          narrowband_mode: 8,
+          wideband_mode: Some(1),
+        },
+        SpeexFrame {
+          bit_offset: 115,
+          bit_len: 5,
+          narrowband_mode: 0,
+          wideband_mode: None,
+        },
+      ]
+    );
+
+    // truncated wideband layer
+    let truncated = SpeexAudioPacket {
+      speex_data: &body[1..12],
+    };
+    assert_eq!(truncated.frames().count(), 0);
+
+    // 11 kHz
+    body[0] = 0xb6;
+    assert_eq!(
+      speex_audio_packet(&body, body.len()),
+      Err(Err::Error(FlvError::UnexpectedSoundFlags(
+        SoundFormat::SPEEX
+      )))
+    );
+    body[0] = 0x12;
+    assert_eq!(
+      speex_audio_packet(&body, body.len()),
+      Err(Err::Error(FlvError::UnexpectedSoundFormat(
+        SoundFormat::ADPCM
+      )))
+    );
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn nellymoser_packets() {
+    let mut body = vec![0x6e];
+    body.extend_from_slice(&[0x55; 128]);
+    let (rest, packet) = nellymoser_audio_packet(&body, body.len()).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(packet.blocks().count(), 2);
+    assert!(packet.blocks().all(|block| block.len() == 64));
+    assert_eq!(packet.samples(), 512);
+    assert_eq!(packet.sample_rate(), 44100);
+
+    body[0] = 0x52;
+    let (_, packet) = nellymoser_audio_packet(&body, 65).unwrap();
+    assert_eq!(packet.sound_format, SoundFormat::NELLYMOSER_8KHZ_MONO);
+    assert_eq!(packet.sample_rate(), 8000);
+    assert_eq!(packet.samples(), 256);
+
+    assert_eq!(
+      nellymoser_audio_packet(&body, 66),
+      Err(Err::Error(FlvError::PartialBlock {
+        len: 65,
+        block_size: 64
+      }))
+    );
+    assert_eq!(
+      nellymoser_audio_packet(&body, 1),
+      Err(Err::Error(FlvError::PartialBlock {
+        len: 0,
+        block_size: 64
+      }))
+    );
+    body[0] = 0x12;
+    assert_eq!(
+      nellymoser_audio_packet(&body, 65),
+      Err(Err::Error(FlvError::UnexpectedSoundFormat(
+        SoundFormat::ADPCM
+      )))
+    );
+  }
+
+  #[test]
+  fn aac_audio_specific_config() {
+    // AAC LC, 44.1 kHz, stereo, then the GASpecificConfig bits
+    let (rest, config) = audio_specific_config(&[0x12, 0x10]).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(
+      config,
+      AudioSpecificConfig {
+        object_type: 2,
+        sampling_frequency_index: 4,
+        sampling_frequency: None,
+        channel_configuration: 2,
+      }
+    );
+    assert_eq!(config.sample_rate(), Some(44100));
+    assert_eq!(config.channels(), Some(2));
+
+    // escaped object type 42 and explicit 48 kHz, mono
+    let (_, config) = audio_specific_config(&[0xf9, 0x5e, 0x01, 0x77, 0x00, 0x20]).unwrap();
+    assert_eq!(config.object_type, 42);
+    assert_eq!(config.sampling_frequency_index, 15);
+    assert_eq!(config.sample_rate(), Some(48000));
+    assert_eq!(config.channels(), Some(1));
+
+    // 7.1
+    let (_, config) = audio_specific_config(&[0x11, 0xb8]).unwrap();
+    assert_eq!(config.sample_rate(), Some(48000));
+    assert_eq!(config.channels(), Some(8));
+
+    assert!(matches!(
+      audio_specific_config(&[0x12]),
+      Err(Err::Incomplete(_))
+    ));
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn nal_units() {
+    let data = [0, 0, 0, 2, 0x09, 0xf0, 0, 0, 0, 3, 0x65, 0x88, 0x84];
+    assert_eq!(
+      avc_nal_units(&data, 4),
+      Ok((&b""[..], vec![&data[4..6], &data[10..13]]))
+    );
+    assert_eq!(
+      avc_nal_units(&[0, 1, 0x09, 0, 2, 0x65, 0x88], 2),
+      Ok((&b""[..], vec![&[0x09][..], &[0x65, 0x88][..]]))
+    );
+    assert_eq!(avc_nal_units(&[], 4), Ok((&b""[..], vec![])));
+
+    assert_eq!(
+      avc_nal_units(&data[..12], 4),
+      Err(Err::Incomplete(Needed::new(1)))
+    );
+    assert_eq!(
+      avc_nal_units(&data[..8], 4),
+      Err(Err::Incomplete(Needed::new(2)))
+    );
+    assert_eq!(
+      avc_nal_units(&data, 0),
+      Err(Err::Error(FlvError::Nom(ErrorKind::Verify)))
+    );
+  }
+
+  #[test]
+  fn complete_tag_size_mismatch() {
+    let tag_at = first_tag_at(zelda);
+    let tag = &zelda[tag_at..tag_at + 11 + 537];
+    let checked = Parser::with_options(ParserOptions {
+      check_data_size: true,
+      ..ParserOptions::default()
+    });
+    assert_eq!(checked.complete_tag(tag), complete_tag(tag));
+    assert_eq!(
+      checked.complete_tag(&tag[..11 + 100]),
+      Err(Err::Error(FlvError::SizeMismatch {
+        declared: 537,
+        available: 100
+      }))
+    );
+    // the header itself is still incomplete
+    assert_eq!(
+      checked.complete_tag(&tag[..5]),
+      Err(Err::Incomplete(Needed::new(2)))
+    );
+  }
+
+  #[test]
+  fn parser_size_mismatch() {
+    let first = first_tag_at(zelda);
+    let cut = &zelda[..first + 11 + 100];
+    let mismatch = FlvError::SizeMismatch {
+      declared: 537,
+      available: 100,
+    };
+    let checked = Parser::with_options(ParserOptions {
+      check_data_size: true,
+      ..ParserOptions::default()
+    });
+    assert_eq!(
+      checked.tag_with_size(&cut[first..]),
+      Err(Err::Error(mismatch.clone()))
+    );
+    assert_eq!(checked.tags(cut).next(), Some(Err(mismatch.clone())));
+    // the video tag is skipped, but its size is still checked
+    assert_eq!(
+      checked.tags_of_type(cut, TagType::Audio).next(),
+      Some(Err(mismatch.clone()))
+    );
+    assert_eq!(
+      checked.keyframe_tags(cut).next(),
+      Some(Err(mismatch.clone()))
+    );
+    #[cfg(feature = "alloc")]
+    assert_eq!(checked.parse_flv(cut), Err(Err::Error(mismatch)));
+
+    let truncated = Parser::with_options(ParserOptions {
+      check_data_size: true,
+      allow_truncated_tag: true,
+      ..ParserOptions::default()
+    });
+    assert_eq!(truncated.tags(cut).next(), None);
+    #[cfg(feature = "alloc")]
+    assert_eq!(
+      truncated
+        .parse_flv(cut)
+        .map(|(i, (_, tags))| (i.len(), tags.len())),
+      Ok((111, 0))
+    );
+  }
+
+  #[test]
+  fn short_input_needed() {
+    // a 537-byte video tag body with only 500 bytes available
+    let body_at = first_body_at(zelda);
+    let video = &zelda[body_at..body_at + 500];
+    assert_eq!(
+      video_data(video, 537),
+      Err(Err::Incomplete(Needed::new(37)))
+    );
+    assert_eq!(
+      audio_data(&[0xaf, 0x01], 5),
+      Err(Err::Incomplete(Needed::new(3)))
+    );
+    assert_eq!(
+      aac_audio_packet(&[0x01], 4),
+      Err(Err::Incomplete(Needed::new(3)))
+    );
+    assert_eq!(
+      avc_video_packet(&[0x01, 0, 0], 10),
+      Err(Err::Incomplete(Needed::new(7)))
+    );
+    assert_eq!(
+      hevc_video_packet(&[0x90, b'h', b'v'], 8),
+      Err(Err::Incomplete(Needed::new(5)))
+    );
+    assert_eq!(
+      opus_audio_packet(&[0x90], 6),
+      Err(Err::Incomplete(Needed::new(5)))
+    );
+  }
+
+  #[test]
+  fn tag_header_with_data_headers() {
+    let tag_at = first_tag_at(zelda);
+    let (rest, (header, data_header)) = tag_header_with_data_header(&zelda[tag_at..]).unwrap();
+    assert_eq!(header.data_size, 537);
+    assert_eq!(rest, &zelda[tag_at + 11 + 537..]);
+    assert_eq!(
+      data_header,
+      TagDataHeader::Video(VideoDataHeader {
+        frame_type: FrameType::Key,
+        codec_id: CodecId::SORENSON_H263,
+      })
+    );
+
+    // skips every tag of the file like complete_tag
+    let mut input = &zelda[tag_at..];
+    let mut count = 0;
+    while !input.is_empty() {
+      let (rest, (header, data_header)) = tag_header_with_data_header(input).unwrap();
+      if let TagDataHeader::Audio(audio) = data_header {
+        assert_eq!(header.tag_type, TagType::Audio);
+        assert_eq!(audio.sound_format, SoundFormat::ADPCM);
+      }
+      input = &rest[4..];
+      count += 1;
+    }
+    assert_eq!(count, 998);
+
+    assert_eq!(
+      tag_header_with_data_header(&zelda[tag_at..tag_at + 11 + 500]),
+      Err(Err::Incomplete(Needed::new(37)))
+    );
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn timestamps() {
+    let timestamp = Timestamp::from_parts(0x02_0304, 0x01);
+    assert_eq!(timestamp.as_millis(), 0x0102_0304);
+    assert_eq!(timestamp.base(), 0x02_0304);
+    assert_eq!(timestamp.extended(), 0x01);
+    assert_eq!(Timestamp::from_parts(0xff_ffff, 0xff), Timestamp(u32::MAX));
+
+    let mut wrapping = WrappingTimestamps::new();
+    let pushed: Vec<_> = [u32::MAX - 40, u32::MAX - 20, 0, u32::MAX - 10, 5, 3]
+      .iter()
+      .map(|&t| wrapping.push(Timestamp(t)))
+      .collect();
+    let max = u64::from(u32::MAX);
+    assert_eq!(
+      pushed,
+      vec![max - 40, max - 20, max + 1, max - 10, max + 6, max + 4]
+    );
+
+    let mut wrapping = WrappingTimestamps::without_extension();
+    assert_eq!(wrapping.push(Timestamp(0xff_fff0)), 0xff_fff0);
+    assert_eq!(wrapping.push(Timestamp(0x10)), 0x100_0010);
+    assert_eq!(wrapping.push(Timestamp(0xff_fff0)), 0xff_fff0);
+    assert_eq!(wrapping.push(Timestamp(0x0100_0020)), 0x100_0020);
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn sound_rate_hz() {
+    let rates = [
+      (SoundRate::_5_5KHZ, 5512),
+      (SoundRate::_11KHZ, 11025),
+      (SoundRate::_22KHZ, 22050),
+      (SoundRate::_44KHZ, 44100),
+    ];
+    for (rate, hz) in rates {
+      assert_eq!(rate.as_hz(), hz);
+    }
+    assert_eq!(SoundRate::_22KHZ.to_string(), "22050 Hz");
+  }
+
+  #[test]
+  fn strict_reserved_bits() {
+    let strict = ParserOptions {
+      strict_reserved: true,
+      ..ParserOptions::default()
+    };
+    assert_eq!(
+      Parser::with_options(strict).header(&zelda[..9]),
+      header(&zelda[..9])
+    );
+    let tag_at = first_tag_at(zelda);
+    assert_eq!(
+      Parser::with_options(strict).tag_header(&zelda[tag_at..]),
+      tag_header(&zelda[tag_at..])
+    );
+
+    let mut file_header = zelda[..9].to_vec();
+    file_header[4] |= 0x10;
+    assert!(header(&file_header).is_ok());
+    assert_eq!(
+      Parser::with_options(strict).header(&file_header),
+      Err(Err::Error(FlvError::ReservedBitsSet("TypeFlagsReserved")))
+    );
+
+    let mut tag = zelda[tag_at..tag_at + 11].to_vec();
+    tag[10] = 1;
+    assert!(tag_header(&tag).is_ok());
+    assert_eq!(
+      Parser::with_options(strict).tag_header(&tag),
+      Err(Err::Error(FlvError::ReservedBitsSet("StreamID")))
+    );
+    tag[10] = 0;
+    tag[0] |= 0x40;
+    assert_eq!(tag_header(&tag).unwrap().1.tag_type, TagType::Video);
+    assert_eq!(
+      Parser::with_options(strict).tag_header(&tag),
+      Err(Err::Error(FlvError::ReservedBitsSet("TagType")))
+    );
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn encrypted_tags() {
+    // an encrypted AAC raw frame
+    let mut tag = vec![0x28, 0, 0, 0, 0, 0, 0x10, 0, 0, 0, 0];
+    let mut body = vec![0xaf, 0x01, 1, 0, 10];
+    body.extend_from_slice(b"Encryption");
+    body.extend_from_slice(&[0, 0, 16]);
+    body.extend_from_slice(&[7; 16]);
+    body.extend_from_slice(b"data");
+    tag[3] = body.len() as u8;
+    tag.extend_from_slice(&body);
+
+    let (_, parsed) = complete_tag(&tag).unwrap();
+    assert!(parsed.header.filtered);
+    assert_eq!(parsed.header.tag_type, TagType::Audio);
+    let audio = parsed.data.as_audio().unwrap();
+    assert_eq!(audio.sound_format, SoundFormat::AAC);
+    assert_eq!(
+      encryption_header(&audio.sound_data[1..]),
+      Ok((&b"data"[..], Filter::Encryption { iv: [7; 16] }))
+    );
+    assert!(
+      !tag_header(&zelda[first_tag_at(zelda)..])
+        .unwrap()
+        .1
+        .filtered
+    );
+
+    // selective encryption, with and without an IV
+    let mut se = vec![1, 0, 2, b'S', b'E', 0, 0, 17, 0x80];
+    se.extend_from_slice(&[3; 16]);
+    assert_eq!(
+      encryption_header(&se),
+      Ok((&b""[..], Filter::SelectiveEncryption { iv: Some([3; 16]) }))
+    );
+    assert_eq!(
+      encryption_header(&[1, 0, 2, b'S', b'E', 0, 0, 1, 0, 9]),
+      Ok((&[9][..], Filter::SelectiveEncryption { iv: None }))
+    );
+    assert_eq!(
+      encryption_header(&[1, 0, 1, b'X', 0, 0, 2, 5, 6]),
+      Ok((
+        &b""[..],
+        Filter::Other {
+          name: "X",
+          params: &[5, 6]
+        }
+      ))
+    );
+    assert!(encryption_header(&[1, 0, 2, b'S', b'E', 0, 0, 2, 0x80, 0]).is_err());
+    assert_eq!(
+      encryption_header(&[2, 0, 2, b'S', b'E', 0, 0, 0]),
+      Err(Err::Error(FlvError::UnsupportedFilterCount(2)))
+    );
+  }
+
+  #[test]
+  fn filtered_tag_type() {
+    let tag_at = first_tag_at(zelda);
+    let mut tag = zelda[tag_at..tag_at + 11 + 537].to_vec();
+    tag[0] = 0x29;
+    let (_, header) = tag_header(&tag).unwrap();
+    assert_eq!(header.tag_type, TagType::Video);
+    assert!(header.filtered);
+    let (_, parsed) = complete_tag(&tag).unwrap();
+    assert!(parsed.header.filtered);
+    assert_eq!(parsed.data.as_video().unwrap().frame_type, FrameType::Key);
+
+    // the reserved bits are ignored, a wrong type still fails
+    tag[0] = 0xc9;
+    assert!(!tag_header(&tag).unwrap().1.filtered);
+    tag[0] = 0x27;
+    assert_eq!(
+      tag_header(&tag),
+      Err(Err::Error(FlvError::UnknownTagType(7)))
+    );
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn parse_sample_files() {
+    for (input, count) in [(zelda, 998), (zeldaHQ, 998), (commercials, 1923)] {
+      let (rest, (header, tags)) = parse_flv(input).unwrap();
+      assert!(rest.is_empty());
+      assert_eq!(header.offset, 9);
+      assert_eq!(tags.len(), count);
+    }
+
+    let truncated = &zelda[..zelda.len() - 100];
+    assert!(matches!(parse_flv(truncated), Err(Err::Incomplete(_))));
+    let lenient = ParserOptions {
+      allow_truncated_tag: true,
+      ..ParserOptions::default()
+    };
+    let (rest, (_, tags)) = Parser::with_options(lenient).parse_flv(truncated).unwrap();
+    assert_eq!(tags.len(), 997);
+    assert_eq!(tag_header(rest).unwrap().1.timestamp, 29675);
+    // only the last PreviousTagSize is missing
+    let (rest, (_, tags)) = Parser::with_options(lenient)
+      .parse_flv(&zelda[..zelda.len() - 2])
+      .unwrap();
+    assert_eq!(tags.len(), 997);
+    assert_eq!(tag_header(rest).unwrap().1.timestamp, 29675);
+  }
+
+  #[test]
+  fn avc_composition_time() {
+    let sequence_header = [0, 0, 0, 0x21, 1, 2];
+    assert_eq!(
+      avc_video_packet_header(&sequence_header)
+        .unwrap()
+        .1
+        .composition_time,
+      0x21
+    );
+    let checked = Parser::with_options(ParserOptions {
+      strict_composition_time: true,
+      ..ParserOptions::default()
+    });
+    assert_eq!(
+      checked.avc_video_packet_header(&sequence_header),
+      Err(Err::Error(FlvError::NonZeroCompositionTime(0x21)))
+    );
+    assert_eq!(
+      checked.avc_video_packet(&[2, 0xff, 0xff, 0xff], 4),
+      Err(Err::Error(FlvError::NonZeroCompositionTime(-1)))
+    );
+
+    let nalu = [1, 0, 0, 0x21, 1, 2];
+    assert_eq!(
+      checked.avc_video_packet_header(&nalu),
+      avc_video_packet_header(&nalu)
+    );
+    assert_eq!(
+      checked.avc_video_packet(&nalu, 6),
+      avc_video_packet(&nalu, 6)
+    );
+    assert!(checked.avc_video_packet(&[0, 0, 0, 0, 1], 5).is_ok());
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn multitrack_packets() {
+    // audio, many tracks many codecs: Opus track 1 and AAC track 2
+    let audio = [
+      0x95, 0x21, b'O', b'p', b'u', b's', 1, 0, 0, 2, 0xaa, 0xbb, b'm', b'p', b'4', b'a', 2, 0, 0,
+      1, 0xcc,
+    ];
+    assert!(ex_audio_tag_header(&audio).is_err());
+    assert_eq!(
+      ex_audio_multitrack(&[0x95, 0x31], 2),
+      Err(Err::Error(FlvError::UnknownMultitrackType(3)))
+    );
+    let (rest, multitrack) = ex_audio_multitrack(&audio, audio.len()).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(multitrack.packet_type, AudioPacketType::CodedFrames);
+    assert_eq!(
+      multitrack.multitrack_type,
+      AvMultitrackType::ManyTracksManyCodecs
+    );
+    let tracks: Vec<_> = multitrack.tracks.map(Result::unwrap).collect();
+    assert_eq!(
+      tracks,
+      vec![
+        MultitrackPacket {
+          track_id: 1,
+          fourcc: *b"Opus",
+          payload: &[0xaa, 0xbb],
+        },
+        MultitrackPacket {
+          track_id: 2,
+          fourcc: *b"mp4a",
+          payload: &[0xcc],
+        },
+      ]
+    );
+    assert_eq!(ex_track_id(TagType::Audio, &audio), Some(1));
+
+    // video, one hvc1 track with id 2
+    let video = [0x96, 0x01, b'h', b'v', b'c', b'1', 2, 0, 0, 0, 9];
+    let (_, multitrack) = ex_video_multitrack(&video, video.len()).unwrap();
+    assert_eq!(multitrack.frame_type, FrameType::Key);
+    assert_eq!(multitrack.packet_type, VideoPacketType::CodedFrames);
+    let tracks: Vec<_> = multitrack.tracks.collect();
+    assert_eq!(
+      tracks,
+      vec![Ok(MultitrackPacket {
+        track_id: 2,
+        fourcc: *b"hvc1",
+        payload: &[0, 0, 0, 9],
+      })]
+    );
+
+    // many tracks, the second one going past the body
+    let video = [
+      0x96, 0x13, b'a', b'v', b'0', b'1', 0, 0, 0, 1, 7, 1, 0, 0, 4, 8,
+    ];
+    let (_, multitrack) = ex_video_multitrack(&video, video.len()).unwrap();
+    assert_eq!(multitrack.packet_type, VideoPacketType::CodedFramesX);
+    let tracks: Vec<_> = multitrack.tracks.collect();
+    assert_eq!(tracks.len(), 2);
+    assert_eq!(tracks[0].as_ref().unwrap().payload, &[7]);
+    assert_eq!(
+      tracks[1],
+      Err(FlvError::BadLength {
+        field: "track",
+        declared: 4,
+        available: 1
+      })
+    );
+
+    // not multitrack, or nested multitrack
+    assert!(ex_video_multitrack(&[0x91, b'a', b'v', b'0', b'1'], 5).is_err());
+    assert!(ex_audio_multitrack(&[0x95, 0x05, b'O', b'p', b'u', b's', 1], 7).is_err());
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn pcm_audio_data() {
+    let samples = [0x01, 0x02, 0x03, 0x04];
+    let audio = AudioData::new_pcm(
+      true,
+      SoundRate::_44KHZ,
+      SoundSize::Snd16bit,
+      SoundType::SndStereo,
+      &samples,
+    );
+    assert_eq!(audio.sound_format, SoundFormat::PCM_LE);
+    assert_eq!(audio.raw_flags(), 0x3f);
+
+    let audio = AudioData::new_pcm(
+      false,
+      SoundRate::_11KHZ,
+      SoundSize::Snd8bit,
+      SoundType::SndMono,
+      &samples,
+    );
+    assert_eq!(audio.sound_format, SoundFormat::PCM_NE);
+    assert_eq!(audio.raw_flags(), 0x04);
+
+    let mut body = vec![audio.raw_flags()];
+    body.extend_from_slice(&samples);
+    assert_eq!(audio_data(&body, body.len()), Ok((&b""[..], audio)));
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn empty_strict_array() {
+    assert_eq!(
+      script_data_strict_array(&[0, 0, 0, 0, 9]),
+      Ok((&[9][..], vec![]))
+    );
+    assert_eq!(
+      script_data_value(&[10, 0, 0, 0, 0]),
+      Ok((&b""[..], ScriptDataValue::StrictArray(vec![])))
+    );
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn strict_array_count() {
+    let input = [0, 0, 0, 2, 5, 1, 1, 9];
+    assert_eq!(
+      script_data_strict_array(&input),
+      Ok((
+        &[9][..],
+        vec![ScriptDataValue::Null, ScriptDataValue::Boolean(true)]
+      ))
+    );
+
+    // the declared count is authoritative, a missing value is an error
+    assert_eq!(
+      script_data_strict_array(&input[..6]),
+      Err(Err::Incomplete(Needed::new(1)))
+    );
+    assert!(script_data_strict_array(&[0, 0, 0, 2, 5, 42]).is_err());
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn array_limits() {
+    let huge = [10, 0xff, 0xff, 0xff, 0xff, 5];
+    assert_eq!(
+      script_data_value(&huge),
+      Err(Err::Error(FlvError::ArrayTooLarge {
+        len: u32::MAX,
+        max: ParserOptions::DEFAULT_MAX_ARRAY_LEN,
+      }))
+    );
+
+    let options = ParserOptions {
+      max_array_len: 1,
+      ..ParserOptions::default()
+    };
+    let strict = [10, 0, 0, 0, 2, 5, 5];
+    assert!(script_data_value(&strict).is_ok());
+    assert_eq!(
+      Parser::with_options(options).script_data_value(&strict),
+      Err(Err::Error(FlvError::ArrayTooLarge { len: 2, max: 1 }))
+    );
+    // the count of ECMA arrays is only a hint
+    let ecma = [8, 0xff, 0xff, 0xff, 0xff, 0, 0, 9];
+    assert_eq!(
+      Parser::with_options(options).script_data_value(&ecma),
+      Ok((
+        &b""[..],
+        ScriptDataValue::ECMAArray {
+          count: u32::MAX,
+          entries: vec![],
+        }
+      ))
+    );
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn parser_with_options() {
+    let tag_at = first_tag_at(zelda);
+    let parser = Parser::new();
+    assert_eq!(
+      parser.complete_tag(&zelda[tag_at..]),
+      complete_tag(&zelda[tag_at..])
+    );
+    assert_eq!(
+      parser.tag_with_size(&zelda[tag_at..]),
+      tag_with_size(&zelda[tag_at..])
+    );
+    assert_eq!(parser.parse_flv(zelda), parse_flv(zelda));
+
+    let parser = Parser::with_options(ParserOptions {
+      check_data_size: true,
+      strict_composition_time: true,
+      strict_reserved: true,
+      max_array_len: 6,
+      ..ParserOptions::default()
+    });
+    assert_eq!(
+      parser.complete_tag(&zelda[tag_at..tag_at + 100]),
+      Err(Err::Error(FlvError::SizeMismatch {
+        declared: 537,
+        available: 89,
+      }))
+    );
+    // the onMetaData ECMA array declares 7 properties, which is not checked
+    let script_tag_at = first_tag_at(commercials);
+    assert!(parser.complete_tag(&commercials[script_tag_at..]).is_ok());
+    assert!(parser
+      .script_tag_records(&commercials[script_tag_at + 11..script_tag_at + 11 + 273])
+      .is_ok());
+    assert_eq!(
+      parser.avc_video_packet_header(&[0, 0, 0, 1]),
+      Err(Err::Error(FlvError::NonZeroCompositionTime(1)))
+    );
+    assert!(Parser::new().avc_video_packet(&[0, 0, 0, 1], 4).is_ok());
+
+    let mut file = zelda.to_vec();
+    file[tag_at + 8] = 1;
+    assert_eq!(
+      parser.parse_flv(&file),
+      Err(Err::Error(FlvError::ReservedBitsSet("StreamID")))
+    );
+    assert!(Parser::new().parse_flv(&file).is_ok());
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn ecma_array_count() {
+    // declares 5 entries but holds a single one
+    let input = [0, 0, 0, 5, 0, 1, b'a', 5, 0, 0, 9];
+    assert_eq!(
+      script_data_ecma_array(&input),
+      Ok((
+        &b""[..],
+        (
+          5,
+          vec![ScriptDataObject {
+            name: "a",
+            data: ScriptDataValue::Null,
+          }]
+        )
+      ))
+    );
+    assert_eq!(
+      ScriptDataValue::ecma_array(vec![]),
+      ScriptDataValue::ECMAArray {
+        count: 0,
+        entries: vec![],
+      }
+    );
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn raw_strings() {
+    // {"a": "caf\xe9"} in Latin-1, then a valid long string
+    let input = [
+      3, 0, 1, b'a', 2, 0, 4, b'c', b'a', b'f', 0xe9, 0, 1, b'b', 12, 0, 0, 0, 1, b'x', 0, 0, 9,
+    ];
+    assert!(script_data_value(&input).is_err());
+
+    let options = ParserOptions {
+      raw_invalid_strings: true,
+      ..ParserOptions::default()
+    };
+    assert_eq!(
+      Parser::with_options(options).script_data_value(&input),
+      Ok((
+        &b""[..],
+        ScriptDataValue::Object(vec![
+          ScriptDataObject {
+            name: "a",
+            data: ScriptDataValue::RawString(b"caf\xe9"),
+          },
+          ScriptDataObject {
+            name: "b",
+            data: ScriptDataValue::LongString("x"),
+          },
+        ])
+      ))
+    );
+    assert_eq!(
+      script_data_raw_string(&input[5..]),
+      Ok((&input[11..], &b"caf\xe9"[..]))
+    );
+
+    // names must still be UTF-8
+    assert!(Parser::with_options(options)
+      .script_data_value(&[3, 0, 1, 0xe9, 5, 0, 0, 9])
+      .is_err());
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn string_overrun() {
+    let tag_at = first_tag_at(zelda);
+    // a script tag of 6 bytes whose name declares 10, followed by the next
+    // tag
+    let mut input = vec![18, 0, 0, 6, 0, 0, 0, 0, 0, 0, 0, 2, 0, 10, b'o', b'n', b'M'];
+    input.extend_from_slice(&zelda[tag_at..tag_at + 11 + 537]);
+    let (_, tag) = complete_tag(&input).unwrap();
+    assert_eq!(tag.data, TagData::Script(&input[11..17]));
+    assert_eq!(
+      script_tag_records(&input[11..17]),
+      Err(Err::Error(FlvError::StringOverrun {
+        declared: 10,
+        available: 3,
+      }))
+    );
+    // without the tag boundary, the name reads into the next tag
+    assert_eq!(script_data(&input[11..]).unwrap().1.name.len(), 10);
+
+    // the same for an AMF3 string declaring 10 bytes out of 1
+    let body = [2, 0, 1, b'a', 17, 6, 0x15, b'b'];
+    assert_eq!(
+      script_tag_records(&body),
+      Err(Err::Error(FlvError::StringOverrun {
+        declared: 10,
+        available: 1,
+      }))
+    );
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn bad_script_tags() {
+    // onMetaData, then a cue point
+    let script_at = first_body_at(commercials);
+    let mut body = commercials[script_at..script_at + 273].to_vec();
+    body.extend_from_slice(&[2, 0, 10]);
+    body.extend_from_slice(b"onCuePoint");
+    body.extend_from_slice(&[5]);
+    let (rest, records) = script_tag_records(&body).unwrap();
+    assert!(rest.is_empty());
+    let names: Vec<_> = records.iter().map(|record| record.name).collect();
+    assert_eq!(names, vec!["onMetaData", "onCuePoint"]);
+    assert_eq!(script_tag_records(&[]), Ok((&b""[..], vec![])));
+
+    // bad script tags between the media tags of a file
+    let bodies: [(u8, &[u8]); 4] = [
+      // unknown marker
+      (18, &[2, 0, 1, b'a', 0x42]),
+      // not UTF-8
+      (18, &[2, 0, 1, 0xe9, 5]),
+      // empty
+      (18, &[]),
+      // filtered, so encrypted
+      (18 | 0x20, &[0xde, 0xad, 0xbe, 0xef]),
+    ];
+    let mut input = zelda[..first_tag_at(zelda) + 11 + 537 + 4].to_vec();
+    for (tag_type, body) in bodies.iter() {
+      input.extend_from_slice(&[*tag_type, 0, 0, body.len() as u8, 0, 0, 0, 0, 0, 0, 0]);
+      input.extend_from_slice(body);
+      input.extend_from_slice(&(11 + body.len() as u32).to_be_bytes());
+    }
+    input.extend_from_slice(&zelda[565..565 + 11 + 642 + 4]);
+
+    let tags: Vec<_> = tags(&input).map(Result::unwrap).collect();
+    assert_eq!(tags.len(), 6);
+    assert_eq!(tags[0].header.tag_type, TagType::Video);
+    assert_eq!(tags[5].header.tag_type, TagType::Audio);
+    for (tag, (_, body)) in tags[1..5].iter().zip(bodies.iter()) {
+      assert_eq!(tag.data.as_script(), Some(*body));
+    }
+    assert!(tags[4].header.filtered);
+    assert_eq!(
+      script_tag_records(tags[1].data.as_script().unwrap()),
+      Err(Err::Error(FlvError::Nom(ErrorKind::Alt)))
+    );
+    assert!(script_tag_records(tags[2].data.as_script().unwrap()).is_err());
+    assert_eq!(parse_flv(&input).unwrap().1 .1, tags);
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn tags_of_one_type() {
+    let audio: Vec<_> = tags_of_type(zelda, TagType::Audio)
+      .map(|tag| tag.unwrap())
+      .collect();
+    let expected: Vec<_> = tags(zelda)
+      .map(|tag| tag.unwrap())
+      .filter(|tag| tag.header.tag_type == TagType::Audio)
+      .collect();
+    assert!(!audio.is_empty());
+    assert_eq!(audio, expected);
+
+    assert_eq!(tags_of_type(commercials, TagType::Script).count(), 1);
+    assert_eq!(
+      tags_of_type(zelda, TagType::Video).count() + audio.len(),
+      998
+    );
+
+    // a truncated file still ends with an error, even if the last tag is
+    // skipped
+    let truncated: Vec<_> = tags_of_type(&zelda[..1000], TagType::Script).collect();
+    assert!(matches!(&truncated[..], [Err(FlvError::Incomplete(_))]));
+  }
 
-pub fn script_data_object_end(input: &[u8]) -> IResult<&[u8], &[u8]> {
-  tag(script_data_object_end_terminator)(input)
-}
+  #[test]
+  fn raw_tag_walk() {
+    let tag_at = first_tag_at(zelda);
+    let body_at = first_body_at(zelda);
+    let mut walk = raw_tags(zelda);
+    let first = walk.next().unwrap();
+    assert_eq!(first.offset, tag_at);
+    assert_eq!(first.header.tag_type, TagType::Video);
+    assert_eq!(first.body(), Some(&zelda[body_at..body_at + 537]));
+    assert_eq!(first.bytes(), Some(&zelda[tag_at..tag_at + 11 + 537 + 4]));
+    assert_eq!(walk.next().unwrap().offset, 565);
+    assert_eq!(raw_tags(zelda).count(), tags(zelda).count());
 
-pub fn script_data_string(input: &[u8]) -> IResult<&[u8], &str> {
-  map_res(length_data(be_u16), from_utf8)(input)
-}
+    // the last tag is yielded without its body
+    let truncated = raw_tags(&zelda[..1000]).last().unwrap();
+    assert_eq!(truncated.offset, 565);
+    assert_eq!(truncated.header.data_size, 642);
+    assert_eq!(truncated.body(), None);
+    assert_eq!(truncated.bytes(), None);
 
-pub fn script_data_long_string(input: &[u8]) -> IResult<&[u8], &str> {
-  map_res(length_data(be_u32), from_utf8)(input)
-}
+    assert_eq!(raw_tags(b"RIFF").count(), 0);
+  }
 
-pub fn script_data_date(input: &[u8]) -> IResult<&[u8], ScriptDataDate> {
-  map(
-    pair(be_f64, be_i16),
-    |(date_time, local_date_time_offset)| ScriptDataDate {
-      date_time,
-      local_date_time_offset,
-    },
-  )(input)
-}
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn keyframes() {
+    fn file(bodies: &[(u8, &[u8])]) -> Vec<u8> {
+      let mut file = b"FLV\x01\x05\x00\x00\x00\x09\x00\x00\x00\x00".to_vec();
+      for (tag_type, body) in bodies {
+        file.extend_from_slice(&[*tag_type, 0, 0, body.len() as u8, 0, 0, 0, 0, 0, 0, 0]);
+        file.extend_from_slice(body);
+        file.extend_from_slice(&(11 + body.len() as u32).to_be_bytes());
+      }
+      file
+    }
 
-pub fn script_data_ecma_array(input: &[u8]) -> IResult<&[u8], Vec<ScriptDataObject>> {
-  map(pair(be_u32, script_data_objects), |(_, data_objects)| {
-    data_objects
-  })(input)
-}
+    let input = file(&[
+      // AVC sequence header, keyframe, inter frame
+      (9, &[0x17, 0, 0, 0, 0, 1, 0x64]),
+      (8, &[0xaf, 0, 0x12, 0x10]),
+      (9, &[0x17, 1, 0, 0, 0, 0xaa]),
+      (9, &[0x27, 1, 0, 0, 0, 0xbb]),
+      // enhanced HEVC keyframe
+      (9, &[0x93, b'h', b'v', b'c', b'1', 0xcc]),
+      // Sorenson H263 keyframe
+      (9, &[0x12, 0, 0, 0x84]),
+    ]);
+    let keyframes: Vec<_> = keyframe_tags(&input)
+      .map(|tag| tag.unwrap().data.as_video().unwrap().video_data.to_vec())
+      .collect();
+    assert_eq!(keyframes, vec![vec![1, 0, 0, 0, 0xaa], vec![0, 0, 0x84],]);
 
-pub fn script_data_strict_array(input: &[u8]) -> IResult<&[u8], Vec<ScriptDataValue>> {
-  flat_map(be_u32, |o| many_m_n(1, o as usize, script_data_value))(input)
-}
+    let first = keyframe_tags(commercials).next().unwrap().unwrap();
+    assert_eq!(first.data.as_video().unwrap().frame_type, FrameType::Key);
+    assert_eq!(
+      keyframe_tags(zelda).count(),
+      tags(zelda)
+        .filter(|tag| matches!(
+          &tag.as_ref().unwrap().data,
+          TagData::Video(video) if video.frame_type == FrameType::Key
+        ))
+        .count()
+    );
+  }
 
-#[allow(non_upper_case_globals)]
-#[cfg(test)]
-mod tests {
-  use super::*;
-  use nom::number::streaming::be_u32;
-  use nom::HexDisplay;
+  #[test]
+  fn aac_audio_data() {
+    // AAC LC sequence header, 44.1 kHz stereo, then a raw frame
+    let (_, audio) = audio_data(&[0xaf, 0, 0x12, 0x10], 4).unwrap();
+    assert_eq!(audio.sound_data, &[0, 0x12, 0x10]);
+    assert_eq!(
+      audio.aac_packet(),
+      Some(AACAudioPacket {
+        packet_type: AACPacketType::SequenceHeader,
+        aac_data: &[0x12, 0x10],
+      })
+    );
+    let (_, audio) = audio_data(&[0xaf, 1, 0x21, 0x1b], 4).unwrap();
+    assert_eq!(
+      audio.aac_packet(),
+      Some(AACAudioPacket {
+        packet_type: AACPacketType::Raw,
+        aac_data: &[0x21, 0x1b],
+      })
+    );
 
-  const zelda: &[u8] = include_bytes!("../assets/zelda.flv");
-  const zeldaHQ: &[u8] = include_bytes!("../assets/zeldaHQ.flv");
-  const commercials: &[u8] = include_bytes!("../assets/asian-commercials-are-weird.flv");
+    let (_, audio) = audio_data(&[0xaf, 2], 2).unwrap();
+    assert_eq!(audio.aac_packet(), None);
+    assert_eq!(
+      aac_audio_packet(&[2], 1),
+      Err(Err::Error(FlvError::UnknownPacketType(2)))
+    );
+    let (_, audio) = audio_data(&[0xaf], 1).unwrap();
+    assert_eq!(audio.aac_packet(), None);
+    let (_, audio) = audio_data(&[0x2f, 1], 2).unwrap();
+    assert_eq!(audio.aac_packet(), None);
+  }
 
   #[test]
-  fn headers() {
+  fn avc_video_data() {
+    // a NALU keyframe with a composition time of 66 ms
+    let body = [0x17, 1, 0, 0, 0x42, 0, 0, 0, 2, 0x65, 0x88];
+    let (_, video) = video_data(&body, body.len()).unwrap();
+    assert_eq!(video.video_data, &body[1..]);
     assert_eq!(
-      header(&zelda[..9]),
-      Ok((
-        &b""[..],
-        Header {
-          version: 1,
-          audio: true,
-          video: true,
-          offset: 9
-        }
-      ))
+      video.avc_packet(),
+      Some(AVCVideoPacket {
+        packet_type: AVCPacketType::NALU,
+        composition_time: 66,
+        avc_data: &body[5..],
+      })
     );
+
+    let (_, video) = video_data(&[0x17, 1, 0], 3).unwrap();
+    assert_eq!(video.avc_packet(), None);
+    let (_, video) = video_data(&zelda[first_body_at(zelda)..], 537).unwrap();
+    assert_eq!(video.avc_packet(), None);
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn codec_video_tags() {
+    let body = [0x17, 1, 0, 0, 0, 0, 0, 0, 1, 0x65];
     assert_eq!(
-      header(&zeldaHQ[..9]),
+      video_tag(&body, body.len()),
       Ok((
         &b""[..],
-        Header {
-          version: 1,
-          audio: true,
-          video: true,
-          offset: 9
-        }
+        VideoTag::Avc(AVCVideoPacket {
+          packet_type: AVCPacketType::NALU,
+          composition_time: 0,
+          avc_data: &body[5..],
+        })
       ))
     );
+
+    // a VP6 keyframe of commercials
+    let (_, tag) = complete_tag(&commercials[499..]).unwrap();
+    let size = tag.header.data_size as usize;
+    match video_tag(&commercials[499 + 11..], size) {
+      Ok((_, VideoTag::Vp6(packet))) => {
+        assert_eq!(packet.vertical_adjust, 4);
+        assert_eq!(packet.alpha_offset, None);
+        assert_eq!(packet.data, &commercials[499 + 13..499 + 11 + size]);
+      }
+      other => panic!("expected a VP6 packet: {:?}", other),
+    }
+
+    // zelda is Sorenson H.263
+    let body_at = first_body_at(zelda);
     assert_eq!(
-      header(&commercials[..9]),
+      video_tag(&zelda[body_at..], 537),
       Ok((
-        &b""[..],
-        Header {
-          version: 1,
-          audio: true,
-          video: true,
-          offset: 9
+        &zelda[body_at + 537..],
+        VideoTag::Other {
+          codec_id: CodecId::SORENSON_H263,
+          data: &zelda[body_at + 1..body_at + 537],
         }
       ))
     );
-  }
 
-  #[test]
-  fn first_tag_headers() {
-    // starts at 9 bytes (header) + 4 (size of previous tag)
-    // header is 11 bytes long
+    let body = [0x13, 0x10, 0x10, 0x00, 0x10, 0, 1, 7];
+    match video_tag(&body, body.len()) {
+      Ok((_, VideoTag::Screen(packet))) => {
+        assert_eq!(packet.blocks().collect::<Vec<_>>(), vec![&[7][..]])
+      }
+      other => panic!("expected a screen video packet: {:?}", other),
+    }
+
+    // an H264 command frame, which has no AVC packet header
     assert_eq!(
-      tag_header(&zelda[13..24]),
+      video_tag(&[0x57, 0], 2),
       Ok((
         &b""[..],
-        TagHeader {
-          tag_type: TagType::Video,
-          data_size: 537,
-          timestamp: 0,
-          stream_id: 0
+        VideoTag::Command {
+          codec_id: CodecId::H264,
+          data: &[0],
         }
       ))
     );
     assert_eq!(
-      tag_header(&zeldaHQ[13..24]),
-      Ok((
-        &b""[..],
-        TagHeader {
-          tag_type: TagType::Video,
-          data_size: 2984,
-          timestamp: 0,
-          stream_id: 0
-        }
-      ))
+      video_tag(&[0x17, 1, 0], 3),
+      Err(Err::Error(FlvError::BadLength {
+        field: "AVC packet header",
+        declared: 4,
+        available: 2,
+      }))
     );
     assert_eq!(
-      tag_header(&commercials[13..24]),
+      video_tag(&[0x17], 1),
+      Err(Err::Error(FlvError::BadLength {
+        field: "AVC packet header",
+        declared: 4,
+        available: 0,
+      }))
+    );
+
+    let body = [0x1c, 1, 0, 0, 0x10, 0x26];
+    assert_eq!(
+      video_tag(&body, body.len()),
       Ok((
         &b""[..],
-        TagHeader {
-          tag_type: TagType::Script,
-          data_size: 273,
-          timestamp: 0,
-          stream_id: 0
-        }
+        VideoTag::Hevc(HEVCVideoPacket {
+          signaling: CodecSignaling::CodecId,
+          frame_type: FrameType::Key,
+          packet_type: HEVCPacketType::NALU,
+          composition_time: 0x10,
+          hevc_data: &[0x26],
+        })
       ))
     );
   }
 
   #[test]
-  fn audio_tags() {
-    let tag_start = 24 + 537 + 4;
-    println!(
-      "size of previous tag: {:?}",
-      be_u32::<_, ()>(&zelda[24 + 537..tag_start])
-    );
+  fn format_audio_tags() {
     assert_eq!(
-      tag_header(&zelda[tag_start..tag_start + 11]),
+      audio_tag(&[0xaf, 1, 0x21, 0x1b], 4),
       Ok((
         &b""[..],
-        TagHeader {
-          tag_type: TagType::Audio,
-          data_size: 642,
-          timestamp: 0,
-          stream_id: 0
-        }
+        AudioTag::Aac(AACAudioPacket {
+          packet_type: AACPacketType::Raw,
+          aac_data: &[0x21, 0x1b],
+        })
       ))
     );
-
-    let tag_start2 = 24 + 2984 + 4;
-    println!(
-      "size of previous tag: {:?}",
-      be_u32::<_, ()>(&zeldaHQ[24 + 2984..tag_start2])
+    assert!(audio_tag(&[0xaf, 2, 0x21], 3).is_err());
+    assert_eq!(
+      audio_tag(&[0xaf], 1),
+      Err(Err::Error(FlvError::BadLength {
+        field: "AACPacketType",
+        declared: 1,
+        available: 0,
+      }))
     );
-    println!(
-      "data:\n{}",
-      (&zeldaHQ[tag_start2..tag_start2 + 11]).to_hex(8)
+
+    // MP3 in commercials, ADPCM in zelda
+    assert_eq!(
+      audio_tag(&commercials[312..], 183),
+      Ok((
+        &commercials[312 + 183..],
+        AudioTag::Mp3(&commercials[313..312 + 183])
+      ))
     );
     assert_eq!(
-      tag_header(&zeldaHQ[tag_start2..tag_start2 + 11]),
+      audio_tag(&zelda[576..], 642),
       Ok((
-        &b""[..],
-        TagHeader {
-          tag_type: TagType::Audio,
-          data_size: 642,
-          timestamp: 0,
-          stream_id: 0
+        &zelda[576 + 642..],
+        AudioTag::Raw {
+          format: SoundFormat::ADPCM,
+          data: &zelda[577..576 + 642],
         }
       ))
     );
+  }
 
-    println!(
-      "data: {:?}",
-      audio_data(&zelda[tag_start + 11..tag_start + 11 + 642], 642)
+  #[test]
+  fn strict_header() {
+    let strict = Parser::with_options(ParserOptions {
+      strict_header: true,
+      ..ParserOptions::default()
+    });
+    assert_eq!(strict.header(&zelda[..9]), header(&zelda[..9]));
+
+    let version_2 = b"FLV\x02\x05\x00\x00\x00\x09";
+    assert!(header(version_2).is_ok());
+    assert_eq!(
+      strict.header(version_2),
+      Err(Err::Error(FlvError::UnsupportedVersion(2)))
     );
-    println!(
-      "data: {:?}",
-      audio_data(&zeldaHQ[tag_start2 + 11..tag_start2 + 11 + 642], 642)
+    let reserved = b"FLV\x01\x0d\x00\x00\x00\x09";
+    assert!(header(reserved).is_ok());
+    assert_eq!(
+      strict.header(reserved),
+      Err(Err::Error(FlvError::ReservedBitsSet("TypeFlagsReserved")))
     );
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn header_data_offset() {
+    let strict = Parser::with_options(ParserOptions {
+      strict_header: true,
+      ..ParserOptions::default()
+    });
+    let short = b"FLV\x01\x05\x00\x00\x00\x08";
     assert_eq!(
-      audio_data(&zelda[tag_start + 11..tag_start + 11 + 642], 642),
-      Ok((
-        &b""[..],
-        AudioData {
-          sound_format: SoundFormat::ADPCM,
-          sound_rate: SoundRate::_22KHZ,
-          sound_size: SoundSize::Snd16bit,
-          sound_type: SoundType::SndMono,
-          sound_data: &zelda[tag_start + 12..tag_start + 11 + 642]
-        }
-      ))
+      strict.header(short),
+      Err(Err::Error(FlvError::BadDataOffset(8)))
     );
 
+    // 3 bytes of padding after a header declaring an offset of 12
+    let mut padded = b"FLV\x01\x05\x00\x00\x00\x0c\xff\xff\xff".to_vec();
+    padded.extend_from_slice(&zelda[9..]);
+    assert_eq!(strict.header(&padded).unwrap().1.offset, 12);
+    assert_eq!(tags(&padded).count(), 998);
+    assert!(tags(&padded).all(|tag| tag.is_ok()));
+    let (_, (_, parsed)) = strict.parse_flv(&padded).unwrap();
+    assert_eq!(parsed, parse_flv(zelda).unwrap().1 .1);
+  }
+
+  #[test]
+  fn first_tag() {
+    let tag_at = first_tag_at(zelda);
+    let (_, parsed) = header(zelda).unwrap();
+    assert_eq!(body_start(&parsed), 13);
+    assert_eq!(skip_to_first_tag(zelda, &parsed), &zelda[tag_at..]);
     assert_eq!(
-      audio_data(&zeldaHQ[tag_start2 + 11..tag_start2 + 11 + 642], 642),
-      Ok((
-        &b""[..],
-        AudioData {
-          sound_format: SoundFormat::ADPCM,
-          sound_rate: SoundRate::_22KHZ,
-          sound_size: SoundSize::Snd16bit,
-          sound_type: SoundType::SndMono,
-          sound_data: &zeldaHQ[tag_start2 + 12..tag_start2 + 11 + 642]
-        }
-      ))
+      complete_tag(skip_to_first_tag(zelda, &parsed)),
+      complete_tag(&zelda[tag_at..])
     );
+    assert_eq!(skip_to_first_tag(&zelda[..11], &parsed), &[]);
   }
 
+  #[cfg(feature = "alloc")]
   #[test]
-  fn video_tags() {
-    let tag_start = 24;
+  fn initial_tag_size() {
+    let options = ParserOptions {
+      strict_initial_tag_size: true,
+      ..ParserOptions::default()
+    };
     assert_eq!(
-      video_data(&zelda[tag_start..tag_start + 537], 537),
-      Ok((
-        &b""[..],
-        VideoData {
-          frame_type: FrameType::Key,
-          codec_id: CodecId::SORENSON_H263,
-          video_data: &zelda[tag_start + 1..tag_start + 537]
-        }
-      ))
+      Parser::with_options(options).parse_flv(zelda),
+      parse_flv(zelda)
     );
+
+    let mut spliced = zelda.to_vec();
+    spliced[12] = 0x20;
+    assert!(parse_flv(&spliced).is_ok());
     assert_eq!(
-      video_data(&zeldaHQ[tag_start..tag_start + 2984], 2984),
-      Ok((
-        &b""[..],
-        VideoData {
-          frame_type: FrameType::Key,
-          codec_id: CodecId::SORENSON_H263,
-          video_data: &zeldaHQ[tag_start + 1..tag_start + 2984]
-        }
-      ))
+      Parser::with_options(options).parse_flv(&spliced),
+      Err(Err::Error(FlvError::NonZeroInitialTagSize(0x20)))
     );
   }
 
+  #[cfg(feature = "alloc")]
   #[test]
-  fn script_tags() {
-    let tag_start = 24;
-    let tag_end = tag_start + 273;
+  fn tag_types() {
+    let options = ParserOptions {
+      strict_tag_types: true,
+      ..ParserOptions::default()
+    };
+    assert_eq!(
+      Parser::with_options(options).parse_flv(zelda),
+      parse_flv(zelda)
+    );
 
-    match script_data(&commercials[tag_start..tag_end]) {
-      Ok((remaining, script_data)) => {
-        assert_eq!(remaining.len(), 0);
-        assert_eq!(
-          script_data,
-          ScriptData {
-            name: "onMetaData",
-            arguments: ScriptDataValue::ECMAArray(vec![
-              ScriptDataObject {
-                name: "duration",
-                data: ScriptDataValue::Number(28.133)
-              },
-              ScriptDataObject {
-                name: "width",
-                data: ScriptDataValue::Number(464.0)
-              },
-              ScriptDataObject {
-                name: "height",
-                data: ScriptDataValue::Number(348.0)
-              },
-              ScriptDataObject {
-                name: "videodatarate",
-                data: ScriptDataValue::Number(368.0)
-              },
-              ScriptDataObject {
-                name: "framerate",
-                data: ScriptDataValue::Number(30.0)
-              },
-              ScriptDataObject {
-                name: "videocodecid",
-                data: ScriptDataValue::Number(4.0)
-              },
-              ScriptDataObject {
-                name: "audiodatarate",
-                data: ScriptDataValue::Number(56.0)
-              },
-              ScriptDataObject {
-                name: "audiodelay",
-                data: ScriptDataValue::Number(0.0)
-              },
-              ScriptDataObject {
-                name: "audiocodecid",
-                data: ScriptDataValue::Number(2.0)
-              },
-              ScriptDataObject {
-                name: "canSeekToEnd",
-                data: ScriptDataValue::Number(1.0)
-              },
-              ScriptDataObject {
-                name: "creationdate",
-                data: ScriptDataValue::String("Thu Oct 04 18:37:42 2007\n")
-              }
-            ])
-          }
-        );
-      }
-      _ => unreachable!(),
-    }
+    // announce a video only file
+    let mut mislabeled = zelda.to_vec();
+    mislabeled[4] = 0x01;
+    assert!(parse_flv(&mislabeled).is_ok());
+    assert_eq!(
+      Parser::with_options(options).parse_flv(&mislabeled),
+      Err(Err::Error(FlvError::UnexpectedTagType(TagType::Audio)))
+    );
   }
 
+  #[cfg(feature = "alloc")]
   #[test]
-  fn complete_video_tags() {
-    let tag_start = 13;
-    let tag_data_start = tag_start + 11;
+  fn tags_with_options() {
+    let mut options = ParserOptions::default();
+    let parser = Parser::with_options(options);
+    assert!(parser.tags(zelda).eq(tags(zelda)));
+
+    // a truncated last tag is dropped instead of reported
+    let truncated: Vec<_> = tags(&zelda[..1000]).collect();
+    assert!(matches!(
+      &truncated[..],
+      [Ok(_), Err(FlvError::Incomplete(_))]
+    ));
+    options.allow_truncated_tag = true;
+    let parser = Parser::with_options(options);
+    let truncated: Vec<_> = parser.tags(&zelda[..1000]).collect();
+    assert!(matches!(&truncated[..], [Ok(_)]));
     assert_eq!(
-      complete_tag(&zelda[tag_start..tag_data_start + 537]),
-      Ok((
-        &b""[..],
-        Tag {
-          header: TagHeader {
-            tag_type: TagType::Video,
-            data_size: 537,
-            timestamp: 0,
-            stream_id: 0
-          },
-          data: TagData::Video(VideoData {
-            frame_type: FrameType::Key,
-            codec_id: CodecId::SORENSON_H263,
-            video_data: &zelda[tag_data_start + 1..tag_data_start + 537]
-          })
-        }
-      ))
+      parser.tags_of_type(&zelda[..1000], TagType::Audio).count(),
+      0
     );
+
+    let mut mislabeled = zelda.to_vec();
+    mislabeled[4] = 0x01;
+    mislabeled[12] = 0x20;
+    assert!(tags(&mislabeled).all(|tag| tag.is_ok()));
+    options.strict_tag_types = true;
+    let errors: Vec<_> = Parser::with_options(options)
+      .tags_of_type(&mislabeled, TagType::Video)
+      .filter_map(Result::err)
+      .collect();
+    assert_eq!(errors.len(), 1);
     assert_eq!(
-      complete_tag(&zeldaHQ[tag_start..tag_data_start + 2984]),
-      Ok((
-        &b""[..],
-        Tag {
-          header: TagHeader {
-            tag_type: TagType::Video,
-            data_size: 2984,
-            timestamp: 0,
-            stream_id: 0
-          },
-          data: TagData::Video(VideoData {
-            frame_type: FrameType::Key,
-            codec_id: CodecId::SORENSON_H263,
-            video_data: &zeldaHQ[tag_data_start + 1..tag_data_start + 2984]
-          })
-        }
-      ))
+      errors[0].root(),
+      &FlvError::UnexpectedTagType(TagType::Audio)
+    );
+    options.strict_initial_tag_size = true;
+    assert_eq!(
+      Parser::with_options(options).tags(&mislabeled).next(),
+      Some(Err(FlvError::NonZeroInitialTagSize(0x20)))
+    );
+
+    // the reserved bits of the second tag type
+    let mut reserved = zelda.to_vec();
+    reserved[565] |= 0x40;
+    let strict = Parser::with_options(ParserOptions {
+      strict_reserved: true,
+      ..ParserOptions::default()
+    });
+    let reserved: Vec<_> = strict.keyframe_tags(&reserved).collect();
+    assert_eq!(reserved.len(), 2);
+    assert_eq!(
+      reserved[1].as_ref().unwrap_err().root(),
+      &FlvError::ReservedBitsSet("TagType")
     );
   }
 }
@@ -0,0 +1,66 @@
+use alloc::vec::Vec;
+
+use crate::parser::{body_start, header, raw_tags};
+
+/// Rewrites every PreviousTagSize of a complete FLV file to `11 + data_size`
+/// of the tag before it, the first one to 0, as players seeking from the end
+/// of the file expect.
+///
+/// Tags are delimited by the `data_size` of their header, the existing
+/// PreviousTagSize fields are not trusted. Input that can't be walked this
+/// way, like a truncated last tag, is copied as is.
+pub fn repair_tag_sizes(input: &[u8]) -> Vec<u8> {
+  let mut output = Vec::with_capacity(input.len());
+  let header = match header(input) {
+    Ok((_, header)) if input.len() >= body_start(&header) => header,
+    _ => {
+      output.extend_from_slice(input);
+      return output;
+    }
+  };
+  output.extend_from_slice(&input[..header.offset as usize]);
+  output.extend_from_slice(&0u32.to_be_bytes());
+  let mut offset = body_start(&header);
+
+  for tag in raw_tags(input) {
+    let bytes = match tag.bytes() {
+      Some(bytes) => bytes,
+      None => break,
+    };
+    let size = bytes.len() - 4;
+    output.extend_from_slice(&bytes[..size]);
+    output.extend_from_slice(&(size as u32).to_be_bytes());
+    offset += bytes.len();
+  }
+
+  output.extend_from_slice(&input[offset..]);
+  output
+}
+
+#[allow(non_upper_case_globals)]
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const zelda: &[u8] = include_bytes!("../assets/zelda.flv");
+
+  #[test]
+  fn corrupt_sizes() {
+    assert_eq!(repair_tag_sizes(zelda), zelda);
+
+    let mut corrupt = zelda.to_vec();
+    // the initial PreviousTagSize, and the one after the first tag
+    corrupt[9..13].copy_from_slice(&[0xff; 4]);
+    let first = raw_tags(zelda).next().unwrap();
+    let end = first.offset + first.bytes().unwrap().len();
+    corrupt[end - 4..end].copy_from_slice(&[0, 0, 0, 1]);
+    assert_eq!(repair_tag_sizes(&corrupt), zelda);
+  }
+
+  #[test]
+  fn truncated() {
+    let truncated = &zelda[..1000];
+    assert_eq!(repair_tag_sizes(truncated), truncated);
+    assert_eq!(repair_tag_sizes(b"not an flv"), b"not an flv");
+  }
+}
@@ -0,0 +1,126 @@
+use nom::bits::bits;
+use nom::bits::streaming::take;
+use nom::bytes::streaming::take as take_bytes;
+use nom::combinator::flat_map;
+use nom::number::streaming::be_u16;
+use nom::sequence::tuple;
+use nom::IResult;
+
+use crate::error::FlvError;
+
+/// A screen video (`CodecId::SCREEN`) packet: the image is cut in blocks,
+/// each compressed independently.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScreenVideoPacket<'a> {
+  /// In pixels, a multiple of 16 up to 256.
+  pub block_width: u16,
+  pub image_width: u16,
+  /// In pixels, a multiple of 16 up to 256.
+  pub block_height: u16,
+  pub image_height: u16,
+  /// The size-prefixed blocks, see `blocks`.
+  blocks: &'a [u8],
+}
+
+impl<'a> ScreenVideoPacket<'a> {
+  /// Number of blocks per row, the last one can be narrower.
+  pub fn columns(&self) -> usize {
+    usize::from(self.image_width).div_ceil(usize::from(self.block_width))
+  }
+
+  /// Number of rows of blocks, the last one can be shorter.
+  pub fn rows(&self) -> usize {
+    usize::from(self.image_height).div_ceil(usize::from(self.block_height))
+  }
+
+  /// The data of each block, row by row from the bottom left of the image.
+  /// It is zlib compressed BGR pixels, bottom row first, or empty if the
+  /// block did not change since the last keyframe.
+  pub fn blocks(&self) -> ScreenBlocks<'a> {
+    ScreenBlocks { input: self.blocks }
+  }
+}
+
+/// Iterator over the blocks of a screen video packet.
+#[derive(Clone, Debug)]
+pub struct ScreenBlocks<'a> {
+  input: &'a [u8],
+}
+
+impl<'a> Iterator for ScreenBlocks<'a> {
+  type Item = &'a [u8];
+
+  fn next(&mut self) -> Option<Self::Item> {
+    // the sizes were checked by screen_video_packet
+    let (rest, block) = block(self.input).ok()?;
+    self.input = rest;
+    Some(block)
+  }
+}
+
+fn block(input: &[u8]) -> IResult<&[u8], &[u8], FlvError> {
+  flat_map(be_u16, take_bytes)(input)
+}
+
+/// Parses the `video_data` of a screen video tag, checking the size of
+/// every block.
+///
+/// Only version 1 is supported: `CodecId::SCREEN2` packets have more
+/// headers, and fail or give meaningless blocks.
+pub fn screen_video_packet(input: &[u8]) -> IResult<&[u8], ScreenVideoPacket<'_>, FlvError> {
+  let (i, (block_width, image_width, block_height, image_height)) =
+    bits::<_, _, FlvError, _, _>(tuple((
+      take::<_, u16, _, _>(4usize),
+      take::<_, u16, _, _>(12usize),
+      take::<_, u16, _, _>(4usize),
+      take::<_, u16, _, _>(12usize),
+    )))(input)?;
+  let mut packet = ScreenVideoPacket {
+    block_width: (block_width + 1) * 16,
+    image_width,
+    block_height: (block_height + 1) * 16,
+    image_height,
+    blocks: i,
+  };
+
+  let mut rest = i;
+  for _ in 0..packet.columns() * packet.rows() {
+    rest = block(rest)?.0;
+  }
+  packet.blocks = &i[..i.len() - rest.len()];
+  Ok((rest, packet))
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+  use super::*;
+  use alloc::{vec, vec::Vec};
+  use nom::{Err, Needed};
+
+  #[test]
+  fn blocks() {
+    // 40x20 image in 32x16 blocks: 2 columns and 2 rows
+    let input = [
+      0x10, 0x28, 0x00, 0x14, // header
+      0, 2, 0x78, 0x9c, // bottom left
+      0, 0, // unchanged
+      0, 1, 7, // top left
+      0, 0, // unchanged
+      0xff,
+    ];
+    let (rest, packet) = screen_video_packet(&input).unwrap();
+    assert_eq!(rest, &[0xff]);
+    assert_eq!(packet.block_width, 32);
+    assert_eq!(packet.image_width, 40);
+    assert_eq!(packet.block_height, 16);
+    assert_eq!(packet.image_height, 20);
+    assert_eq!((packet.columns(), packet.rows()), (2, 2));
+    let blocks: Vec<_> = packet.blocks().collect();
+    assert_eq!(blocks, vec![&[0x78, 0x9c][..], &[][..], &[7][..], &[][..]]);
+
+    assert_eq!(
+      screen_video_packet(&input[..11]),
+      Err(Err::Incomplete(Needed::new(1)))
+    );
+  }
+}
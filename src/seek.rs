@@ -0,0 +1,189 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "std")]
+use crate::metadata::KeyframeIndex;
+#[cfg(feature = "std")]
+use crate::parser::tag_header;
+use crate::parser::{raw_tags, video_data_header, FrameType, TagHeader, TagType};
+
+/// Finds the first video keyframe whose timestamp is at or after
+/// `target_ms`, scanning the tags of a complete FLV file from the start.
+///
+/// Tags are walked with `raw_tags`, so no index is needed. Returns the byte
+/// offset of the tag header in `input` along with the header, or `None` if
+/// the file ends (or stops parsing) before such a keyframe.
+///
+/// The offset is a `usize` index into `input`, like the ones returned by
+/// `seek_to_time` and `check_monotonic`: the whole file is in memory, so it
+/// always fits.
+pub fn find_tag_near(input: &[u8], target_ms: u32) -> Option<(usize, TagHeader)> {
+  raw_tags(input)
+    .find(|tag| {
+      tag.header.tag_type == TagType::Video
+        && tag.header.timestamp >= target_ms
+        && matches!(
+          tag.body().map(video_data_header),
+          Some(Ok((_, video))) if video.frame_type == FrameType::Key
+        )
+    })
+    .map(|tag| (tag.offset, tag.header))
+}
+
+/// Computes the duration of a complete FLV file in milliseconds, as the
+/// greatest audio or video tag timestamp, for files whose `onMetaData` has
+/// no `duration`. Script tags are skipped.
+///
+/// Tags are walked like in `find_tag_near`, up to the end of the file or
+/// the first tag that can't be read. Returns `None` if no media tag was
+/// found.
+pub fn compute_duration(input: &[u8]) -> Option<u32> {
+  raw_tags(input)
+    .filter(|tag| tag.header.tag_type != TagType::Script)
+    .map(|tag| tag.header.timestamp)
+    .max()
+}
+
+/// Finds the tags whose timestamp is smaller than the one of the previous
+/// tag of the same type, scanning a complete FLV file with `raw_tags`.
+/// Returns the byte offset of each such tag header in `input` and its
+/// timestamp, so nothing is allocated for a well-ordered file.
+///
+/// The scan stops at the first tag that can't be read.
+#[cfg(feature = "alloc")]
+pub fn check_monotonic(input: &[u8]) -> Vec<(usize, u32)> {
+  let mut decreasing = Vec::new();
+  // the last timestamp of audio, video and script tags
+  let mut last = [None; 3];
+
+  for tag in raw_tags(input) {
+    let timestamp = tag.header.timestamp;
+    let last = &mut last[match tag.header.tag_type {
+      TagType::Audio => 0,
+      TagType::Video => 1,
+      TagType::Script => 2,
+    }];
+    if matches!(*last, Some(previous) if timestamp < previous) {
+      decreasing.push((tag.offset, timestamp));
+    }
+    *last = Some(timestamp);
+  }
+  decreasing
+}
+
+/// Returns the byte offset in `input` of the last keyframe of `index` at or
+/// before `target_ms`, so that playback can start there without scanning
+/// the file.
+///
+/// Times before the first keyframe give the first one and times past the
+/// end the last one. `index.times` must be sorted. Returns `None` if the
+/// index is empty or its offset does not point to a video tag of `input`,
+/// as when the index is stale.
+#[cfg(feature = "std")]
+pub fn seek_to_time(input: &[u8], index: &KeyframeIndex, target_ms: u32) -> Option<usize> {
+  let target = f64::from(target_ms) / 1000.0;
+  let i = index
+    .times
+    .partition_point(|&time| time <= target)
+    .saturating_sub(1);
+  let position = *index.file_positions.get(i)?;
+  if position < 0.0 || position.fract() != 0.0 {
+    return None;
+  }
+  let offset = position as usize;
+
+  let (_, tag) = tag_header(input.get(offset..)?).ok()?;
+  if tag.tag_type != TagType::Video {
+    return None;
+  }
+  Some(offset)
+}
+
+#[allow(non_upper_case_globals)]
+#[cfg(test)]
+mod tests {
+  use super::*;
+  #[cfg(feature = "alloc")]
+  use alloc::vec;
+
+  const zelda: &[u8] = include_bytes!("../assets/zelda.flv");
+
+  #[test]
+  fn zelda_keyframes() {
+    let (offset, tag) = find_tag_near(zelda, 0).unwrap();
+    assert_eq!(offset, 13);
+    assert_eq!(tag.timestamp, 0);
+
+    let (offset, tag) = find_tag_near(zelda, 3000).unwrap();
+    assert_eq!(offset, 92680);
+    assert_eq!(tag.tag_type, TagType::Video);
+    assert_eq!(tag.timestamp, 4000);
+
+    let (offset, tag) = find_tag_near(zelda, 4000).unwrap();
+    assert_eq!(offset, 92680);
+    assert_eq!(tag.timestamp, 4000);
+
+    assert_eq!(find_tag_near(zelda, 1_000_000), None);
+  }
+
+  #[cfg(feature = "std")]
+  #[test]
+  fn seek_with_index() {
+    let index = KeyframeIndex {
+      times: vec![0.0, 2.0, 4.0],
+      file_positions: vec![13.0, 47201.0, 92680.0],
+      truncated: false,
+    };
+    assert_eq!(seek_to_time(zelda, &index, 0), Some(13));
+    assert_eq!(seek_to_time(zelda, &index, 1999), Some(13));
+    assert_eq!(seek_to_time(zelda, &index, 2000), Some(47201));
+    assert_eq!(seek_to_time(zelda, &index, 3000), Some(47201));
+    assert_eq!(seek_to_time(zelda, &index, 1_000_000), Some(92680));
+
+    // before the first keyframe
+    let index = KeyframeIndex {
+      times: vec![2.0, 4.0],
+      file_positions: vec![47201.0, 92680.0],
+      truncated: false,
+    };
+    assert_eq!(seek_to_time(zelda, &index, 0), Some(47201));
+
+    assert_eq!(seek_to_time(zelda, &KeyframeIndex::default(), 0), None);
+    let stale = KeyframeIndex {
+      times: vec![0.0],
+      file_positions: vec![14.0],
+      truncated: false,
+    };
+    assert_eq!(seek_to_time(zelda, &stale, 0), None);
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn duration() {
+    assert_eq!(compute_duration(zelda), Some(29750));
+    // the same file, cut in the middle of a tag
+    assert_eq!(compute_duration(&zelda[..92680 + 20]), Some(4000));
+
+    let empty = b"FLV\x01\x05\0\0\0\x09\0\0\0\0";
+    assert_eq!(compute_duration(empty), None);
+    assert_eq!(compute_duration(b"RIFF"), None);
+
+    // a single script tag with a timestamp
+    let mut script = empty.to_vec();
+    script.extend_from_slice(&[18, 0, 0, 1, 0, 0, 0x10, 0, 0, 0, 0, 5, 0, 0, 0, 12]);
+    assert_eq!(compute_duration(&script), None);
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn monotonic() {
+    assert_eq!(check_monotonic(zelda), vec![]);
+
+    let mut input = zelda.to_vec();
+    // the video keyframe at 3 s goes back to 0
+    input[92680 + 4..92680 + 8].fill(0);
+    assert_eq!(check_monotonic(&input), vec![(92680, 0)]);
+
+    assert_eq!(check_monotonic(b"RIFF"), vec![]);
+  }
+}
@@ -0,0 +1,196 @@
+use alloc::vec::Vec;
+
+use crate::parser::{audio_data, header, raw_tags, video_data, FrameType, SoundFormat, TagType};
+
+/// Whether a tag body is an AVC, HEVC or AAC sequence header, and if not,
+/// whether it is a video keyframe.
+fn classify(tag_type: TagType, body: &[u8]) -> (bool, bool) {
+  match tag_type {
+    TagType::Video => match video_data(body, body.len()) {
+      Ok((_, video)) => (
+        video.is_sequence_header(),
+        video.frame_type == FrameType::Key && !video.is_sequence_header(),
+      ),
+      Err(_) => (false, false),
+    },
+    TagType::Audio => match audio_data(body, body.len()) {
+      Ok((_, audio)) => (
+        audio.sound_format == SoundFormat::AAC && audio.sound_data.first() == Some(&0),
+        false,
+      ),
+      Err(_) => (false, false),
+    },
+    TagType::Script => (false, false),
+  }
+}
+
+/// Cuts a complete FLV file into self-contained FLV fragments, as needed to
+/// package it for HLS or DASH.
+///
+/// A new fragment starts at the first video keyframe at least
+/// `target_duration_ms` after the start of the current one. Each fragment
+/// is made of the original file header, then the AVC, HEVC and AAC
+/// sequence headers in effect, then the tags up to the next cut. Tags are
+/// copied byte for byte, so timestamps are not rebased. Sequence headers
+/// found within a fragment are kept in place, to catch configuration
+/// changes.
+///
+/// Parsing stops at the first tag that can't be read, the fragments that
+/// were built so far are returned. Files without video are returned as a
+/// single fragment, and files whose header `offset` is smaller than 9 give
+/// no fragment.
+pub fn segment_at_keyframes(input: &[u8], target_duration_ms: u32) -> Vec<Vec<u8>> {
+  let mut fragments = Vec::new();
+  let file_header = match header(input) {
+    // an offset within the 9 bytes of the header would truncate it
+    Ok((_, header)) if header.offset >= 9 => input.get(..header.offset as usize),
+    _ => None,
+  };
+  let file_header = match file_header {
+    Some(file_header) => file_header,
+    None => return fragments,
+  };
+
+  let mut video_sequence_header: Option<&[u8]> = None;
+  let mut audio_sequence_header: Option<&[u8]> = None;
+  let mut current: Option<(u32, Vec<u8>)> = None;
+
+  for tag in raw_tags(input) {
+    let (body, raw) = match (tag.body(), tag.bytes()) {
+      (Some(body), Some(raw)) => (body, raw),
+      _ => break,
+    };
+    let (is_sequence_header, is_keyframe) = classify(tag.header.tag_type, body);
+    if is_sequence_header {
+      match tag.header.tag_type {
+        TagType::Video => video_sequence_header = Some(raw),
+        _ => audio_sequence_header = Some(raw),
+      }
+      if let Some((_, fragment)) = current.as_mut() {
+        fragment.extend_from_slice(raw);
+      }
+      continue;
+    }
+
+    let timestamp = tag.header.timestamp;
+    let cut = match &current {
+      Some((start, _)) => is_keyframe && timestamp.saturating_sub(*start) >= target_duration_ms,
+      None => true,
+    };
+    if cut {
+      if let Some((_, fragment)) = current.take() {
+        fragments.push(fragment);
+      }
+      let mut fragment = file_header.to_vec();
+      fragment.extend_from_slice(&0u32.to_be_bytes());
+      for sequence_header in [video_sequence_header, audio_sequence_header]
+        .iter()
+        .flatten()
+      {
+        fragment.extend_from_slice(sequence_header);
+      }
+      current = Some((timestamp, fragment));
+    }
+
+    if let Some((_, fragment)) = current.as_mut() {
+      fragment.extend_from_slice(raw);
+    }
+  }
+
+  if let Some((_, fragment)) = current {
+    fragments.push(fragment);
+  }
+  fragments
+}
+
+#[allow(non_upper_case_globals)]
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::parse_flv;
+  use alloc::vec;
+
+  const zelda: &[u8] = include_bytes!("../assets/zelda.flv");
+  const commercials: &[u8] = include_bytes!("../assets/asian-commercials-are-weird.flv");
+
+  fn tag(tag_type: u8, timestamp: u32, body: &[u8]) -> Vec<u8> {
+    let size = body.len() as u32;
+    let mut tag = vec![tag_type];
+    tag.extend_from_slice(&size.to_be_bytes()[1..]);
+    tag.extend_from_slice(&timestamp.to_be_bytes()[1..]);
+    tag.extend_from_slice(&[0; 4]);
+    tag.extend_from_slice(body);
+    tag.extend_from_slice(&(11 + size).to_be_bytes());
+    tag
+  }
+
+  #[test]
+  fn sample_files() {
+    // keyframes every 2 seconds, with a few irregular ones
+    let fragments = segment_at_keyframes(commercials, 4000);
+    let starts = [0, 4000, 8000, 12000, 16966, 20966, 24966];
+    assert_eq!(fragments.len(), starts.len());
+
+    let mut count = 0;
+    for (fragment, start) in fragments.iter().zip(starts) {
+      let (rest, (_, tags)) = parse_flv(fragment).unwrap();
+      assert!(rest.is_empty());
+      let first_video = tags
+        .iter()
+        .find(|tag| tag.header.tag_type == TagType::Video)
+        .unwrap();
+      assert_eq!(first_video.header.timestamp, start);
+      count += tags.len();
+    }
+    assert_eq!(count, 1923);
+
+    assert_eq!(segment_at_keyframes(zelda, 0).len(), 15);
+    assert_eq!(segment_at_keyframes(zelda, u32::MAX), vec![zelda.to_vec()]);
+  }
+
+  #[test]
+  fn sequence_headers() {
+    let avc_config = tag(9, 0, &[0x17, 0, 0, 0, 0, 1, 0x64, 0, 0x1f]);
+    let aac_config = tag(8, 0, &[0xaf, 0, 0x12, 0x10]);
+
+    let mut flv = b"FLV\x01\x05\x00\x00\x00\x09\x00\x00\x00\x00".to_vec();
+    flv.extend(&avc_config);
+    flv.extend(&aac_config);
+    flv.extend(tag(9, 0, &[0x17, 1, 0, 0, 0, 0xaa]));
+    flv.extend(tag(8, 20, &[0xaf, 1, 0xbb]));
+    flv.extend(tag(9, 40, &[0x27, 1, 0, 0, 0, 0xcc]));
+    flv.extend(tag(9, 80, &[0x17, 1, 0, 0, 0, 0xdd]));
+    flv.extend(tag(8, 90, &[0xaf, 1, 0xee]));
+
+    let fragments = segment_at_keyframes(&flv, 50);
+    assert_eq!(fragments.len(), 2);
+    // the last video and audio tags are 21 and 18 bytes long
+    assert_eq!(fragments[0], flv[..flv.len() - 21 - 18]);
+
+    let mut second = b"FLV\x01\x05\x00\x00\x00\x09\x00\x00\x00\x00".to_vec();
+    second.extend(&avc_config);
+    second.extend(&aac_config);
+    second.extend(tag(9, 80, &[0x17, 1, 0, 0, 0, 0xdd]));
+    second.extend(tag(8, 90, &[0xaf, 1, 0xee]));
+    assert_eq!(fragments[1], second);
+
+    // legacy HEVC
+    let hevc_config = tag(9, 0, &[0x1c, 0, 0, 0, 0, 1]);
+    let mut flv = b"FLV\x01\x01\x00\x00\x00\x09\x00\x00\x00\x00".to_vec();
+    flv.extend(&hevc_config);
+    flv.extend(tag(9, 0, &[0x1c, 1, 0, 0, 0, 0xaa]));
+    flv.extend(tag(9, 80, &[0x1c, 1, 0, 0, 0, 0xbb]));
+
+    let fragments = segment_at_keyframes(&flv, 50);
+    assert_eq!(fragments.len(), 2);
+
+    let mut short_offset = flv.clone();
+    short_offset[8] = 5;
+    assert!(segment_at_keyframes(&short_offset, 50).is_empty());
+
+    let mut second = b"FLV\x01\x01\x00\x00\x00\x09\x00\x00\x00\x00".to_vec();
+    second.extend(&hevc_config);
+    second.extend(tag(9, 80, &[0x1c, 1, 0, 0, 0, 0xbb]));
+    assert_eq!(fragments[1], second);
+  }
+}
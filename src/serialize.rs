@@ -0,0 +1,512 @@
+use std::io::Write;
+
+use cookie_factory::bytes::{be_f64, be_i16, be_u16, be_u24, be_u32, be_u8};
+use cookie_factory::combinator::{slice, string};
+use cookie_factory::sequence::tuple;
+use cookie_factory::{GenError, GenResult, SerializeFn, WriteContext};
+
+use crate::amf3::{AMF3Property, AMF3Value};
+use crate::parser::{
+  Header, ScriptData, ScriptDataObject, ScriptDataValue, Tag, TagData, TagHeader,
+};
+
+/// Writes the 9-byte FLV file header. This is the inverse of `header`.
+pub fn write_header<'a, W: Write + 'a>(header: &'a Header) -> impl SerializeFn<W> + 'a {
+  let flags = if header.audio { 4 } else { 0 } | if header.video { 1 } else { 0 };
+  tuple((
+    slice(b"FLV"),
+    be_u8(header.version),
+    be_u8(flags),
+    be_u32(header.offset),
+  ))
+}
+
+/// Writes the 11-byte tag header. This is the inverse of `tag_header`: the
+/// timestamp is split into its lower 24 bits and its extended upper 8 bits.
+///
+/// `data_size` and `stream_id` are written on 24 bits, higher bits are
+/// dropped.
+pub fn write_tag_header<'a, W: Write + 'a>(header: &'a TagHeader) -> impl SerializeFn<W> + 'a {
+  let filter = if header.filtered { 0x20 } else { 0 };
+  tuple((
+    be_u8(u8::from(header.tag_type) | filter),
+    be_u24(header.data_size),
+    be_u24(header.timestamp & 0x00ff_ffff),
+    be_u8((header.timestamp >> 24) as u8),
+    be_u24(header.stream_id),
+  ))
+}
+
+/// Error code returned in `GenError::CustomError` when a string does not fit
+/// its AMF0 length prefix (16 bits, or 32 bits for long strings).
+pub const STRING_TOO_LONG: u32 = 1;
+
+/// Error code returned in `GenError::CustomError` when a video tag has a
+/// codec that is only signaled by a FourCC, like VP9 or AV1, see
+/// `VideoData::raw_nibbles`.
+pub const NO_CODEC_ID: u32 = 2;
+
+/// Writes a complete tag: its header, then its body, followed by the
+/// PreviousTagSize of `11 + data_size` if `previous_tag_size` is set. This is
+/// the inverse of `complete_tag`.
+///
+/// The `data_size` written is the size of the body, whatever the one of
+/// `tag.header`. Video tags with a codec that has no codec id fail with
+/// `NO_CODEC_ID`.
+pub fn write_tag<'a, W: Write + 'a>(
+  tag: &'a Tag<'a>,
+  previous_tag_size: bool,
+) -> impl SerializeFn<W> + 'a {
+  move |out| {
+    let (first, rest) = match &tag.data {
+      TagData::Audio(audio) => (audio.raw_flags(), audio.sound_data),
+      TagData::Video(video) => {
+        let (frame_type, codec_id) = video
+          .raw_nibbles()
+          .map_err(|_| GenError::CustomError(NO_CODEC_ID))?;
+        ((frame_type << 4) | codec_id, video.video_data)
+      }
+      TagData::Script(body) | TagData::ExAudio(body) | TagData::ExVideo(body) => {
+        let header = TagHeader {
+          data_size: body.len() as u32,
+          ..tag.header.clone()
+        };
+        let out = tuple((write_tag_header(&header), slice(body)))(out)?;
+        return if previous_tag_size {
+          be_u32(11 + header.data_size)(out)
+        } else {
+          Ok(out)
+        };
+      }
+    };
+    let header = TagHeader {
+      data_size: 1 + rest.len() as u32,
+      ..tag.header.clone()
+    };
+
+    let out = tuple((write_tag_header(&header), be_u8(first), slice(rest)))(out)?;
+    if previous_tag_size {
+      be_u32(11 + header.data_size)(out)
+    } else {
+      Ok(out)
+    }
+  }
+}
+
+fn write_string<W: Write>(s: &str, out: WriteContext<W>) -> GenResult<W> {
+  let len = u16::try_from(s.len()).map_err(|_| GenError::CustomError(STRING_TOO_LONG))?;
+  tuple((be_u16(len), string(s)))(out)
+}
+
+fn write_long_string<W: Write>(s: &str, out: WriteContext<W>) -> GenResult<W> {
+  let len = u32::try_from(s.len()).map_err(|_| GenError::CustomError(STRING_TOO_LONG))?;
+  tuple((be_u32(len), string(s)))(out)
+}
+
+fn write_objects<W: Write>(objects: &[ScriptDataObject<'_>], out: WriteContext<W>) -> GenResult<W> {
+  let mut out = out;
+  for object in objects {
+    out = write_string(object.name, out)?;
+    out = write_value(&object.data, out)?;
+  }
+  // object end marker
+  tuple((be_u16(0), be_u8(9)))(out)
+}
+
+fn write_value<W: Write>(value: &ScriptDataValue<'_>, out: WriteContext<W>) -> GenResult<W> {
+  match value {
+    ScriptDataValue::Number(n) => tuple((be_u8(0), be_f64(*n)))(out),
+    ScriptDataValue::Boolean(b) => tuple((be_u8(1), be_u8(u8::from(*b))))(out),
+    ScriptDataValue::String(s) => write_string(s, be_u8(2)(out)?),
+    ScriptDataValue::Object(objects) => write_objects(objects, be_u8(3)(out)?),
+    ScriptDataValue::MovieClip(s) => write_string(s, be_u8(4)(out)?),
+    ScriptDataValue::Null => be_u8(5)(out),
+    ScriptDataValue::Undefined => be_u8(6)(out),
+    ScriptDataValue::Reference(r) => tuple((be_u8(7), be_u16(*r)))(out),
+    ScriptDataValue::ECMAArray { count, entries } => {
+      let out = tuple((be_u8(8), be_u32(*count)))(out)?;
+      write_objects(entries, out)
+    }
+    ScriptDataValue::StrictArray(values) => {
+      let mut out = tuple((be_u8(10), be_u32(values.len() as u32)))(out)?;
+      for value in values {
+        out = write_value(value, out)?;
+      }
+      Ok(out)
+    }
+    ScriptDataValue::Date(date) => tuple((
+      be_u8(11),
+      be_f64(date.date_time),
+      be_i16(date.local_date_time_offset),
+    ))(out),
+    ScriptDataValue::LongString(s) => write_long_string(s, be_u8(12)(out)?),
+    ScriptDataValue::TypedObject {
+      class_name,
+      properties,
+    } => {
+      let out = write_string(class_name, be_u8(16)(out)?)?;
+      write_objects(properties, out)
+    }
+    ScriptDataValue::AMF3(value) => write_amf3(value, be_u8(17)(out)?),
+    ScriptDataValue::RawString(bytes) => match u16::try_from(bytes.len()) {
+      Ok(len) => tuple((be_u8(2), be_u16(len), slice(bytes)))(out),
+      Err(_) => {
+        let len = u32::try_from(bytes.len()).map_err(|_| GenError::CustomError(STRING_TOO_LONG))?;
+        tuple((be_u8(12), be_u32(len), slice(bytes)))(out)
+      }
+    },
+  }
+}
+
+fn write_u29<W: Write>(n: u32, out: WriteContext<W>) -> GenResult<W> {
+  match n {
+    0..=0x7f => be_u8(n as u8)(out),
+    0x80..=0x3fff => tuple((be_u8((n >> 7) as u8 | 0x80), be_u8(n as u8 & 0x7f)))(out),
+    0x4000..=0x1f_ffff => tuple((
+      be_u8((n >> 14) as u8 | 0x80),
+      be_u8((n >> 7) as u8 | 0x80),
+      be_u8(n as u8 & 0x7f),
+    ))(out),
+    _ => tuple((
+      be_u8((n >> 22) as u8 | 0x80),
+      be_u8((n >> 15) as u8 | 0x80),
+      be_u8((n >> 8) as u8 | 0x80),
+      be_u8(n as u8),
+    ))(out),
+  }
+}
+
+fn write_amf3_string<W: Write>(s: &str, out: WriteContext<W>) -> GenResult<W> {
+  write_amf3_bytes(s.as_bytes(), out)
+}
+
+fn write_amf3_bytes<W: Write>(bytes: &[u8], out: WriteContext<W>) -> GenResult<W> {
+  if bytes.len() >= 1 << 28 {
+    return Err(GenError::CustomError(STRING_TOO_LONG));
+  }
+  let out = write_u29(((bytes.len() as u32) << 1) | 1, out)?;
+  slice(bytes)(out)
+}
+
+fn write_amf3_properties<W: Write>(
+  properties: &[AMF3Property<'_>],
+  out: WriteContext<W>,
+) -> GenResult<W> {
+  let mut out = out;
+  for property in properties {
+    out = write_amf3_string(property.name, out)?;
+    out = write_amf3(&property.value, out)?;
+  }
+  write_amf3_string("", out)
+}
+
+/// Strings and traits are always written inline, never as references.
+fn write_amf3<W: Write>(value: &AMF3Value<'_>, out: WriteContext<W>) -> GenResult<W> {
+  match value {
+    AMF3Value::Undefined => be_u8(0)(out),
+    AMF3Value::Null => be_u8(1)(out),
+    AMF3Value::Boolean(b) => be_u8(2 + u8::from(*b))(out),
+    // like Flash Player, integers out of the 29-bit range are sent as doubles
+    AMF3Value::Integer(n) if (-(1 << 28)..1 << 28).contains(n) => {
+      write_u29(*n as u32 & 0x1fff_ffff, be_u8(4)(out)?)
+    }
+    AMF3Value::Integer(n) => tuple((be_u8(5), be_f64(f64::from(*n))))(out),
+    AMF3Value::Double(n) => tuple((be_u8(5), be_f64(*n)))(out),
+    AMF3Value::String(s) => write_amf3_string(s, be_u8(6)(out)?),
+    AMF3Value::RawString(bytes) => write_amf3_bytes(bytes, be_u8(6)(out)?),
+    AMF3Value::Array { associative, dense } => {
+      let out = write_u29(((dense.len() as u32) << 1) | 1, be_u8(9)(out)?)?;
+      let mut out = write_amf3_properties(associative, out)?;
+      for value in dense {
+        out = write_amf3(value, out)?;
+      }
+      Ok(out)
+    }
+    AMF3Value::Object {
+      class_name,
+      sealed,
+      dynamic,
+    } => {
+      let flags = if dynamic.is_some() { 0x0b } else { 0x03 };
+      let out = write_u29(((sealed.len() as u32) << 4) | flags, be_u8(10)(out)?)?;
+      let mut out = write_amf3_string(class_name, out)?;
+      for property in sealed {
+        out = write_amf3_string(property.name, out)?;
+      }
+      for property in sealed {
+        out = write_amf3(&property.value, out)?;
+      }
+      match dynamic {
+        Some(properties) => write_amf3_properties(properties, out),
+        None => Ok(out),
+      }
+    }
+    // array and object references share the object table
+    AMF3Value::Reference(index) => write_u29(index << 1, be_u8(10)(out)?),
+  }
+}
+
+/// Writes an AMF0 value, type marker included. This is the inverse of
+/// `script_data_value`: objects end with the `0, 0, 9` terminator, and ECMA
+/// arrays are prefixed with their number of properties.
+pub fn write_script_data_value<'a, W: Write + 'a>(
+  value: &'a ScriptDataValue<'a>,
+) -> impl SerializeFn<W> + 'a {
+  move |out| write_value(value, out)
+}
+
+/// Writes a script tag body: the name as an AMF0 string, then the arguments.
+/// This is the inverse of `script_data`.
+///
+/// Object and ECMA array properties are written in `Vec` order, so a parsed
+/// tag is re-emitted with its original key order, which some players rely
+/// on.
+pub fn write_script_data<'a, W: Write + 'a>(
+  script_data: &'a ScriptData<'a>,
+) -> impl SerializeFn<W> + 'a {
+  move |out| {
+    let out = write_string(script_data.name, be_u8(2)(out)?)?;
+    write_value(&script_data.arguments, out)
+  }
+}
+
+#[allow(non_upper_case_globals)]
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::amf3::script_data_amf3_value;
+  use crate::parser::{
+    complete_tag, header, raw_tags, script_data, script_data_value, skip_to_first_tag, tag_header,
+    CodecId, ScriptDataDate, TagType,
+  };
+  use cookie_factory::gen_simple;
+
+  const zelda: &[u8] = include_bytes!("../assets/zelda.flv");
+  const commercials: &[u8] = include_bytes!("../assets/asian-commercials-are-weird.flv");
+
+  #[test]
+  fn script_data_value_round_trip() {
+    let value = ScriptDataValue::Object(vec![
+      ScriptDataObject {
+        name: "number",
+        data: ScriptDataValue::Number(1.5),
+      },
+      ScriptDataObject {
+        name: "array",
+        data: ScriptDataValue::StrictArray(vec![
+          ScriptDataValue::Boolean(true),
+          ScriptDataValue::String("string"),
+          ScriptDataValue::LongString("long string"),
+        ]),
+      },
+      ScriptDataObject {
+        name: "date",
+        data: ScriptDataValue::Date(ScriptDataDate {
+          date_time: 1e12,
+          local_date_time_offset: -60,
+        }),
+      },
+      ScriptDataObject {
+        name: "typed",
+        data: ScriptDataValue::TypedObject {
+          class_name: "Point",
+          properties: vec![ScriptDataObject {
+            name: "x",
+            data: ScriptDataValue::Number(1.0),
+          }],
+        },
+      },
+      ScriptDataObject {
+        name: "ecma",
+        data: ScriptDataValue::ecma_array(vec![ScriptDataObject {
+          name: "ref",
+          data: ScriptDataValue::Reference(3),
+        }]),
+      },
+    ]);
+
+    let out = gen_simple(write_script_data_value(&value), Vec::new()).unwrap();
+    assert_eq!(script_data_value(&out), Ok((&b""[..], value)));
+  }
+
+  #[test]
+  fn string_too_long() {
+    let long = "a".repeat(70_000);
+    let value = ScriptDataValue::String(&long);
+    assert!(matches!(
+      gen_simple(write_script_data_value(&value), Vec::new()),
+      Err(GenError::CustomError(STRING_TOO_LONG))
+    ));
+  }
+
+  #[test]
+  fn on_metadata_round_trip() {
+    let body = raw_tags(commercials).next().unwrap().body().unwrap();
+    let (_, parsed) = script_data(body).unwrap();
+    let out = gen_simple(write_script_data(&parsed), Vec::new()).unwrap();
+
+    // the ECMA array count is 7 for 11 properties, and is kept as is
+    assert_eq!(out[14..18], [0, 0, 0, 7]);
+    assert_eq!(out, body);
+  }
+
+  #[test]
+  fn header_round_trip() {
+    let (_, parsed) = header(zelda).unwrap();
+    let out = gen_simple(write_header(&parsed), Vec::new()).unwrap();
+    assert_eq!(out, zelda[..9]);
+    assert_eq!(header(&out).unwrap().1, parsed);
+
+    let video_only = Header {
+      version: 1,
+      audio: false,
+      video: true,
+      offset: 9,
+    };
+    let out = gen_simple(write_header(&video_only), Vec::new()).unwrap();
+    assert_eq!(header(&out).unwrap().1, video_only);
+  }
+
+  #[test]
+  fn tag_header_round_trip() {
+    let sizes = [0, 1, 0xff, 0x1234, 0x00ff_ffff];
+    let timestamps = [
+      0,
+      1,
+      0x00ff_ffff,
+      0x0100_0000,
+      0x7fff_ffff,
+      0xff00_00ff,
+      u32::MAX,
+    ];
+    for &tag_type in &[TagType::Audio, TagType::Video, TagType::Script] {
+      for &data_size in &sizes {
+        for &timestamp in &timestamps {
+          for &stream_id in &sizes {
+            for &filtered in &[false, true] {
+              let header = TagHeader {
+                tag_type,
+                data_size,
+                timestamp,
+                stream_id,
+                filtered,
+              };
+              let out = gen_simple(write_tag_header(&header), Vec::new()).unwrap();
+              assert_eq!(out.len(), 11);
+              assert_eq!(tag_header(&out), Ok((&b""[..], header)));
+            }
+          }
+        }
+      }
+    }
+
+    let first = skip_to_first_tag(zelda, &header(zelda).unwrap().1);
+    assert_eq!(
+      gen_simple(write_tag_header(&tag_header(first).unwrap().1), Vec::new()).unwrap(),
+      first[..11]
+    );
+  }
+
+  #[test]
+  fn tag_round_trip() {
+    // the first video tag of zelda and its PreviousTagSize
+    let raw = raw_tags(zelda).next().unwrap().bytes().unwrap();
+    let (_, tag) = complete_tag(raw).unwrap();
+
+    let out = gen_simple(write_tag(&tag, true), Vec::new()).unwrap();
+    assert_eq!(out, raw);
+    assert_eq!(out[11], 0x12);
+    assert_eq!(out[out.len() - 4..], (11u32 + 537).to_be_bytes());
+
+    let out = gen_simple(write_tag(&tag, false), Vec::new()).unwrap();
+    assert_eq!(out, raw[..raw.len() - 4]);
+
+    // VP9 has no codec id for the legacy video header
+    let mut vp9 = tag;
+    if let TagData::Video(video) = &mut vp9.data {
+      video.codec_id = CodecId::VP9;
+    }
+    assert!(matches!(
+      gen_simple(write_tag(&vp9, true), Vec::new()),
+      Err(GenError::CustomError(NO_CODEC_ID))
+    ));
+
+    // the onMetaData tag of commercials, whose ECMA array count is not the
+    // number of properties
+    let raw = raw_tags(commercials).next().unwrap().bytes().unwrap();
+    let (_, script) = complete_tag(raw).unwrap();
+    let out = gen_simple(write_tag(&script, true), Vec::new()).unwrap();
+    assert_eq!(out, raw);
+  }
+
+  #[test]
+  fn amf3_round_trip() {
+    let property = |name, value| AMF3Property { name, value };
+    let value = AMF3Value::Array {
+      associative: vec![property("name", AMF3Value::String("flavors"))],
+      dense: vec![
+        AMF3Value::Undefined,
+        AMF3Value::Null,
+        AMF3Value::Boolean(true),
+        AMF3Value::Integer(-1),
+        AMF3Value::Integer(0x3fff),
+        AMF3Value::Integer(0x0fff_ffff),
+        AMF3Value::Double(0.5),
+        AMF3Value::Object {
+          class_name: "Point",
+          sealed: vec![property("x", AMF3Value::Integer(1))],
+          dynamic: None,
+        },
+        AMF3Value::Object {
+          class_name: "",
+          sealed: vec![],
+          dynamic: Some(vec![property("y", AMF3Value::Integer(2))]),
+        },
+        AMF3Value::Reference(0),
+      ],
+    };
+    let script = ScriptDataValue::AMF3(value);
+    let out = gen_simple(write_script_data_value(&script), Vec::new()).unwrap();
+    assert_eq!(out[0], 17);
+    assert_eq!(script_data_value(&out), Ok((&b""[..], script)));
+
+    // out of the 29-bit range
+    let out = gen_simple(
+      write_script_data_value(&ScriptDataValue::AMF3(AMF3Value::Integer(1 << 28))),
+      Vec::new(),
+    )
+    .unwrap();
+    assert_eq!(
+      script_data_amf3_value(&out[1..]),
+      Ok((&b""[..], AMF3Value::Double(f64::from(1 << 28))))
+    );
+  }
+
+  #[test]
+  fn null_and_undefined_round_trip() {
+    for (value, marker) in [(ScriptDataValue::Null, 5), (ScriptDataValue::Undefined, 6)] {
+      let out = gen_simple(write_script_data_value(&value), Vec::new()).unwrap();
+      assert_eq!(out, [marker]);
+      // only the marker is consumed
+      let input = [marker, 0xff];
+      assert_eq!(script_data_value(&input), Ok((&[0xff][..], value)));
+    }
+  }
+
+  #[test]
+  fn commercials_metadata_value_round_trip() {
+    let body = raw_tags(commercials).next().unwrap().body().unwrap();
+    let (_, parsed) = script_data(body).unwrap();
+    let out = gen_simple(write_script_data_value(&parsed.arguments), Vec::new()).unwrap();
+    assert_eq!(out[0], 8);
+    assert_eq!(out[out.len() - 3..], [0, 0, 9]);
+    assert_eq!(script_data_value(&out), Ok((&b""[..], parsed.arguments)));
+  }
+
+  #[test]
+  fn raw_string_round_trip() {
+    let value = ScriptDataValue::RawString(b"caf\xe9");
+    let out = gen_simple(write_script_data_value(&value), Vec::new()).unwrap();
+    assert_eq!(out, b"\x02\x00\x04caf\xe9");
+  }
+}
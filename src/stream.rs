@@ -0,0 +1,310 @@
+use std::io::{self, Read};
+#[cfg(feature = "futures")]
+use std::pin::Pin;
+#[cfg(feature = "futures")]
+use std::task::{Context, Poll};
+
+#[cfg(feature = "futures")]
+use futures_core::Stream;
+#[cfg(feature = "futures")]
+use futures_io::AsyncRead;
+use nom::Err;
+#[cfg(feature = "futures")]
+use nom::Needed;
+
+use crate::error::FlvError;
+#[cfg(feature = "futures")]
+use crate::owned::OwnedTag;
+use crate::parser::{announced, body_start, previous_tag_size, Header, Parser, ParserOptions, Tag};
+
+/// Incremental FLV parser for data arriving in chunks, like partial reads
+/// from a socket.
+///
+/// Data is appended with `push` or `read_from`, and tags taken out with
+/// `next_tag`, which parses the file header first, and skips the
+/// PreviousTagSize fields.
+#[derive(Debug, Default)]
+pub struct FlvParser {
+  buffer: Vec<u8>,
+  /// Start of the data not parsed yet in `buffer`.
+  position: usize,
+  header: Option<Header>,
+  parser: Parser,
+}
+
+/// Turns an incomplete parse into `Ok(None)`.
+fn complete<T>(result: Result<T, Err<FlvError>>) -> Result<Option<T>, FlvError> {
+  match result {
+    Ok(value) => Ok(Some(value)),
+    Err(Err::Incomplete(_)) => Ok(None),
+    Err(e) => Err(e.into()),
+  }
+}
+
+impl FlvParser {
+  pub fn new() -> FlvParser {
+    FlvParser::default()
+  }
+
+  /// A parser applying `options` like `Parser::with_options`. With
+  /// `ParserOptions::allow_truncated_tag`, an `FlvStream` ending in the
+  /// middle of a tag ends without an error.
+  pub fn with_options(options: ParserOptions) -> FlvParser {
+    FlvParser {
+      parser: Parser::with_options(options),
+      ..FlvParser::default()
+    }
+  }
+
+  /// Appends data to the internal buffer.
+  pub fn push(&mut self, data: &[u8]) {
+    self.buffer.drain(..self.position);
+    self.position = 0;
+    self.buffer.extend_from_slice(data);
+  }
+
+  /// Reads one chunk from `reader` into the internal buffer, returning the
+  /// number of bytes read, 0 meaning the end of the stream.
+  pub fn read_from<R: Read>(&mut self, reader: &mut R) -> io::Result<usize> {
+    let mut chunk = [0; 8192];
+    let read = reader.read(&mut chunk)?;
+    self.push(&chunk[..read]);
+    Ok(read)
+  }
+
+  /// Parses the file header and the first PreviousTagSize if that was not
+  /// done yet. Returns `Ok(None)` if more data is needed.
+  pub fn header(&mut self) -> Result<Option<&Header>, FlvError> {
+    if self.header.is_none() {
+      let input = &self.buffer[self.position..];
+      let parsed = match complete(self.parser.header(input))? {
+        Some((_, parsed)) => parsed,
+        None => return Ok(None),
+      };
+      let size = body_start(&parsed);
+      if input.len() < size {
+        return Ok(None);
+      }
+      if self.parser.options().strict_initial_tag_size {
+        let (_, initial) = previous_tag_size(&input[size - 4..])?;
+        if initial != 0 {
+          return Err(FlvError::NonZeroInitialTagSize(initial));
+        }
+      }
+      self.position += size;
+      self.header = Some(parsed);
+    }
+    Ok(self.header.as_ref())
+  }
+
+  /// Parses the next tag, along with the PreviousTagSize following it.
+  /// Returns `Ok(None)` if more data is needed, and an error if the data is
+  /// not a valid tag, after which the parser should not be used anymore.
+  pub fn next_tag(&mut self) -> Result<Option<Tag<'_>>, FlvError> {
+    if self.header()?.is_none() {
+      return Ok(None);
+    }
+
+    let start = self.position;
+    let tag_header = match complete(self.parser.tag_header(&self.buffer[start..]))? {
+      Some((_, tag_header)) => tag_header,
+      None => return Ok(None),
+    };
+    if let Some(header) = &self.header {
+      if self.parser.options().strict_tag_types && !announced(header, tag_header.tag_type) {
+        return Err(FlvError::UnexpectedTagType(tag_header.tag_type));
+      }
+    }
+    let size = 11 + tag_header.data_size as usize;
+    if self.buffer.len() < start + size + 4 {
+      return Ok(None);
+    }
+    self.position += size + 4;
+
+    let (_, tag) = self
+      .parser
+      .complete_tag(&self.buffer[start..start + size])?;
+    Ok(Some(tag))
+  }
+}
+
+/// Asynchronous stream of the tags read from `reader`, with a `FlvParser`.
+///
+/// Read errors are reported as `FlvError::Io`, and a stream ending in the
+/// middle of a tag as `FlvError::Incomplete`. The stream ends after the
+/// first error. Tokio readers can be adapted with `tokio_util::compat`.
+#[cfg(feature = "futures")]
+#[derive(Debug)]
+pub struct FlvStream<R> {
+  reader: R,
+  parser: FlvParser,
+  done: bool,
+}
+
+#[cfg(feature = "futures")]
+impl<R: AsyncRead + Unpin> FlvStream<R> {
+  pub fn new(reader: R) -> FlvStream<R> {
+    FlvStream::with_parser(reader, FlvParser::new())
+  }
+
+  /// A stream reading tags with `parser`, like one from
+  /// `FlvParser::with_options`.
+  pub fn with_parser(reader: R, parser: FlvParser) -> FlvStream<R> {
+    FlvStream {
+      reader,
+      parser,
+      done: false,
+    }
+  }
+
+  /// The file header, once it was read.
+  pub fn header(&self) -> Option<&Header> {
+    self.parser.header.as_ref()
+  }
+}
+
+#[cfg(feature = "futures")]
+impl<R: AsyncRead + Unpin> Stream for FlvStream<R> {
+  type Item = Result<OwnedTag, FlvError>;
+
+  fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    let this = self.get_mut();
+    let mut chunk = [0; 8192];
+    while !this.done {
+      let error = match this.parser.next_tag() {
+        Ok(Some(tag)) => return Poll::Ready(Some(Ok(tag.into()))),
+        Ok(None) => match Pin::new(&mut this.reader).poll_read(cx, &mut chunk) {
+          Poll::Pending => return Poll::Pending,
+          Poll::Ready(Ok(0)) if this.parser.position == this.parser.buffer.len() => None,
+          Poll::Ready(Ok(0)) if this.parser.parser.options().allow_truncated_tag => None,
+          Poll::Ready(Ok(0)) => Some(FlvError::Incomplete(Needed::Unknown)),
+          Poll::Ready(Ok(read)) => {
+            this.parser.push(&chunk[..read]);
+            continue;
+          }
+          Poll::Ready(Err(e)) => Some(FlvError::Io(e.kind())),
+        },
+        Err(e) => Some(e),
+      };
+      this.done = true;
+      if let Some(error) = error {
+        return Poll::Ready(Some(Err(error)));
+      }
+    }
+    Poll::Ready(None)
+  }
+}
+
+#[allow(non_upper_case_globals)]
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::TagType;
+
+  const zelda: &[u8] = include_bytes!("../assets/zelda.flv");
+  const commercials: &[u8] = include_bytes!("../assets/asian-commercials-are-weird.flv");
+
+  #[test]
+  fn chunked_input() {
+    let mut parser = FlvParser::new();
+    let mut timestamps = Vec::new();
+
+    // chunks that split headers and bodies at odd places
+    for chunk in zelda.chunks(1000) {
+      parser.push(chunk);
+      while let Some(tag) = parser.next_tag().unwrap() {
+        timestamps.push(tag.header.timestamp);
+      }
+    }
+
+    assert_eq!(timestamps.len(), 998);
+    assert_eq!(timestamps.last(), Some(&29675));
+    assert_eq!(parser.header().unwrap().map(|h| h.offset), Some(9));
+  }
+
+  #[test]
+  fn from_reader() {
+    let mut parser = FlvParser::new();
+    let mut reader = commercials;
+    let mut count = 0;
+    while parser.read_from(&mut reader).unwrap() > 0 {
+      while parser.next_tag().unwrap().is_some() {
+        count += 1;
+      }
+    }
+    assert_eq!(count, 1923);
+  }
+
+  #[test]
+  fn incomplete_and_invalid() {
+    let mut parser = FlvParser::new();
+    parser.push(&zelda[..5]);
+    assert_eq!(parser.header(), Ok(None));
+    assert_eq!(parser.next_tag(), Ok(None));
+
+    parser.push(&zelda[5..13]);
+    assert!(parser.header().unwrap().is_some());
+    assert_eq!(parser.next_tag(), Ok(None));
+
+    parser.push(&[7; 20]);
+    assert!(parser.next_tag().is_err());
+  }
+
+  #[test]
+  fn parser_options() {
+    let mut mislabeled = zelda.to_vec();
+    // a video only file, whose first PreviousTagSize is not 0
+    mislabeled[4] = 0x01;
+    mislabeled[12] = 0x20;
+
+    let mut options = ParserOptions {
+      strict_tag_types: true,
+      ..ParserOptions::default()
+    };
+    let mut parser = FlvParser::with_options(options);
+    parser.push(&mislabeled);
+    assert!(parser.next_tag().unwrap().is_some());
+    assert_eq!(
+      parser.next_tag(),
+      Err(FlvError::UnexpectedTagType(TagType::Audio))
+    );
+
+    options.strict_initial_tag_size = true;
+    let mut parser = FlvParser::with_options(options);
+    parser.push(&mislabeled);
+    assert_eq!(parser.header(), Err(FlvError::NonZeroInitialTagSize(0x20)));
+  }
+
+  #[cfg(feature = "futures")]
+  #[test]
+  fn async_stream() {
+    use futures_executor::block_on_stream;
+
+    let tags: Vec<_> = block_on_stream(FlvStream::new(zelda)).collect();
+    assert_eq!(tags.len(), 998);
+    assert!(tags.iter().all(|tag| tag.is_ok()));
+    assert_eq!(
+      tags.last().unwrap().as_ref().unwrap().header.timestamp,
+      29675
+    );
+
+    let mut stream = FlvStream::new(&commercials[..1000]);
+    assert!(stream.header().is_none());
+    let tags: Vec<_> = block_on_stream(&mut stream).collect();
+    assert_eq!(stream.header().map(|h| h.offset), Some(9));
+    assert!(tags[..tags.len() - 1].iter().all(|tag| tag.is_ok()));
+    assert_eq!(
+      tags.last(),
+      Some(&Err(FlvError::Incomplete(Needed::Unknown)))
+    );
+
+    let parser = FlvParser::with_options(ParserOptions {
+      allow_truncated_tag: true,
+      ..ParserOptions::default()
+    });
+    let truncated: Vec<_> =
+      block_on_stream(FlvStream::with_parser(&commercials[..1000], parser)).collect();
+    assert_eq!(truncated.len(), tags.len() - 1);
+    assert!(truncated.iter().all(|tag| tag.is_ok()));
+  }
+}
@@ -0,0 +1,284 @@
+use nom::error::ErrorKind;
+use nom::number::streaming::{be_f64, be_u16, be_u32, be_u8};
+use nom::{Err, IResult};
+
+#[cfg(feature = "alloc")]
+use crate::amf3::{script_data_amf3_value, AMF3Value};
+use crate::error::FlvError;
+use crate::parser::{
+  script_data_date, script_data_long_string, script_data_object_end, script_data_string,
+  ScriptDataDate,
+};
+
+/// Callbacks for `visit_script_data`, called in the order values appear in
+/// the script data. Every method does nothing by default.
+///
+/// `name` is the property name for values in objects and ECMA arrays, and
+/// `None` for the arguments and strict array elements.
+pub trait ScriptDataVisitor<'a> {
+  fn on_number(&mut self, _name: Option<&'a str>, _value: f64) {}
+  fn on_boolean(&mut self, _name: Option<&'a str>, _value: bool) {}
+  /// Strings, long strings and movie clips.
+  fn on_string(&mut self, _name: Option<&'a str>, _value: &'a str) {}
+  fn on_null(&mut self, _name: Option<&'a str>) {}
+  fn on_undefined(&mut self, _name: Option<&'a str>) {}
+  fn on_reference(&mut self, _name: Option<&'a str>, _index: u16) {}
+  fn on_date(&mut self, _name: Option<&'a str>, _date: ScriptDataDate) {}
+  /// An AMF3 value, which is parsed whole.
+  #[cfg(feature = "alloc")]
+  fn on_amf3(&mut self, _name: Option<&'a str>, _value: AMF3Value<'a>) {}
+  /// The start of an object, an ECMA array, or a typed object with its
+  /// `class_name`. Its properties follow, up to `exit_object`.
+  fn enter_object(&mut self, _name: Option<&'a str>, _class_name: Option<&'a str>) {}
+  fn exit_object(&mut self) {}
+  /// The start of a strict array of `len` elements, up to `exit_array`.
+  fn enter_array(&mut self, _name: Option<&'a str>, _len: u32) {}
+  fn exit_array(&mut self) {}
+}
+
+/// Walks script data like `script_data`, calling `visitor` for each value
+/// instead of building them, so nothing is allocated for AMF0 values.
+/// Returns the name of the script data, like `onMetaData`.
+///
+/// AMF3 values need the `alloc` feature, they fail to parse otherwise. They
+/// are still built whole, as an `AMF3Value` given to `on_amf3`, which
+/// allocates for arrays and objects.
+pub fn visit_script_data<'a, V: ScriptDataVisitor<'a>>(
+  input: &'a [u8],
+  visitor: &mut V,
+) -> IResult<&'a [u8], &'a str, FlvError> {
+  let (i, marker) = be_u8(input)?;
+  if marker != 2 {
+    return Err(Err::Error(FlvError::Nom(ErrorKind::Tag)));
+  }
+  let (i, name) = script_data_string(i)?;
+  let (i, _) = visit_value(i, None, visitor)?;
+  Ok((i, name))
+}
+
+fn visit_value<'a, V: ScriptDataVisitor<'a>>(
+  input: &'a [u8],
+  name: Option<&'a str>,
+  visitor: &mut V,
+) -> IResult<&'a [u8], (), FlvError> {
+  let (i, marker) = be_u8(input)?;
+  let i = match marker {
+    0 => {
+      let (i, n) = be_f64(i)?;
+      visitor.on_number(name, n);
+      i
+    }
+    1 => {
+      let (i, b) = be_u8(i)?;
+      visitor.on_boolean(name, b != 0);
+      i
+    }
+    2 | 4 => {
+      let (i, s) = script_data_string(i)?;
+      visitor.on_string(name, s);
+      i
+    }
+    3 => {
+      visitor.enter_object(name, None);
+      visit_properties(i, visitor)?.0
+    }
+    5 => {
+      visitor.on_null(name);
+      i
+    }
+    6 => {
+      visitor.on_undefined(name);
+      i
+    }
+    7 => {
+      let (i, index) = be_u16(i)?;
+      visitor.on_reference(name, index);
+      i
+    }
+    8 => {
+      let (i, _) = be_u32(i)?;
+      visitor.enter_object(name, None);
+      visit_properties(i, visitor)?.0
+    }
+    10 => {
+      let (mut i, len) = be_u32(i)?;
+      visitor.enter_array(name, len);
+      for _ in 0..len {
+        i = visit_value(i, None, visitor)?.0;
+      }
+      visitor.exit_array();
+      i
+    }
+    11 => {
+      let (i, date) = script_data_date(i)?;
+      visitor.on_date(name, date);
+      i
+    }
+    12 => {
+      let (i, s) = script_data_long_string(i)?;
+      visitor.on_string(name, s);
+      i
+    }
+    16 => {
+      let (i, class_name) = script_data_string(i)?;
+      visitor.enter_object(name, Some(class_name));
+      visit_properties(i, visitor)?.0
+    }
+    #[cfg(feature = "alloc")]
+    17 => {
+      let (i, value) = script_data_amf3_value(i)?;
+      visitor.on_amf3(name, value);
+      i
+    }
+    _ => return Err(Err::Error(FlvError::Nom(ErrorKind::Alt))),
+  };
+  Ok((i, ()))
+}
+
+/// Visits properties up to the object end marker, then calls `exit_object`.
+fn visit_properties<'a, V: ScriptDataVisitor<'a>>(
+  input: &'a [u8],
+  visitor: &mut V,
+) -> IResult<&'a [u8], (), FlvError> {
+  let mut input = input;
+  loop {
+    match script_data_object_end(input) {
+      Ok((i, _)) => {
+        visitor.exit_object();
+        return Ok((i, ()));
+      }
+      Err(Err::Error(_)) => {}
+      Err(e) => return Err(e),
+    }
+    let (i, name) = script_data_string(input)?;
+    input = visit_value(i, Some(name), visitor)?.0;
+  }
+}
+
+#[allow(non_upper_case_globals)]
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::raw_tags;
+  #[cfg(feature = "alloc")]
+  use alloc::{vec, vec::Vec};
+
+  const commercials: &[u8] = include_bytes!("../assets/asian-commercials-are-weird.flv");
+
+  /// Keeps the top-level numbers and counts nesting.
+  #[derive(Default)]
+  struct Fields<'a> {
+    depth: usize,
+    max_depth: usize,
+    width: Option<f64>,
+    creation_date: Option<&'a str>,
+    events: usize,
+  }
+
+  impl<'a> ScriptDataVisitor<'a> for Fields<'a> {
+    fn on_number(&mut self, name: Option<&'a str>, value: f64) {
+      self.events += 1;
+      if self.depth == 1 && name == Some("width") {
+        self.width = Some(value);
+      }
+    }
+
+    fn on_boolean(&mut self, _name: Option<&'a str>, _value: bool) {
+      self.events += 1;
+    }
+
+    fn on_string(&mut self, name: Option<&'a str>, value: &'a str) {
+      self.events += 1;
+      if name == Some("creationdate") {
+        self.creation_date = Some(value);
+      }
+    }
+
+    fn enter_object(&mut self, _name: Option<&'a str>, _class_name: Option<&'a str>) {
+      self.depth += 1;
+      self.max_depth = self.max_depth.max(self.depth);
+    }
+
+    fn exit_object(&mut self) {
+      self.depth -= 1;
+    }
+
+    fn enter_array(&mut self, _name: Option<&'a str>, _len: u32) {
+      self.depth += 1;
+    }
+
+    fn exit_array(&mut self) {
+      self.depth -= 1;
+    }
+  }
+
+  #[test]
+  fn on_metadata() {
+    let body = raw_tags(commercials).next().unwrap().body().unwrap();
+    let mut fields = Fields::default();
+    let (rest, name) = visit_script_data(body, &mut fields).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(name, "onMetaData");
+    assert_eq!(fields.width, Some(464.0));
+    assert_eq!(fields.creation_date, Some("Thu Oct 04 18:37:42 2007\n"));
+    assert_eq!(fields.depth, 0);
+    assert_eq!(fields.max_depth, 1);
+    assert_eq!(fields.events, 11);
+  }
+
+  #[cfg(feature = "alloc")]
+  #[test]
+  fn nested_values() {
+    // ["a", {b: null}, typed Point {x: 1}]
+    let input = [
+      2, 0, 1, b's', 10, 0, 0, 0, 3, 2, 0, 1, b'a', 3, 0, 1, b'b', 5, 0, 0, 9, 16, 0, 5, b'P',
+      b'o', b'i', b'n', b't', 0, 1, b'x', 0, 0x3f, 0xf0, 0, 0, 0, 0, 0, 0, 0, 0, 9,
+    ];
+
+    #[derive(Default)]
+    struct Events<'a>(Vec<(&'static str, Option<&'a str>)>);
+
+    impl<'a> ScriptDataVisitor<'a> for Events<'a> {
+      fn on_number(&mut self, name: Option<&'a str>, _value: f64) {
+        self.0.push(("number", name));
+      }
+      fn on_string(&mut self, _name: Option<&'a str>, value: &'a str) {
+        self.0.push(("string", Some(value)));
+      }
+      fn on_null(&mut self, name: Option<&'a str>) {
+        self.0.push(("null", name));
+      }
+      fn enter_object(&mut self, _name: Option<&'a str>, class_name: Option<&'a str>) {
+        self.0.push(("enter object", class_name));
+      }
+      fn exit_object(&mut self) {
+        self.0.push(("exit object", None));
+      }
+      fn enter_array(&mut self, _name: Option<&'a str>, _len: u32) {
+        self.0.push(("enter array", None));
+      }
+      fn exit_array(&mut self) {
+        self.0.push(("exit array", None));
+      }
+    }
+
+    let mut events = Events::default();
+    assert_eq!(visit_script_data(&input, &mut events), Ok((&b""[..], "s")));
+    assert_eq!(
+      events.0,
+      vec![
+        ("enter array", None),
+        ("string", Some("a")),
+        ("enter object", None),
+        ("null", Some("b")),
+        ("exit object", None),
+        ("enter object", Some("Point")),
+        ("number", Some("x")),
+        ("exit object", None),
+        ("exit array", None),
+      ]
+    );
+
+    assert!(visit_script_data(&[2, 0, 1, b's', 42], &mut Events::default()).is_err());
+  }
+}
@@ -0,0 +1,191 @@
+use nom::number::streaming::{be_u24, be_u8};
+use nom::{Err, IResult};
+
+use crate::error::FlvError;
+use crate::parser::{CodecId, VideoData};
+
+/// The FLV header of a VP6 or VP6 with alpha video payload.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Vp6VideoPacket<'a> {
+  /// Pixels to crop from the right of the coded frame.
+  pub horizontal_adjust: u8,
+  /// Pixels to crop from the bottom of the coded frame.
+  pub vertical_adjust: u8,
+  /// For VP6 with alpha, where the alpha plane starts in `data`.
+  pub alpha_offset: Option<u32>,
+  /// The VP6 frame, followed by the alpha plane for VP6 with alpha.
+  pub data: &'a [u8],
+}
+
+impl<'a> Vp6VideoPacket<'a> {
+  /// The alpha plane of a VP6 with alpha packet.
+  pub fn alpha_data(&self) -> Option<&'a [u8]> {
+    self.data.get(self.alpha_offset? as usize..)
+  }
+}
+
+/// Parses the `video_data` of a VP6 or VP6 with alpha video tag, which
+/// extends to the end of `input`. Other codecs fail with
+/// `FlvError::UnexpectedCodecId`.
+pub fn vp6_video_packet(
+  input: &[u8],
+  codec_id: CodecId,
+) -> IResult<&[u8], Vp6VideoPacket<'_>, FlvError> {
+  let alpha = match codec_id {
+    CodecId::VP6 => false,
+    CodecId::VP6A => true,
+    _ => return Err(Err::Error(FlvError::UnexpectedCodecId(codec_id))),
+  };
+  let (i, adjustment) = be_u8(input)?;
+  let (i, alpha_offset) = if alpha {
+    let (i, offset) = be_u24(i)?;
+    (i, Some(offset))
+  } else {
+    (i, None)
+  };
+
+  Ok((
+    &i[i.len()..],
+    Vp6VideoPacket {
+      horizontal_adjust: adjustment >> 4,
+      vertical_adjust: adjustment & 0x0f,
+      alpha_offset,
+      data: i,
+    },
+  ))
+}
+
+/// What the VP6 frame header tells about a frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Vp6FrameInfo {
+  /// Whether the bitstream marks this as an intra frame. This is read from
+  /// the VP6 header, not from the FLV frame type.
+  pub is_keyframe: bool,
+  /// Displayed width and height, only carried by keyframes. The FLV
+  /// adjustment byte is applied to the coded macroblock size.
+  pub dimensions: Option<(u16, u16)>,
+}
+
+/// Reads the VP6 frame header of a VP6 or VP6 with alpha video tag.
+///
+/// The FLV payload starts with a byte giving how many pixels to crop from
+/// the right (high nibble) and bottom (low nibble) of the coded frame, and
+/// for VP6 with alpha, a 24 bits offset to the alpha plane. Returns `None`
+/// for other codecs or if the header is cut short.
+pub fn vp6_frame_info(video: &VideoData<'_>) -> Option<Vp6FrameInfo> {
+  let header_offset = match video.codec_id {
+    CodecId::VP6 => 1,
+    CodecId::VP6A => 4,
+    _ => return None,
+  };
+  let adjustment = *video.video_data.first()?;
+  let frame = video.video_data.get(header_offset..)?;
+
+  let is_keyframe = frame.first()? & 0x80 == 0;
+  if !is_keyframe {
+    return Some(Vp6FrameInfo {
+      is_keyframe,
+      dimensions: None,
+    });
+  }
+
+  let separated_coefficients = frame[0] & 1 != 0;
+  let filter_header = frame.get(1)? & 0x06;
+  // a 16 bits offset to the coefficients partition comes before the sizes
+  let sizes = if separated_coefficients || filter_header == 0 {
+    frame.get(4..6)?
+  } else {
+    frame.get(2..4)?
+  };
+  let (rows, cols) = (u16::from(sizes[0]), u16::from(sizes[1]));
+
+  Some(Vp6FrameInfo {
+    is_keyframe,
+    dimensions: Some((
+      (cols * 16).saturating_sub(u16::from(adjustment >> 4)),
+      (rows * 16).saturating_sub(u16::from(adjustment & 0x0f)),
+    )),
+  })
+}
+
+#[allow(non_upper_case_globals)]
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::{complete_tag, FrameType};
+
+  const commercials: &[u8] = include_bytes!("../assets/asian-commercials-are-weird.flv");
+
+  fn video_at(offset: usize) -> VideoData<'static> {
+    let (_, tag) = complete_tag(&commercials[offset..]).unwrap();
+    tag.data.as_video().unwrap().clone()
+  }
+
+  #[test]
+  fn commercials_dimensions() {
+    // 29x22 macroblocks, with 4 rows of pixels cropped to match the 464x348
+    // of onMetaData
+    let video = video_at(499);
+    assert_eq!(video.frame_type, FrameType::Key);
+    assert_eq!(
+      vp6_frame_info(&video),
+      Some(Vp6FrameInfo {
+        is_keyframe: true,
+        dimensions: Some((464, 348)),
+      })
+    );
+
+    let video = video_at(14289);
+    assert_eq!(video.frame_type, FrameType::Inter);
+    assert_eq!(
+      vp6_frame_info(&video),
+      Some(Vp6FrameInfo {
+        is_keyframe: false,
+        dimensions: None,
+      })
+    );
+  }
+
+  #[test]
+  fn not_vp6() {
+    let video = VideoData {
+      frame_type: FrameType::Key,
+      codec_id: CodecId::SORENSON_H263,
+      video_data: &[0, 0x78, 0x46, 0x16, 0x1d],
+    };
+    assert_eq!(vp6_frame_info(&video), None);
+
+    let video = VideoData {
+      codec_id: CodecId::VP6,
+      video_data: &[0, 0x78, 0x46],
+      ..video
+    };
+    assert_eq!(vp6_frame_info(&video), None);
+  }
+
+  #[test]
+  fn vp6_packets() {
+    let video = video_at(499);
+    let (rest, packet) = vp6_video_packet(video.video_data, video.codec_id).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(packet.horizontal_adjust, 0);
+    assert_eq!(packet.vertical_adjust, 4);
+    assert_eq!(packet.alpha_offset, None);
+    assert_eq!(packet.data, &video.video_data[1..]);
+    assert_eq!(packet.alpha_data(), None);
+
+    let (rest, packet) = vp6_video_packet(&[0x21, 0, 0, 2, 0x78, 0x46, 9], CodecId::VP6A).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(packet.horizontal_adjust, 2);
+    assert_eq!(packet.vertical_adjust, 1);
+    assert_eq!(packet.alpha_offset, Some(2));
+    assert_eq!(packet.data, &[0x78, 0x46, 9]);
+    assert_eq!(packet.alpha_data(), Some(&[9][..]));
+
+    assert!(vp6_video_packet(&[0x21, 0, 0], CodecId::VP6A).is_err());
+    assert_eq!(
+      vp6_video_packet(&[0x21], CodecId::SCREEN),
+      Err(Err::Error(FlvError::UnexpectedCodecId(CodecId::SCREEN)))
+    );
+  }
+}